@@ -0,0 +1,195 @@
+use crate::{
+    density::{log_density, write_histogram, RANDOM_SOURCE, WORKGROUP_SIZE},
+    palette::Palette,
+    scene::UnknownOption,
+};
+use num_complex::Complex;
+use std::{fmt::Write, str::FromStr};
+
+/// Points each invocation plots, after settling onto the attractor.
+pub const POINTS_PER_INVOCATION: u32 = 1024;
+
+/// Iterations each stream takes before plotting, so its path onto the
+/// attractor isn't drawn.
+pub const SETTLE_ITERATIONS: u32 = 64;
+
+/// Workgroups dispatched per batch of points.
+pub const WORKGROUPS_PER_BATCH: u32 = 1024;
+
+/// Points plotted by each dispatch.
+pub const POINTS_PER_BATCH: u64 =
+    WORKGROUP_SIZE as u64 * POINTS_PER_INVOCATION as u64 * WORKGROUPS_PER_BATCH as u64;
+
+/// The corner of the region the point streams start in.
+pub const START_CORNER: Complex<f32> = Complex { re: -0.1, im: -0.1 };
+
+/// The size of the region the point streams start in, small enough to be in
+/// every map's basin of attraction.
+pub const START_SIZE: Complex<f32> = Complex { re: 0.2, im: 0.2 };
+
+/// A two dimensional map with a strange attractor.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AttractorMap {
+    /// `x' = sin(a y) + c cos(a x)`, `y' = sin(b x) + d cos(b y)`
+    Clifford,
+    /// `x' = sin(a y) - cos(b x)`, `y' = sin(c x) - cos(d y)`
+    DeJong,
+    /// `x' = 1 - a x^2 + y`, `y' = b x`
+    Henon,
+}
+
+impl AttractorMap {
+    /// Parameters giving each map a well known attractor.
+    pub fn default_parameters(self) -> [f32; 4] {
+        match self {
+            AttractorMap::Clifford => [-1.4, 1.6, 1.0, 0.7],
+            AttractorMap::DeJong => [1.641, 1.902, 0.316, 1.525],
+            AttractorMap::Henon => [1.4, 0.3, 0.0, 0.0],
+        }
+    }
+
+    /// The WGSL expression for the image of `p`, using the parameters `a`,
+    /// `b`, `c` and `d`.
+    fn to_wgsl(self) -> &'static str {
+        match self {
+            AttractorMap::Clifford => {
+                "vec2<f32>(sin(a * p.y) + c * cos(a * p.x), sin(b * p.x) + d * cos(b * p.y))"
+            }
+            AttractorMap::DeJong => {
+                "vec2<f32>(sin(a * p.y) - cos(b * p.x), sin(c * p.x) - cos(d * p.y))"
+            }
+            AttractorMap::Henon => "vec2<f32>(1.0 - a * p.x * p.x + p.y, b * p.x)",
+        }
+    }
+}
+
+/// Plots the density of a strange attractor, accumulated by running many
+/// streams of points through its map in a compute pass.
+///
+/// The map's parameters are uniforms, so they can change between frames
+/// without generating a new shader.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Attractor {
+    pub map: AttractorMap,
+    /// The parameters `a`, `b`, `c` and `d` of the map.
+    pub parameters: [f32; 4],
+    /// The number of points plotted.
+    pub points: u64,
+    /// Colors the density.
+    pub palette: Palette,
+}
+
+impl Attractor {
+    /// The number of compute dispatches needed to plot every point.
+    pub fn batches(&self) -> u64 {
+        (self.points as f64 / POINTS_PER_BATCH as f64).ceil() as u64
+    }
+
+    /// Writes the histogram binding, random number generator and compute
+    /// stage after the template.
+    pub fn write_shader(&self, out: &mut String) {
+        write_histogram(out, 1);
+        out.push_str(RANDOM_SOURCE);
+
+        write!(
+            out,
+            r#"
+[[stage(compute), workgroup_size({workgroup_size})]]
+fn comp_main([[builtin(global_invocation_id)]] id: vec3<u32>) {{
+    rng_state = hash(id.x ^ hash(uniforms.sampling.seed));
+
+    let a = uniforms.attractor.parameters.x;
+    let b = uniforms.attractor.parameters.y;
+    let c = uniforms.attractor.parameters.z;
+    let d = uniforms.attractor.parameters.w;
+
+    var p = uniforms.sampling.sample_start + vec2<f32>(random(), random()) * uniforms.sampling.sample_size;
+    for (var i: u32 = 0u; i < {iterations}u; i = i + 1u) {{
+        p = {step};
+
+        // Stop streams that have left the basin of attraction, including any
+        // that have overflowed to infinity or NaN.
+        if (!(length_sqr(p) < 1000000.0)) {{
+            break;
+        }}
+
+        if (i >= {settle_iterations}u) {{
+            accumulate(p, 0u);
+        }}
+    }}
+}}
+"#,
+            workgroup_size = WORKGROUP_SIZE,
+            iterations = SETTLE_ITERATIONS + POINTS_PER_INVOCATION,
+            settle_iterations = SETTLE_ITERATIONS,
+            step = self.map.to_wgsl(),
+        )
+        .unwrap();
+    }
+}
+
+impl FromStr for AttractorMap {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clifford" => Ok(AttractorMap::Clifford),
+            "de-jong" => Ok(AttractorMap::DeJong),
+            "henon" => Ok(AttractorMap::Henon),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+/// Maps histogram counts to RGBA8 pixels.
+///
+/// Counts are compressed logarithmically and normalized so the densest pixel
+/// takes the last color of the palette.
+pub fn tone_map(counts: &[u32], palette: Palette) -> Vec<u8> {
+    let max = counts.iter().copied().max().unwrap_or(0);
+
+    let mut image = Vec::with_capacity(counts.len() * 4);
+    for &count in counts {
+        image.extend_from_slice(&palette.color(log_density(count, max)));
+        image.push(255);
+    }
+    image
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        attractor::{tone_map, Attractor, AttractorMap, POINTS_PER_BATCH},
+        palette::Palette,
+    };
+
+    #[test]
+    fn tone_map_uses_palette() {
+        let image = tone_map(&[0, 1, 3], Palette::Grayscale);
+        assert_eq!(
+            image,
+            vec![0, 0, 0, 255, 128, 128, 128, 255, 255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn batches_cover_points() {
+        let mut attractor = Attractor {
+            map: AttractorMap::Clifford,
+            parameters: AttractorMap::Clifford.default_parameters(),
+            points: 2 * POINTS_PER_BATCH,
+            palette: Palette::Fire,
+        };
+        assert_eq!(attractor.batches(), 2);
+        attractor.points += 1;
+        assert_eq!(attractor.batches(), 3);
+    }
+
+    #[test]
+    fn parse_attractor_map() {
+        assert_eq!("de-jong".parse(), Ok(AttractorMap::DeJong));
+        assert!("lorenz".parse::<AttractorMap>().is_err());
+    }
+}
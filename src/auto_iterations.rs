@@ -0,0 +1,193 @@
+use crate::{
+    interior_check::InteriorChecks,
+    scene::{Scene, UnknownOption},
+    view::View,
+};
+use num_complex::Complex;
+use std::str::FromStr;
+
+/// Chooses the iteration limit of an escape-time render from its view rather
+/// than a fixed setting.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AutoIterations {
+    /// Grows the limit with how far the view is zoomed in from the default
+    /// view, measured by the size of a pixel at its center.
+    ZoomDepth,
+    /// Iterates a coarse grid of the view's pixels on the CPU, doubling the
+    /// limit until doing so no longer changes how many pixels reach it.
+    Probe,
+}
+
+/// The smallest limit either method picks.
+pub const MIN_ITERATIONS: u32 = 64;
+/// The largest limit either method picks.
+pub const MAX_ITERATIONS: u32 = 16384;
+
+/// The limit [`AutoIterations::ZoomDepth`] picks for the default view.
+const BASE_ITERATIONS: f32 = 200.0;
+/// The iterations [`AutoIterations::ZoomDepth`] adds each time pixels halve
+/// in size.
+const ITERATIONS_PER_OCTAVE: f32 = 50.0;
+/// The plane width of the default view, which has zoom depth 0.
+const BASE_PLANE_WIDTH: f32 = 3.0;
+
+/// Side length of the grid of pixels [`AutoIterations::Probe`] iterates.
+const PROBE_GRID: usize = 48;
+/// The change in the fraction of sampled pixels reaching the limit small
+/// enough for doubling it to count as having stabilized.
+const STABLE_FRACTION: f32 = 0.002;
+
+impl AutoIterations {
+    /// The iteration limit to render `view` with.
+    pub fn choose(&self, scene: &Scene, view: &View) -> u32 {
+        match self {
+            AutoIterations::ZoomDepth => {
+                let iterations = BASE_ITERATIONS + ITERATIONS_PER_OCTAVE * zoom_depth(scene, view);
+                (iterations.round() as u32).clamp(MIN_ITERATIONS, MAX_ITERATIONS)
+            }
+            AutoIterations::Probe => probe(scene, view),
+        }
+    }
+}
+
+/// The number of times pixels at the center of the view have halved in size
+/// compared to the default view, or 0 if the projection misses them.
+fn zoom_depth(scene: &Scene, view: &View) -> f32 {
+    let (x, y) = (view.image_width / 2, view.image_height / 2);
+    let point = |pixel| {
+        view.get_transformed_plane_coordinates(pixel, &scene.projection, &scene.plane_transforms)
+    };
+    match (point((x, y)), point((x + 1, y))) {
+        (Some(center), Some(next)) if center != next => {
+            let base_size = BASE_PLANE_WIDTH / view.image_width as f32;
+            (base_size / (next - center).norm()).log2()
+        }
+        _ => 0.0,
+    }
+}
+
+/// Doubles the limit from [`MIN_ITERATIONS`] until the fraction of probed
+/// pixels reaching it stabilizes.
+fn probe(scene: &Scene, view: &View) -> u32 {
+    let starts = scene.orbit_starts(view, PROBE_GRID);
+    raise_limit(scene, &starts, MIN_ITERATIONS, |_| false)
+}
+
+/// Doubles `limit` until `enough` accepts the fraction of `starts` reaching
+/// it, or that fraction changes by less than [`STABLE_FRACTION`] from one
+/// limit to the next, up to [`MAX_ITERATIONS`]. Only the points that reached
+/// the previous limit are iterated again each time.
+pub fn raise_limit(
+    scene: &Scene,
+    starts: &[(Complex<f32>, Complex<f32>)],
+    limit: u32,
+    enough: impl Fn(f32) -> bool,
+) -> u32 {
+    if starts.is_empty() {
+        return limit;
+    }
+    let count = |(z, c): (Complex<f32>, Complex<f32>), limit| {
+        InteriorChecks::NONE.count_iterations(scene, z, c, limit)
+    };
+
+    let mut limit = limit;
+    let mut counts: Vec<u32> = starts.iter().map(|&start| count(start, limit)).collect();
+    let reached = |counts: &[u32], limit| counts.iter().filter(|&&n| n >= limit).count();
+    while limit < MAX_ITERATIONS {
+        let fraction = reached(&counts, limit) as f32 / starts.len() as f32;
+        if enough(fraction) {
+            return limit;
+        }
+
+        let next = (limit * 2).min(MAX_ITERATIONS);
+        for (n, &start) in counts.iter_mut().zip(starts) {
+            if *n >= limit {
+                *n = count(start, next);
+            }
+        }
+
+        let change = fraction - reached(&counts, next) as f32 / starts.len() as f32;
+        if change < STABLE_FRACTION {
+            return limit;
+        }
+        limit = next;
+    }
+    limit
+}
+
+impl FromStr for AutoIterations {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zoom-depth" => Ok(AutoIterations::ZoomDepth),
+            "probe" => Ok(AutoIterations::Probe),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        auto_iterations::{AutoIterations, MAX_ITERATIONS, MIN_ITERATIONS},
+        plane_transform::PlaneTransform,
+        scene::Scene,
+        view::View,
+    };
+    use num_complex::Complex;
+
+    fn mandelbrot(plane_transforms: Vec<PlaneTransform>) -> Scene {
+        Scene {
+            plane_transforms,
+            ..Scene::test_mandelbrot()
+        }
+    }
+
+    /// Zooms in on a point by `zoom` times.
+    fn zoom(center: Complex<f32>, zoom: f32) -> PlaneTransform {
+        PlaneTransform::Mobius {
+            a: Complex::new(1.0 / zoom, 0.0),
+            b: center,
+            c: Complex::new(0.0, 0.0),
+            d: Complex::new(1.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn zoom_depth() {
+        let view = View::new_centered_uniform(200, 200, 3.0);
+        let depth = AutoIterations::ZoomDepth;
+        assert_eq!(depth.choose(&mandelbrot(vec![]), &view), 200);
+
+        let zoomed = mandelbrot(vec![zoom(Complex::new(-0.75, 0.1), 1024.0)]);
+        assert_eq!(depth.choose(&zoomed, &view), 700);
+
+        let zoomed_out = mandelbrot(vec![zoom(Complex::new(0.0, 0.0), 1.0 / 1024.0)]);
+        assert_eq!(depth.choose(&zoomed_out, &view), MIN_ITERATIONS);
+    }
+
+    #[test]
+    fn probe() {
+        let view = View::new_centered_uniform(200, 200, 3.0);
+        let probe = AutoIterations::Probe;
+        let shallow = probe.choose(&mandelbrot(vec![]), &view);
+        assert!(
+            shallow > MIN_ITERATIONS && shallow < MAX_ITERATIONS,
+            "{}",
+            shallow
+        );
+
+        // The edge of the seahorse valley needs more iterations than the
+        // whole set.
+        let seahorse = mandelbrot(vec![zoom(Complex::new(-0.745, 0.1), 100.0)]);
+        let deep = probe.choose(&seahorse, &view);
+        assert!(deep > shallow, "{} <= {}", deep, shallow);
+
+        // Every pixel of a view inside the main cardioid reaches any limit.
+        let interior = mandelbrot(vec![zoom(Complex::new(-0.1, 0.1), 100.0)]);
+        assert_eq!(probe.choose(&interior, &view), MIN_ITERATIONS);
+    }
+}
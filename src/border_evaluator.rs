@@ -0,0 +1,151 @@
+use crate::{
+    generator::BORDER_WORKGROUP_SIZE,
+    gpu::{Gpu, SceneBinding},
+    scene::Scene,
+    uniforms::Uniforms,
+};
+use std::{mem::size_of, num::NonZeroU64};
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferAddress, BufferBinding,
+    BufferBindingType, BufferDescriptor, BufferUsage, CommandEncoderDescriptor,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, PipelineLayoutDescriptor,
+    ShaderModule, ShaderStage,
+};
+
+/// Colors lists of pixels with the `border_main` compute stage, for
+/// Mariani–Silver rendering. The positions and colors are bound after the
+/// uniforms and the image trap's texture, if there is one.
+pub struct BorderEvaluator {
+    scene_binding: SceneBinding,
+    bind_group_layout: BindGroupLayout,
+    pipeline: ComputePipeline,
+}
+
+impl BorderEvaluator {
+    pub fn new(
+        gpu: &Gpu,
+        module: &ShaderModule,
+        uniforms: Uniforms,
+        scene: &Scene,
+    ) -> BorderEvaluator {
+        let device = &gpu.device;
+        let scene_binding = gpu.bind_scene(uniforms, scene, ShaderStage::COMPUTE);
+
+        let storage_entry = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(size_of::<u32>() as u64),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Border bind group layout"),
+            entries: &[storage_entry(0, true), storage_entry(1, false)],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Border Pipeline Layout"),
+            bind_group_layouts: &scene_binding.layouts(Some(&bind_group_layout)),
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Border Pipeline"),
+            layout: Some(&pipeline_layout),
+            module,
+            entry_point: "border_main",
+        });
+
+        BorderEvaluator {
+            scene_binding,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    /// Colors pixels at positions within the image, returning their RGBA8
+    /// colors packed into `u32`s in the same order.
+    pub async fn evaluate(&self, gpu: &Gpu, positions: &[(usize, usize)]) -> Vec<u32> {
+        if positions.is_empty() {
+            return vec![];
+        }
+        let device = &gpu.device;
+        let size = (positions.len() * size_of::<u32>()) as BufferAddress;
+
+        let positions_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Border Positions"),
+            size,
+            usage: BufferUsage::STORAGE,
+            mapped_at_creation: true,
+        });
+        {
+            let packed: Vec<u32> = positions
+                .iter()
+                .map(|&(x, y)| x as u32 | ((y as u32) << 16))
+                .collect();
+            positions_buffer
+                .slice(..)
+                .get_mapped_range_mut()
+                .copy_from_slice(bytemuck::cast_slice(&packed));
+        }
+        positions_buffer.unmap();
+
+        let colors_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Border Colors"),
+            size,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Border Colors Read Buffer"),
+            size,
+            usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Border bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &positions_buffer,
+                        offset: 0,
+                        size: None,
+                    }),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &colors_buffer,
+                        offset: 0,
+                        size: None,
+                    }),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Border Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Border Pass"),
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            self.scene_binding
+                .set_compute(&mut compute_pass, &bind_group);
+            let workgroups =
+                (positions.len() as u32 + BORDER_WORKGROUP_SIZE - 1) / BORDER_WORKGROUP_SIZE;
+            compute_pass.dispatch(workgroups, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&colors_buffer, 0, &read_buffer, 0, size);
+        gpu.queue.submit(Some(encoder.finish()));
+
+        bytemuck::cast_slice(&gpu.read_buffer(&read_buffer).await).to_vec()
+    }
+}
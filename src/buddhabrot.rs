@@ -0,0 +1,158 @@
+use crate::{
+    density::{log_density, write_histogram, RANDOM_SOURCE, WORKGROUP_SIZE},
+    generator::{complex_literal, write_formula},
+    scene::Scene,
+};
+use num_complex::Complex;
+use std::fmt::Write;
+
+/// Values of `c` sampled by each invocation.
+pub const SAMPLES_PER_INVOCATION: u32 = 16;
+
+/// Workgroups dispatched per batch of samples.
+pub const WORKGROUPS_PER_BATCH: u32 = 4096;
+
+/// Values of `c` sampled by each dispatch.
+pub const SAMPLES_PER_BATCH: u64 =
+    WORKGROUP_SIZE as u64 * SAMPLES_PER_INVOCATION as u64 * WORKGROUPS_PER_BATCH as u64;
+
+/// Renders the density of escaping orbits, accumulated by sampling random
+/// values of `c` in a compute pass.
+///
+/// Each color channel counts the orbits that escape within its own iteration
+/// limit, so equal limits give the classic Buddhabrot and differing ones a
+/// Nebulabrot.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Buddhabrot {
+    /// The iteration limits of the red, green and blue channels.
+    pub iteration_limits: [u32; 3],
+    /// The number of `c` values sampled.
+    pub samples: u64,
+    /// The corner of the region `c` is sampled from.
+    pub sample_start: Complex<f32>,
+    /// The size of the region `c` is sampled from.
+    pub sample_size: Complex<f32>,
+}
+
+impl Buddhabrot {
+    /// The number of compute dispatches needed to take every sample.
+    pub fn batches(&self) -> u64 {
+        (self.samples as f64 / SAMPLES_PER_BATCH as f64).ceil() as u64
+    }
+
+    /// Writes the histogram binding, random number generator and compute
+    /// stage after the template.
+    pub fn write_shader(&self, out: &mut String, scene: &Scene) {
+        write_formula(out, scene);
+        write_histogram(out, 3);
+        out.push_str(RANDOM_SOURCE);
+
+        let max_iterations = self.iteration_limits.iter().max().unwrap();
+        write!(
+            out,
+            r#"
+[[stage(compute), workgroup_size({workgroup_size})]]
+fn comp_main([[builtin(global_invocation_id)]] id: vec3<u32>) {{
+    rng_state = hash(id.x ^ hash(uniforms.sampling.seed));
+
+    for (var sample: u32 = 0u; sample < {samples}u; sample = sample + 1u) {{
+        let c = uniforms.sampling.sample_start + vec2<f32>(random(), random()) * uniforms.sampling.sample_size;
+
+        // Find when the orbit escapes, if it does.
+        var z = {critical_point};
+        var previous_z = vec2<f32>(0.0, 0.0);
+        var n: i32 = 0;
+        for (; n < {max_iterations}; n = n + 1) {{
+            if (length_sqr(z) > bailout * bailout) {{
+                break;
+            }}
+            let z_next = f(z, previous_z, c, n);
+            previous_z = z;
+            z = z_next;
+        }}
+        if (n >= {max_iterations}) {{
+            continue;
+        }}
+
+        // Retrace the orbit, counting it in every channel whose limit it
+        // escaped within.
+        z = {critical_point};
+        previous_z = vec2<f32>(0.0, 0.0);
+        for (var i: i32 = 0; i < n; i = i + 1) {{
+            let z_next = f(z, previous_z, c, i);
+            previous_z = z;
+            z = z_next;
+"#,
+            workgroup_size = WORKGROUP_SIZE,
+            samples = SAMPLES_PER_INVOCATION,
+            critical_point = complex_literal(scene.formula.critical_point),
+            max_iterations = max_iterations,
+        )
+        .unwrap();
+        for (channel, limit) in self.iteration_limits.iter().enumerate() {
+            writeln!(
+                out,
+                "            if (n < {}) {{\n                accumulate(z, {}u);\n            }}",
+                limit, channel
+            )
+            .unwrap();
+        }
+        out.push_str("        }\n    }\n}\n");
+    }
+}
+
+/// Maps RGB histogram counts to RGBA8 pixels.
+///
+/// Counts are compressed logarithmically and normalized per channel so each
+/// channel's densest pixel is at full brightness.
+pub fn tone_map(counts: &[u32]) -> Vec<u8> {
+    let mut max = [0u32; 3];
+    for pixel in counts.chunks(3) {
+        for (channel, &count) in pixel.iter().enumerate() {
+            max[channel] = max[channel].max(count);
+        }
+    }
+
+    let mut image = Vec::with_capacity(counts.len() / 3 * 4);
+    for pixel in counts.chunks(3) {
+        for (channel, &count) in pixel.iter().enumerate() {
+            let value = log_density(count, max[channel]);
+            // A square root lifts the faint outer orbits out of the dark.
+            image.push((value.sqrt() * 255.0).round() as u8);
+        }
+        image.push(255);
+    }
+    image
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::buddhabrot::{tone_map, Buddhabrot, SAMPLES_PER_BATCH};
+    use num_complex::Complex;
+
+    #[test]
+    fn tone_map_normalizes_channels() {
+        let image = tone_map(&[0, 10, 4, 100, 10, 1]);
+        assert_eq!(image, vec![0, 255, 255, 255, 255, 255, 167, 255]);
+    }
+
+    #[test]
+    fn tone_map_empty_histogram() {
+        assert_eq!(tone_map(&[0, 0, 0]), vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn batches_cover_samples() {
+        let mut buddhabrot = Buddhabrot {
+            iteration_limits: [1000; 3],
+            samples: SAMPLES_PER_BATCH,
+            sample_start: Complex::new(-2.0, -2.0),
+            sample_size: Complex::new(4.0, 4.0),
+        };
+        assert_eq!(buddhabrot.batches(), 1);
+        buddhabrot.samples += 1;
+        assert_eq!(buddhabrot.batches(), 2);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::view::View;
+use cgmath::{InnerSpace, Vector3};
+
+/// A pinhole camera looking into a 3D scene.
+///
+/// Rays leave the camera through an image plane one unit in front of it. The
+/// image plane is mapped onto pixels by a [`View`], so 3D renders can be
+/// divided into tiles the same way as 2D ones.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Camera {
+    pub position: Vector3<f32>,
+    /// Rotation about the vertical axis in degrees, with 0 looking along +z.
+    pub yaw: f32,
+    /// Rotation above the horizon in degrees.
+    pub pitch: f32,
+    /// The horizontal field of view in degrees.
+    pub field_of_view: f32,
+}
+
+/// The camera's axes in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraBasis {
+    pub right: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub forward: Vector3<f32>,
+}
+
+impl Camera {
+    /// Gets the direction the camera looks in and the directions of the
+    /// image plane's axes.
+    pub fn basis(&self) -> CameraBasis {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        let forward = Vector3::new(
+            yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            yaw.cos() * pitch.cos(),
+        );
+        let right = Vector3::unit_y().cross(forward).normalize();
+        let up = forward.cross(right);
+
+        CameraBasis { right, up, forward }
+    }
+
+    /// Creates the view of the image plane covered by the field of view.
+    pub fn view(&self, image_width: usize, image_height: usize) -> View {
+        let plane_width = 2.0 * (self.field_of_view.to_radians() / 2.0).tan();
+        View::new_centered_uniform(image_width, image_height, plane_width)
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Camera;
+    use cgmath::{InnerSpace, Vector3};
+
+    fn camera(yaw: f32, pitch: f32) -> Camera {
+        Camera {
+            position: Vector3::new(0.0, 0.0, -3.0),
+            yaw,
+            pitch,
+            field_of_view: 90.0,
+        }
+    }
+
+    #[test]
+    fn basis_looks_along_z() {
+        let basis = camera(0.0, 0.0).basis();
+        assert!((basis.right - Vector3::unit_x()).magnitude() < 1e-6);
+        assert!((basis.up - Vector3::unit_y()).magnitude() < 1e-6);
+        assert!((basis.forward - Vector3::unit_z()).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn basis_is_orthonormal() {
+        let basis = camera(30.0, -20.0).basis();
+        for axis in &[basis.right, basis.up, basis.forward] {
+            assert!((axis.magnitude() - 1.0).abs() < 1e-6);
+        }
+        assert!(basis.right.dot(basis.up).abs() < 1e-6);
+        assert!(basis.right.dot(basis.forward).abs() < 1e-6);
+        assert!(basis.up.dot(basis.forward).abs() < 1e-6);
+    }
+
+    #[test]
+    fn view_covers_field_of_view() {
+        let view = camera(0.0, 0.0).view(100, 50);
+        assert!((view.plane_start_x + 1.0).abs() < 1e-6);
+        assert!((view.plane_start_y + 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,579 @@
+use crate::{generator::TRAP_TEXTURE_GROUP, scene::UnknownOption};
+use num_complex::Complex;
+use std::{fmt::Write, path::PathBuf, str::FromStr};
+
+/// Describes how points that escape are colored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExteriorColoring {
+    /// Colors by the iteration at which the point escaped.
+    Iteration,
+    /// Colors by how closely the point's orbit approached a trap shape.
+    OrbitTrap(OrbitTrap),
+    /// Composites an image placed on the complex plane wherever the point's
+    /// orbit lands inside it.
+    ImageTrap(ImageTrap),
+    /// Colors by a statistic averaged over the point's orbit, interpolated
+    /// using the final iteration's escape fraction.
+    Average(OrbitAverage),
+}
+
+/// Describes how points that never escape are colored.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InteriorColoring {
+    /// Paints every interior point black.
+    Black,
+    /// Colors by the magnitude of the final `z`.
+    Magnitude { scale: f32 },
+    /// Colors by the period of the attracting cycle the orbit settled into.
+    Period(CycleDetection),
+    /// Colors by an estimate of the distance to the boundary, computed from
+    /// the attracting cycle.
+    DistanceEstimate { cycle: CycleDetection, scale: f32 },
+    /// Colors by the iteration at which `|z|` was smallest.
+    AtomDomain,
+}
+
+/// Parameters used to find the attracting cycle of an interior point.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CycleDetection {
+    /// Distance within which `z` counts as having returned to itself.
+    pub epsilon: f32,
+    /// Longest cycle searched for.
+    pub max_period: u32,
+}
+
+/// A shape that orbits are measured against.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TrapShape {
+    Point,
+    Line,
+    Circle,
+    Cross,
+}
+
+/// Describes what an orbit trap's coloring is based on.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TrapColoring {
+    /// Colors by the smallest distance between the orbit and the trap.
+    Distance,
+    /// Colors by the iteration at which the smallest distance occurred.
+    Iteration,
+    /// Uses the iteration for hue and the distance for brightness.
+    DistanceAndIteration,
+}
+
+/// An orbit trap's shape and placement on the complex plane.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OrbitTrap {
+    pub shape: TrapShape,
+    pub coloring: TrapColoring,
+    pub position: Complex<f32>,
+    /// Rotation of line and cross traps, in radians.
+    pub angle: f32,
+    /// Radius of circle traps.
+    pub radius: f32,
+    /// Multiplier applied to trap distances before they are turned into colors.
+    pub scale: f32,
+}
+
+/// Describes which of an orbit's hits on an image trap is shown.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TrapBlend {
+    FirstHit,
+    LastHit,
+}
+
+/// An image mapped onto a rectangle of the complex plane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTrap {
+    pub path: PathBuf,
+    pub blend: TrapBlend,
+    /// The corner of the image's rectangle with the smallest coordinates.
+    pub position: Complex<f32>,
+    /// The width and height of the image's rectangle.
+    pub size: Complex<f32>,
+    /// Samples with alpha at or below this value don't count as hits.
+    pub alpha_threshold: f32,
+}
+
+/// A per-iteration statistic that averaging colorings accumulate.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AverageStatistic {
+    /// Stripe average, `sin(density * arg(z))` mapped onto `[0, 1]`.
+    Stripe,
+    /// Triangle inequality average, where `|z|` lies between the bounds given
+    /// by the previous `z` and `c`.
+    TriangleInequality,
+    /// Curvature average, the angle between consecutive orbit steps.
+    Curvature,
+}
+
+/// An averaging coloring's statistic and parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OrbitAverage {
+    pub statistic: AverageStatistic,
+    /// Number of stripes per turn around the origin.
+    pub stripe_density: f32,
+    /// Number of initial iterations left out of the average.
+    pub skip_iterations: u32,
+}
+
+/// A variable a coloring keeps across loop iterations, which multi-pass
+/// rendering saves between passes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LoopVariable {
+    pub name: &'static str,
+    /// The WGSL type, one storage buffers can hold.
+    pub ty: &'static str,
+    /// The WGSL expression the variable starts as.
+    pub initial: &'static str,
+}
+
+impl LoopVariable {
+    const fn new(name: &'static str, ty: &'static str, initial: &'static str) -> LoopVariable {
+        LoopVariable { name, ty, initial }
+    }
+}
+
+const ORBIT_TRAP_STATE: &[LoopVariable] = &[
+    LoopVariable::new("closest_distance", "f32", "1000000.0"),
+    LoopVariable::new("closest_iteration", "i32", "0"),
+];
+
+// Storage buffers can't hold bools, so whether the trap was hit is a u32.
+const IMAGE_TRAP_STATE: &[LoopVariable] = &[
+    LoopVariable::new("trap_hit", "u32", "0u"),
+    LoopVariable::new("trap_color", "vec4<f32>", "vec4<f32>(0.0, 0.0, 0.0, 0.0)"),
+];
+
+const AVERAGE_STATE: &[LoopVariable] = &[
+    LoopVariable::new("sum", "f32", "0.0"),
+    LoopVariable::new("previous_sum", "f32", "0.0"),
+    LoopVariable::new("count", "f32", "0.0"),
+    LoopVariable::new("z_older", "vec2<f32>", "z"),
+];
+
+const ATOM_DOMAIN_STATE: &[LoopVariable] = &[
+    LoopVariable::new("atom_distance", "f32", "1000000.0"),
+    LoopVariable::new("atom_iteration", "i32", "0"),
+];
+
+impl ExteriorColoring {
+    /// Writes any helper functions this coloring needs into the shader.
+    pub fn write_functions(&self, out: &mut String) {
+        match self {
+            ExteriorColoring::OrbitTrap(trap) => trap.shape.write_distance_function(out),
+            ExteriorColoring::Average(average) => average.statistic.write_function(out),
+            ExteriorColoring::ImageTrap(_) => write!(
+                out,
+                r#"
+[[group({group}), binding(0)]]
+var trap_texture: texture_2d<f32>;
+[[group({group}), binding(1)]]
+var trap_sampler: sampler;
+"#,
+                group = TRAP_TEXTURE_GROUP
+            )
+            .unwrap(),
+            _ => {}
+        }
+    }
+
+    /// The variables this coloring keeps across loop iterations.
+    pub fn loop_state(&self) -> &'static [LoopVariable] {
+        match self {
+            ExteriorColoring::Iteration => &[],
+            ExteriorColoring::OrbitTrap(_) => ORBIT_TRAP_STATE,
+            ExteriorColoring::ImageTrap(_) => IMAGE_TRAP_STATE,
+            ExteriorColoring::Average(_) => AVERAGE_STATE,
+        }
+    }
+
+    /// Writes the variables this coloring keeps across loop iterations.
+    pub fn write_loop_state(&self, out: &mut String) {
+        write_loop_variables(out, self.loop_state());
+    }
+
+    /// Writes the statements run on each iteration before `z` is advanced.
+    pub fn write_iteration(&self, out: &mut String) {
+        match self {
+            ExteriorColoring::OrbitTrap(_) => out.push_str(
+                r#"        let distance = orbit_trap_distance(z);
+        if (distance < closest_distance) {
+            closest_distance = distance;
+            closest_iteration = n;
+        }
+"#,
+            ),
+            ExteriorColoring::ImageTrap(trap) => {
+                // The first hit never gets replaced, so later samples can be skipped.
+                let condition = match trap.blend {
+                    TrapBlend::FirstHit => "trap_hit == 0u && inside",
+                    TrapBlend::LastHit => "inside",
+                };
+                write!(
+                    out,
+                    r#"        let uv = (z - uniforms.image_trap.position) / uniforms.image_trap.size;
+        let inside = uv.x >= 0.0 && uv.x < 1.0 && uv.y >= 0.0 && uv.y < 1.0;
+        if ({}) {{
+            let sample = textureSampleLevel(trap_texture, trap_sampler, uv, 0.0);
+            if (sample.a > uniforms.image_trap.alpha_threshold) {{
+                trap_hit = 1u;
+                trap_color = sample;
+            }}
+        }}
+"#,
+                    condition
+                )
+                .unwrap();
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes the statements run on each iteration after `z` is advanced.
+    pub fn write_after_step(&self, out: &mut String) {
+        if let ExteriorColoring::Average(average) = self {
+            // Curvature needs two previous steps, so it can't start on the first iteration.
+            let (statistic, start) = match average.statistic {
+                AverageStatistic::Stripe => (
+                    "0.5 * sin(uniforms.average.stripe_density * atan2(z.y, z.x)) + 0.5",
+                    "uniforms.average.skip_iterations",
+                ),
+                AverageStatistic::TriangleInequality => (
+                    "triangle_inequality(z, previous_z, c)",
+                    "uniforms.average.skip_iterations",
+                ),
+                AverageStatistic::Curvature => (
+                    "curvature(z, previous_z, z_older)",
+                    "max(uniforms.average.skip_iterations, 1)",
+                ),
+            };
+
+            write!(
+                out,
+                r#"        if (n >= {}) {{
+            previous_sum = sum;
+            sum = sum + {};
+            count = count + 1.0;
+        }}
+        z_older = previous_z;
+"#,
+                start, statistic
+            )
+            .unwrap();
+        }
+    }
+
+    /// Writes the statements that return the color of an escaped point.
+    pub fn write_color(&self, out: &mut String) {
+        match self {
+            ExteriorColoring::Iteration => out.push_str(
+                r#"        let v = f32(n);
+        return fromHSB((v * 3.3 / 256.0) % 1.0, 1.0, (v / 16.0) % 1.0, 1.0);
+"#,
+            ),
+            ExteriorColoring::OrbitTrap(trap) => {
+                out.push_str("        let distance = closest_distance * uniforms.trap.scale;\n");
+                out.push_str("        let v = f32(closest_iteration);\n");
+                let (hue, brightness) = match trap.coloring {
+                    TrapColoring::Distance => ("distance % 1.0", "1.0 / (1.0 + distance)"),
+                    TrapColoring::Iteration => ("(v * 3.3 / 256.0) % 1.0", "1.0"),
+                    TrapColoring::DistanceAndIteration => {
+                        ("(v * 3.3 / 256.0) % 1.0", "1.0 / (1.0 + distance)")
+                    }
+                };
+                writeln!(
+                    out,
+                    "        return fromHSB({}, 1.0, {}, 1.0);",
+                    hue, brightness
+                )
+                .unwrap();
+            }
+            ExteriorColoring::ImageTrap(_) => out.push_str(
+                r#"        let v = f32(n);
+        let base = fromHSB((v * 3.3 / 256.0) % 1.0, 1.0, (v / 16.0) % 1.0, 1.0);
+        if (trap_hit != 0u) {
+            let alpha = trap_color.a;
+            return vec4<f32>(mix(base.rgb, trap_color.rgb, vec3<f32>(alpha, alpha, alpha)), 1.0);
+        }
+        return base;
+"#,
+            ),
+            ExteriorColoring::Average(_) => out.push_str(
+                r#"        // Interpolate between the averages with and without the last iteration.
+        let fraction = clamp(1.0 + log2(log(bailout) / log(length(z))), 0.0, 1.0);
+        let average = sum / max(count, 1.0);
+        let previous_average = previous_sum / max(count - 1.0, 1.0);
+        let v = mix(previous_average, average, fraction);
+        return fromHSB(v, 1.0, 1.0, 1.0);
+"#,
+            ),
+        }
+    }
+}
+
+impl InteriorColoring {
+    /// Writes any helper functions this coloring needs into the shader.
+    pub fn write_functions(&self, out: &mut String) {
+        match self {
+            InteriorColoring::Period(_) => out.push_str(FIND_PERIOD_SOURCE),
+            InteriorColoring::DistanceEstimate { .. } => {
+                out.push_str(FIND_PERIOD_SOURCE);
+                out.push_str(INTERIOR_DISTANCE_SOURCE);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether raising the iteration limit can change the color of a point
+    /// that reaches it, as it does for colorings taken from where the orbit
+    /// stopped rather than from the cycle it settled into.
+    pub fn depends_on_limit(&self) -> bool {
+        matches!(
+            self,
+            InteriorColoring::Magnitude { .. } | InteriorColoring::AtomDomain
+        )
+    }
+
+    /// The variables this coloring keeps across loop iterations.
+    pub fn loop_state(&self) -> &'static [LoopVariable] {
+        match self {
+            InteriorColoring::AtomDomain => ATOM_DOMAIN_STATE,
+            _ => &[],
+        }
+    }
+
+    /// Writes the variables this coloring keeps across loop iterations.
+    pub fn write_loop_state(&self, out: &mut String) {
+        write_loop_variables(out, self.loop_state());
+    }
+
+    /// Writes the statements run on each iteration before `z` is advanced.
+    pub fn write_iteration(&self, out: &mut String) {
+        if let InteriorColoring::AtomDomain = self {
+            out.push_str(
+                r#"        if (n > 0 && length_sqr(z) < atom_distance) {
+            atom_distance = length_sqr(z);
+            atom_iteration = n;
+        }
+"#,
+            );
+        }
+    }
+
+    /// Writes the statements that return the color of a point that never
+    /// escaped.
+    pub fn write_color(&self, out: &mut String) {
+        out.push_str(match self {
+            InteriorColoring::Black => "        return vec4<f32>(0.0, 0.0, 0.0, 1.0);\n",
+            InteriorColoring::Magnitude { .. } => {
+                r#"        let v = length(z) * uniforms.interior.scale;
+        return fromHSB(v % 1.0, 1.0, 1.0, 1.0);
+"#
+            }
+            InteriorColoring::Period(_) => {
+                r#"        let period = find_period(z, previous_z, c, n);
+        if (period == 0) {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+        return fromHSB((f32(period) * 0.13) % 1.0, 0.7, 1.0, 1.0);
+"#
+            }
+            InteriorColoring::DistanceEstimate { .. } => {
+                r#"        let period = find_period(z, previous_z, c, n);
+        if (period == 0) {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+        let v = clamp(sqrt(interior_distance(z, previous_z, c, n, period) * uniforms.interior.scale), 0.0, 1.0);
+        return vec4<f32>(v, v, v, 1.0);
+"#
+            }
+            InteriorColoring::AtomDomain => {
+                r#"        return fromHSB((f32(atom_iteration) * 0.13) % 1.0, 0.8, 0.9, 1.0);
+"#
+            }
+        });
+    }
+}
+
+/// Declares each loop variable with its initial value.
+fn write_loop_variables(out: &mut String, variables: &[LoopVariable]) {
+    for variable in variables {
+        writeln!(
+            out,
+            "    var {}: {} = {};",
+            variable.name, variable.ty, variable.initial
+        )
+        .unwrap();
+    }
+}
+
+/// Finds the period of the cycle that `z`, reached after `n` iterations, has
+/// settled into, or 0 if there is none within `max_period`.
+const FIND_PERIOD_SOURCE: &str = r#"
+fn find_period(z: vec2<f32>, previous_z: vec2<f32>, c: vec2<f32>, n: i32) -> i32 {
+    let epsilon_sqr = uniforms.interior.epsilon * uniforms.interior.epsilon;
+    var w = z;
+    var previous_w = previous_z;
+    for (var period: i32 = 1; period <= uniforms.interior.max_period; period = period + 1) {
+        let w_next = f(w, previous_w, c, n + period - 1);
+        previous_w = w;
+        w = w_next;
+        if (length_sqr(w - z) < epsilon_sqr) {
+            return period;
+        }
+    }
+    return 0;
+}
+"#;
+
+/// Estimates the distance to the boundary from the derivatives of f taken
+/// around the attracting cycle that `z0`, reached after `n0` iterations,
+/// belongs to.
+const INTERIOR_DISTANCE_SOURCE: &str = r#"
+fn interior_distance(z0: vec2<f32>, previous_z0: vec2<f32>, c: vec2<f32>, n0: i32, period: i32) -> f32 {
+    var z = z0;
+    var previous_z = previous_z0;
+    var dz = vec2<f32>(1.0, 0.0);
+    var dc = vec2<f32>(0.0, 0.0);
+    var dzdz = vec2<f32>(0.0, 0.0);
+    var dzdc = vec2<f32>(0.0, 0.0);
+    for (var i: i32 = 0; i < period; i = i + 1) {
+        let n = n0 + i;
+        let f_z = f_dz(z, previous_z, c, n);
+        let f_zz = f_dzdz(z, previous_z, c, n);
+        dzdc = complex_multiply(f_z, dzdc) + complex_multiply(complex_multiply(f_zz, dz), dc) + complex_multiply(f_dzdc(z, previous_z, c, n), dz);
+        dzdz = complex_multiply(f_z, dzdz) + complex_multiply(f_zz, complex_multiply(dz, dz));
+        dc = complex_multiply(f_z, dc) + f_dc(z, previous_z, c, n);
+        dz = complex_multiply(f_z, dz);
+        let z_next = f(z, previous_z, c, n);
+        previous_z = z;
+        z = z_next;
+    }
+    let numerator = 1.0 - length_sqr(dz);
+    let denominator = dzdc + complex_divide(complex_multiply(dzdz, dc), vec2<f32>(1.0, 0.0) - dz);
+    return numerator / length(denominator);
+}
+"#;
+
+impl TrapShape {
+    /// Writes the `orbit_trap_distance` function measuring the distance from
+    /// `z` to this shape.
+    fn write_distance_function(&self, out: &mut String) {
+        let distance = match self {
+            TrapShape::Point => "length(delta)",
+            TrapShape::Line => "abs(delta.x * direction.y - delta.y * direction.x)",
+            TrapShape::Circle => "abs(length(delta) - uniforms.trap.radius)",
+            TrapShape::Cross => {
+                "min(abs(delta.x * direction.y - delta.y * direction.x), abs(dot(delta, direction)))"
+            }
+        };
+
+        write!(
+            out,
+            r#"
+fn orbit_trap_distance(z: vec2<f32>) -> f32 {{
+    let delta = z - uniforms.trap.position;
+    let direction = uniforms.trap.direction;
+    return {};
+}}
+"#,
+            distance
+        )
+        .unwrap();
+    }
+}
+
+impl AverageStatistic {
+    /// Writes the helper function computing this statistic, if it needs one.
+    fn write_function(&self, out: &mut String) {
+        match self {
+            AverageStatistic::Stripe => {}
+            AverageStatistic::TriangleInequality => out.push_str(
+                r#"
+fn triangle_inequality(z: vec2<f32>, previous_z: vec2<f32>, c: vec2<f32>) -> f32 {
+    let z_length = length(complex_sqr(previous_z));
+    let c_length = length(c);
+    let low = abs(z_length - c_length);
+    let high = z_length + c_length;
+    return (length(z) - low) / max(high - low, 0.000001);
+}
+"#,
+            ),
+            AverageStatistic::Curvature => out.push_str(
+                r#"
+fn curvature(z: vec2<f32>, previous_z: vec2<f32>, z_older: vec2<f32>) -> f32 {
+    let a = z - previous_z;
+    let b = previous_z - z_older;
+    // Argument of a / b.
+    return abs(atan2(a.y * b.x - a.x * b.y, a.x * b.x + a.y * b.y)) / 3.14159265;
+}
+"#,
+            ),
+        }
+    }
+}
+
+impl FromStr for TrapShape {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "point" => Ok(TrapShape::Point),
+            "line" => Ok(TrapShape::Line),
+            "circle" => Ok(TrapShape::Circle),
+            "cross" => Ok(TrapShape::Cross),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+impl FromStr for TrapBlend {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first-hit" => Ok(TrapBlend::FirstHit),
+            "last-hit" => Ok(TrapBlend::LastHit),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+impl FromStr for TrapColoring {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "distance" => Ok(TrapColoring::Distance),
+            "iteration" => Ok(TrapColoring::Iteration),
+            "distance-and-iteration" => Ok(TrapColoring::DistanceAndIteration),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coloring::{TrapColoring, TrapShape},
+        scene::UnknownOption,
+    };
+
+    #[test]
+    fn parse_trap_shape() {
+        assert_eq!("cross".parse(), Ok(TrapShape::Cross));
+        assert_eq!("square".parse::<TrapShape>(), Err(UnknownOption));
+    }
+
+    #[test]
+    fn parse_trap_coloring() {
+        assert_eq!(
+            "distance-and-iteration".parse(),
+            Ok(TrapColoring::DistanceAndIteration)
+        );
+    }
+}
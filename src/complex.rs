@@ -0,0 +1,567 @@
+use crate::scene::UnknownOption;
+use naga::{front, Block, Handle, Module, Statement};
+use num_complex::Complex;
+use std::{collections::HashSet, str::FromStr};
+
+/// A function of one complex argument that formulas can call.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Function {
+    Reciprocal,
+    Exp,
+    /// The principal branch of the natural logarithm.
+    Log,
+    /// The principal square root.
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    /// The angle of the argument, as a complex number with no imaginary part.
+    Arg,
+}
+
+/// A function in the shader's complex library.
+///
+/// Functions are listed so that each one only calls functions before it,
+/// which lets them be written out in declaration order.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum LibraryFunction {
+    Conj,
+    Multiply,
+    Sqr,
+    Divide,
+    Reciprocal,
+    Abs,
+    Arg,
+    Exp,
+    Log,
+    Pow,
+    PowReal,
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+}
+
+const LIBRARY: [LibraryFunction; 17] = [
+    LibraryFunction::Conj,
+    LibraryFunction::Multiply,
+    LibraryFunction::Sqr,
+    LibraryFunction::Divide,
+    LibraryFunction::Reciprocal,
+    LibraryFunction::Abs,
+    LibraryFunction::Arg,
+    LibraryFunction::Exp,
+    LibraryFunction::Log,
+    LibraryFunction::Pow,
+    LibraryFunction::PowReal,
+    LibraryFunction::Sqrt,
+    LibraryFunction::Sin,
+    LibraryFunction::Cos,
+    LibraryFunction::Tan,
+    LibraryFunction::Sinh,
+    LibraryFunction::Cosh,
+];
+
+impl LibraryFunction {
+    fn name(&self) -> &'static str {
+        match self {
+            LibraryFunction::Conj => "complex_conj",
+            LibraryFunction::Multiply => "complex_multiply",
+            LibraryFunction::Sqr => "complex_sqr",
+            LibraryFunction::Divide => "complex_divide",
+            LibraryFunction::Reciprocal => "complex_reciprocal",
+            LibraryFunction::Abs => "complex_abs",
+            LibraryFunction::Arg => "complex_arg",
+            LibraryFunction::Exp => "complex_exp",
+            LibraryFunction::Log => "complex_log",
+            LibraryFunction::Pow => "complex_pow",
+            LibraryFunction::PowReal => "complex_pow_real",
+            LibraryFunction::Sqrt => "complex_sqrt",
+            LibraryFunction::Sin => "complex_sin",
+            LibraryFunction::Cos => "complex_cos",
+            LibraryFunction::Tan => "complex_tan",
+            LibraryFunction::Sinh => "complex_sinh",
+            LibraryFunction::Cosh => "complex_cosh",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            LibraryFunction::Conj => {
+                r#"
+fn complex_conj(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x, -a.y);
+}
+"#
+            }
+            LibraryFunction::Multiply => {
+                r#"
+fn complex_multiply(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+"#
+            }
+            LibraryFunction::Sqr => {
+                r#"
+fn complex_sqr(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * a.x - a.y * a.y, 2.0 * a.x * a.y);
+}
+"#
+            }
+            LibraryFunction::Divide => {
+                r#"
+fn complex_divide(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let denominator = b.x * b.x + b.y * b.y;
+    return vec2<f32>(a.x * b.x + a.y * b.y, a.y * b.x - a.x * b.y) / denominator;
+}
+"#
+            }
+            LibraryFunction::Reciprocal => {
+                r#"
+fn complex_reciprocal(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x, -a.y) / length_sqr(a);
+}
+"#
+            }
+            LibraryFunction::Abs => {
+                r#"
+fn complex_abs(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(length(a), 0.0);
+}
+"#
+            }
+            LibraryFunction::Arg => {
+                r#"
+fn complex_arg(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(atan2(a.y, a.x), 0.0);
+}
+"#
+            }
+            LibraryFunction::Exp => {
+                r#"
+fn complex_exp(a: vec2<f32>) -> vec2<f32> {
+    return exp(a.x) * vec2<f32>(cos(a.y), sin(a.y));
+}
+"#
+            }
+            LibraryFunction::Log => {
+                r#"
+fn complex_log(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(log(length(a)), atan2(a.y, a.x));
+}
+"#
+            }
+            LibraryFunction::Pow => {
+                r#"
+fn complex_pow(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    if (a.x == 0.0 && a.y == 0.0) {
+        return vec2<f32>(0.0, 0.0);
+    }
+    return complex_exp(complex_multiply(b, complex_log(a)));
+}
+"#
+            }
+            LibraryFunction::PowReal => {
+                r#"
+fn complex_pow_real(a: vec2<f32>, p: f32) -> vec2<f32> {
+    if (a.x == 0.0 && a.y == 0.0) {
+        return vec2<f32>(0.0, 0.0);
+    }
+    let r = pow(length(a), p);
+    let theta = atan2(a.y, a.x) * p;
+    return vec2<f32>(r * cos(theta), r * sin(theta));
+}
+"#
+            }
+            LibraryFunction::Sqrt => {
+                r#"
+fn complex_sqrt(a: vec2<f32>) -> vec2<f32> {
+    let r = length(a);
+    let re = sqrt(0.5 * (r + a.x));
+    let im = sqrt(0.5 * (r - a.x));
+    if (a.y < 0.0) {
+        return vec2<f32>(re, -im);
+    }
+    return vec2<f32>(re, im);
+}
+"#
+            }
+            LibraryFunction::Sin => {
+                r#"
+fn complex_sin(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(sin(a.x) * cosh(a.y), cos(a.x) * sinh(a.y));
+}
+"#
+            }
+            LibraryFunction::Cos => {
+                r#"
+fn complex_cos(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(cos(a.x) * cosh(a.y), -sin(a.x) * sinh(a.y));
+}
+"#
+            }
+            LibraryFunction::Tan => {
+                r#"
+fn complex_tan(a: vec2<f32>) -> vec2<f32> {
+    return complex_divide(complex_sin(a), complex_cos(a));
+}
+"#
+            }
+            LibraryFunction::Sinh => {
+                r#"
+fn complex_sinh(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(sinh(a.x) * cos(a.y), cosh(a.x) * sin(a.y));
+}
+"#
+            }
+            LibraryFunction::Cosh => {
+                r#"
+fn complex_cosh(a: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(cosh(a.x) * cos(a.y), sinh(a.x) * sin(a.y));
+}
+"#
+            }
+        }
+    }
+}
+
+/// Writes the complex library functions that the shader calls, directly or
+/// through other library functions, after `out`, which holds everything that
+/// comes before them, and before `body`.
+///
+/// The calls are found by parsing the shader with the whole library into
+/// naga IR and following its call graph from every function outside the
+/// library, since those are kept whether they are called or not. A shader
+/// that doesn't parse keeps the whole library, with a warning, so parsing it
+/// again reports the same error rather than one about a missing function.
+pub fn write_library(out: &mut String, body: &str) {
+    let mut shader = out.clone();
+    for function in &LIBRARY {
+        shader.push_str(function.source());
+    }
+    shader.push_str(body);
+
+    let called = match front::wgsl::parse_str(&shader) {
+        Ok(module) => called_functions(&module),
+        Err(error) => {
+            warn!(
+                "Keeping the whole complex library, as the shader doesn't parse: {}",
+                error
+            );
+            LIBRARY.iter().map(|f| f.name().to_string()).collect()
+        }
+    };
+    for function in LIBRARY.iter().filter(|f| called.contains(f.name())) {
+        out.push_str(function.source());
+    }
+}
+
+/// The names of the functions reachable from a module's entry points and
+/// the functions it defines outside the library.
+fn called_functions(module: &Module) -> HashSet<String> {
+    let mut visited = HashSet::new();
+    let mut pending = vec![];
+    for entry_point in &module.entry_points {
+        push_calls(&entry_point.function.body, &mut pending);
+    }
+    for (handle, function) in module.functions.iter() {
+        let name = function.name.as_deref();
+        if !LIBRARY.iter().any(|f| Some(f.name()) == name) {
+            pending.push(handle);
+        }
+    }
+    while let Some(function) = pending.pop() {
+        if visited.insert(function) {
+            push_calls(&module.functions[function].body, &mut pending);
+        }
+    }
+    visited
+        .into_iter()
+        .filter_map(|function| module.functions[function].name.clone())
+        .collect()
+}
+
+/// Pushes the functions a block calls, including from nested blocks.
+fn push_calls(block: &Block, pending: &mut Vec<Handle<naga::Function>>) {
+    for statement in block {
+        match statement {
+            Statement::Call { function, .. } => pending.push(*function),
+            Statement::Block(block) => push_calls(block, pending),
+            Statement::If { accept, reject, .. } => {
+                push_calls(accept, pending);
+                push_calls(reject, pending);
+            }
+            Statement::Switch { cases, default, .. } => {
+                for case in cases {
+                    push_calls(&case.body, pending);
+                }
+                push_calls(default, pending);
+            }
+            Statement::Loop { body, continuing } => {
+                push_calls(body, pending);
+                push_calls(continuing, pending);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Function {
+    /// The name of the library function computing this function in WGSL.
+    pub fn wgsl_name(&self) -> &'static str {
+        match self {
+            Function::Reciprocal => LibraryFunction::Reciprocal,
+            Function::Exp => LibraryFunction::Exp,
+            Function::Log => LibraryFunction::Log,
+            Function::Sqrt => LibraryFunction::Sqrt,
+            Function::Sin => LibraryFunction::Sin,
+            Function::Cos => LibraryFunction::Cos,
+            Function::Tan => LibraryFunction::Tan,
+            Function::Sinh => LibraryFunction::Sinh,
+            Function::Cosh => LibraryFunction::Cosh,
+            Function::Arg => LibraryFunction::Arg,
+        }
+        .name()
+    }
+
+    /// Applies this function on the CPU, the same way the shader does.
+    pub fn apply(&self, a: Complex<f32>) -> Complex<f32> {
+        match self {
+            Function::Reciprocal => reciprocal(a),
+            Function::Exp => exp(a),
+            Function::Log => log(a),
+            Function::Sqrt => sqrt(a),
+            Function::Sin => sin(a),
+            Function::Cos => cos(a),
+            Function::Tan => tan(a),
+            Function::Sinh => sinh(a),
+            Function::Cosh => cosh(a),
+            Function::Arg => arg(a),
+        }
+    }
+}
+
+impl FromStr for Function {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "recip" => Ok(Function::Reciprocal),
+            "exp" => Ok(Function::Exp),
+            "log" | "ln" => Ok(Function::Log),
+            "sqrt" => Ok(Function::Sqrt),
+            "sin" => Ok(Function::Sin),
+            "cos" => Ok(Function::Cos),
+            "tan" => Ok(Function::Tan),
+            "sinh" => Ok(Function::Sinh),
+            "cosh" => Ok(Function::Cosh),
+            "arg" => Ok(Function::Arg),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+// CPU mirrors of the library, computed with the same formulas as the shader.
+
+pub fn conj(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re, -a.im)
+}
+
+pub fn multiply(a: Complex<f32>, b: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re * b.re - a.im * b.im, a.re * b.im + a.im * b.re)
+}
+
+pub fn sqr(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re * a.re - a.im * a.im, 2.0 * a.re * a.im)
+}
+
+pub fn divide(a: Complex<f32>, b: Complex<f32>) -> Complex<f32> {
+    let denominator = b.re * b.re + b.im * b.im;
+    Complex::new(a.re * b.re + a.im * b.im, a.im * b.re - a.re * b.im) / denominator
+}
+
+pub fn reciprocal(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re, -a.im) / (a.re * a.re + a.im * a.im)
+}
+
+pub fn abs(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re.hypot(a.im), 0.0)
+}
+
+pub fn arg(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.im.atan2(a.re), 0.0)
+}
+
+pub fn exp(a: Complex<f32>) -> Complex<f32> {
+    a.re.exp() * Complex::new(a.im.cos(), a.im.sin())
+}
+
+pub fn log(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re.hypot(a.im).ln(), a.im.atan2(a.re))
+}
+
+pub fn pow(a: Complex<f32>, b: Complex<f32>) -> Complex<f32> {
+    if a.re == 0.0 && a.im == 0.0 {
+        return Complex::new(0.0, 0.0);
+    }
+    exp(multiply(b, log(a)))
+}
+
+pub fn pow_real(a: Complex<f32>, p: f32) -> Complex<f32> {
+    if a.re == 0.0 && a.im == 0.0 {
+        return Complex::new(0.0, 0.0);
+    }
+    let r = a.re.hypot(a.im).powf(p);
+    let theta = a.im.atan2(a.re) * p;
+    Complex::new(r * theta.cos(), r * theta.sin())
+}
+
+pub fn sqrt(a: Complex<f32>) -> Complex<f32> {
+    let r = a.re.hypot(a.im);
+    let re = (0.5 * (r + a.re)).sqrt();
+    let im = (0.5 * (r - a.re)).sqrt();
+    if a.im < 0.0 {
+        Complex::new(re, -im)
+    } else {
+        Complex::new(re, im)
+    }
+}
+
+pub fn sin(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re.sin() * a.im.cosh(), a.re.cos() * a.im.sinh())
+}
+
+pub fn cos(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re.cos() * a.im.cosh(), -a.re.sin() * a.im.sinh())
+}
+
+pub fn tan(a: Complex<f32>) -> Complex<f32> {
+    divide(sin(a), cos(a))
+}
+
+pub fn sinh(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re.sinh() * a.im.cos(), a.re.cosh() * a.im.sin())
+}
+
+pub fn cosh(a: Complex<f32>) -> Complex<f32> {
+    Complex::new(a.re.cosh() * a.im.cos(), a.re.sinh() * a.im.sin())
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::complex::{self, write_library, LIBRARY};
+    use naga::front;
+    use num_complex::Complex;
+
+    const SAMPLES: [(f32, f32); 6] = [
+        (0.5, 0.25),
+        (-1.5, 0.75),
+        (-2.0, 0.0),
+        (0.0, -1.25),
+        (1.75, -0.5),
+        (-0.3, -2.2),
+    ];
+
+    fn assert_close(a: Complex<f32>, b: Complex<f32>) {
+        assert!((a - b).norm() <= 1e-4 * b.norm().max(1.0), "{} != {}", a, b);
+    }
+
+    fn assert_agrees(
+        mirror: fn(Complex<f32>) -> Complex<f32>,
+        expected: fn(Complex<f32>) -> Complex<f32>,
+    ) {
+        for &(re, im) in &SAMPLES {
+            let a = Complex::new(re, im);
+            assert_close(mirror(a), expected(a));
+        }
+    }
+
+    #[test]
+    fn unary_functions_match_num_complex() {
+        assert_agrees(complex::conj, |a| a.conj());
+        assert_agrees(complex::sqr, |a| a * a);
+        assert_agrees(complex::reciprocal, |a| a.inv());
+        assert_agrees(complex::abs, |a| Complex::new(a.norm(), 0.0));
+        assert_agrees(complex::arg, |a| Complex::new(a.arg(), 0.0));
+        assert_agrees(complex::exp, |a| a.exp());
+        assert_agrees(complex::log, |a| a.ln());
+        assert_agrees(complex::sqrt, |a| a.sqrt());
+        assert_agrees(complex::sin, |a| a.sin());
+        assert_agrees(complex::cos, |a| a.cos());
+        assert_agrees(complex::tan, |a| a.tan());
+        assert_agrees(complex::sinh, |a| a.sinh());
+        assert_agrees(complex::cosh, |a| a.cosh());
+    }
+
+    #[test]
+    fn binary_functions_match_num_complex() {
+        for &(re, im) in &SAMPLES {
+            let a = Complex::new(re, im);
+            let b = Complex::new(im + 0.5, re);
+            assert_close(complex::multiply(a, b), a * b);
+            assert_close(complex::divide(a, b), a / b);
+            assert_close(complex::pow(a, b), a.powc(b));
+            assert_close(complex::pow_real(a, 2.5), a.powf(2.5));
+        }
+    }
+
+    #[test]
+    fn library_includes_only_used_functions() {
+        let mut library =
+            String::from("fn length_sqr(a: vec2<f32>) -> f32 {\n    return dot(a, a);\n}\n");
+        // Neither a name that merely ends in a library function's name nor a
+        // mention in a comment counts as a call.
+        let body = r#"
+fn my_complex_sinh(a: vec2<f32>) -> vec2<f32> {
+    // complex_exp(a) would overflow here.
+    return a;
+}
+
+[[stage(compute), workgroup_size(1)]]
+fn main() {
+    let z = complex_tan(my_complex_sinh(vec2<f32>(1.0, 0.0)));
+}
+"#;
+        write_library(&mut library, body);
+        assert!(library.contains("fn complex_tan("));
+        assert!(library.contains("fn complex_sin("));
+        assert!(library.contains("fn complex_divide("));
+        assert!(!library.contains("fn complex_sinh("));
+        assert!(!library.contains("fn complex_exp("));
+        assert!(!library.contains("fn complex_multiply("));
+
+        library.push_str(body);
+        front::wgsl::parse_str(&library).unwrap();
+    }
+
+    #[test]
+    fn unparsed_shader_keeps_its_error() {
+        let body = r#"
+fn f(z: vec2<f32>) -> vec2<f32> {
+    return complex_exp(z) +;
+}
+"#;
+        let prelude = "fn length_sqr(a: vec2<f32>) -> f32 {\n    return dot(a, a);\n}\n";
+        let mut shader = String::from(prelude);
+        write_library(&mut shader, body);
+        shader.push_str(body);
+        let error = front::wgsl::parse_str(&shader).unwrap_err();
+
+        // The error is the one the body has with every library function.
+        let mut whole_library = String::from(prelude);
+        for function in &LIBRARY {
+            whole_library.push_str(function.source());
+        }
+        whole_library.push_str(body);
+        let expected = front::wgsl::parse_str(&whole_library).unwrap_err();
+        assert_eq!(error.to_string(), expected.to_string());
+        let (line, _) = error.location(&shader);
+        assert!(shader.lines().nth(line - 1).unwrap().contains("+;"));
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::Write;
+
+/// Invocations in each workgroup of the compute passes accumulating point
+/// densities.
+pub const WORKGROUP_SIZE: u32 = 64;
+
+/// A hash-based random number generator, seeded per invocation.
+pub const RANDOM_SOURCE: &str = r#"
+var<private> rng_state: u32;
+
+// PCG hash, from "Hash Functions for GPU Rendering" by Jarzynski and Olano.
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Returns a random number in [0, 1).
+fn random() -> f32 {
+    rng_state = hash(rng_state);
+    return f32(rng_state >> 8u) / 16777216.0;
+}
+"#;
+
+/// Writes the storage buffer of counts per pixel, the `accumulate` function
+/// counting a point into one of each pixel's `channels`, `accumulate_amount`
+/// adding an arbitrary amount instead, and `histogram_index` finding the first
+/// channel of a point's pixel for stages that add to several channels.
+pub fn write_histogram(out: &mut String, channels: u32) {
+    write!(
+        out,
+        r#"
+[[block]]
+struct Histogram {{
+    counts: array<atomic<u32>>;
+}};
+
+[[group(1), binding(0)]]
+var<storage, read_write> histogram: Histogram;
+
+// The index of the first channel of the pixel a point lands on, or -1 if it
+// lands outside the view.
+fn histogram_index(z: vec2<f32>) -> i32 {{
+    let position = floor((z - uniforms.view.plane_start) / uniforms.view.image_scale + 0.5);
+    if (position.x < 0.0 || position.y < 0.0 || position.x >= uniforms.view.image_size.x || position.y >= uniforms.view.image_size.y) {{
+        return -1;
+    }}
+    return i32((u32(position.y) * u32(uniforms.view.image_size.x) + u32(position.x)) * {channels}u);
+}}
+
+// Adds an amount to one channel of the pixel a point lands on, if it lands in
+// the view.
+fn accumulate_amount(z: vec2<f32>, channel: u32, amount: u32) {{
+    let index = histogram_index(z);
+    if (index < 0) {{
+        return;
+    }}
+    // The atomic's previous value is unused, but atomics are only parsed as
+    // expressions.
+    let previous = atomicAdd(&histogram.counts[u32(index) + channel], amount);
+}}
+
+fn accumulate(z: vec2<f32>, channel: u32) {{
+    accumulate_amount(z, channel, 1u);
+}}
+"#,
+        channels = channels
+    )
+    .unwrap();
+}
+
+/// Compresses a histogram count logarithmically into `0.0..=1.0`, with the
+/// densest count `max` at 1 and an empty histogram all 0.
+pub fn log_density(count: u32, max: u32) -> f32 {
+    if max == 0 {
+        0.0
+    } else {
+        (count as f32).ln_1p() / (max as f32).ln_1p()
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{complex::Function, formula::Expr};
+use num_complex::Complex;
+
+/// A variable expressions can be differentiated with respect to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Variable {
+    Z,
+    C,
+}
+
+/// Error returned when differentiating an expression that has no complex
+/// derivative, such as one using `conj`, `re`, `im` or `abs` of a variable.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct NotHolomorphic;
+
+/// The partial derivatives of a formula's step that distance estimation
+/// carries through the iteration.
+///
+/// The previous `z` is treated as a constant, so these are exact only for
+/// formulas that don't reference it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derivatives {
+    pub dz: Expr,
+    pub dc: Expr,
+    pub dzdz: Expr,
+    pub dzdc: Expr,
+}
+
+impl Derivatives {
+    pub fn of(step: &Expr) -> Result<Derivatives, NotHolomorphic> {
+        let dz = step.derivative(Variable::Z)?;
+        let dc = step.derivative(Variable::C)?;
+        let dzdz = dz.derivative(Variable::Z)?;
+        let dzdc = dz.derivative(Variable::C)?;
+
+        Ok(Derivatives { dz, dc, dzdz, dzdc })
+    }
+}
+
+impl Expr {
+    /// Returns whether this expression references a variable.
+    pub fn depends_on(&self, variable: Variable) -> bool {
+        match self {
+            Expr::Z => variable == Variable::Z,
+            Expr::C => variable == Variable::C,
+            Expr::PreviousZ | Expr::Constant(_) => false,
+            Expr::Add(a, b)
+            | Expr::Sub(a, b)
+            | Expr::Mul(a, b)
+            | Expr::Div(a, b)
+            | Expr::Pow(a, b)
+            | Expr::Complex(a, b) => a.depends_on(variable) || b.depends_on(variable),
+            Expr::Neg(a)
+            | Expr::Conj(a)
+            | Expr::Re(a)
+            | Expr::Im(a)
+            | Expr::Abs(a)
+            | Expr::Function(_, a) => a.depends_on(variable),
+        }
+    }
+
+    /// Differentiates this expression with respect to a variable, treating
+    /// every other variable as a constant.
+    ///
+    /// This works in forward mode: each node's derivative is built from its
+    /// operands' values and derivatives. Terms that are trivially zero or one
+    /// are folded away so repeated differentiation stays readable in the
+    /// generated shader.
+    pub fn derivative(&self, variable: Variable) -> Result<Expr, NotHolomorphic> {
+        if !self.depends_on(variable) {
+            return Ok(Expr::constant(0.0, 0.0));
+        }
+
+        let d = |expr: &Expr| expr.derivative(variable);
+
+        Ok(match self {
+            Expr::Z | Expr::C => Expr::constant(1.0, 0.0),
+            Expr::Add(a, b) => sum(d(a)?, d(b)?),
+            Expr::Sub(a, b) => sum(d(a)?, negate(d(b)?)),
+            Expr::Neg(a) => negate(d(a)?),
+            Expr::Mul(a, b) => sum(product(d(a)?, (**b).clone()), product((**a).clone(), d(b)?)),
+            Expr::Div(a, b) => {
+                let numerator = sum(
+                    product(d(a)?, (**b).clone()),
+                    negate(product((**a).clone(), d(b)?)),
+                );
+                numerator / (**b).clone().sqr()
+            }
+            Expr::Pow(a, b) if !b.depends_on(variable) => {
+                let power = match **b {
+                    Expr::Constant(exponent) if exponent == Complex::new(1.0, 0.0) => {
+                        Expr::constant(1.0, 0.0)
+                    }
+                    Expr::Constant(exponent) if exponent == Complex::new(2.0, 0.0) => (**a).clone(),
+                    Expr::Constant(exponent) => (**a).clone().pow(Expr::Constant(exponent - 1.0)),
+                    _ => (**a).clone().pow((**b).clone() - Expr::constant(1.0, 0.0)),
+                };
+                product(product((**b).clone(), power), d(a)?)
+            }
+            // a^b = exp(b log a), so its derivative is a^b (b' log a + b a' / a).
+            Expr::Pow(a, b) => {
+                let log_a = (**a).clone().apply(Function::Log);
+                let ratio = product((**b).clone(), d(a)?) / (**a).clone();
+                product(self.clone(), sum(product(d(b)?, log_a), ratio))
+            }
+            Expr::Function(function, a) => {
+                let a = &**a;
+                let outer = match function {
+                    Function::Reciprocal => negate(Expr::constant(1.0, 0.0) / a.clone().sqr()),
+                    Function::Exp => a.clone().apply(Function::Exp),
+                    Function::Log => a.clone().apply(Function::Reciprocal),
+                    Function::Sqrt => Expr::constant(0.5, 0.0) / a.clone().apply(Function::Sqrt),
+                    Function::Sin => a.clone().apply(Function::Cos),
+                    Function::Cos => negate(a.clone().apply(Function::Sin)),
+                    Function::Tan => {
+                        Expr::constant(1.0, 0.0) / a.clone().apply(Function::Cos).sqr()
+                    }
+                    Function::Sinh => a.clone().apply(Function::Cosh),
+                    Function::Cosh => a.clone().apply(Function::Sinh),
+                    Function::Arg => return Err(NotHolomorphic),
+                };
+                product(outer, d(a)?)
+            }
+            _ => return Err(NotHolomorphic),
+        })
+    }
+}
+
+fn is_constant(expr: &Expr, value: f32) -> bool {
+    *expr == Expr::constant(value, 0.0)
+}
+
+/// Adds two expressions, dropping zero terms.
+fn sum(a: Expr, b: Expr) -> Expr {
+    if is_constant(&a, 0.0) {
+        b
+    } else if is_constant(&b, 0.0) {
+        a
+    } else {
+        a + b
+    }
+}
+
+/// Multiplies two expressions, folding multiplication by zero or one and
+/// gathering constant factors at the front.
+fn product(a: Expr, b: Expr) -> Expr {
+    if is_constant(&a, 0.0) || is_constant(&b, 0.0) {
+        return Expr::constant(0.0, 0.0);
+    } else if is_constant(&a, 1.0) {
+        return b;
+    } else if is_constant(&b, 1.0) {
+        return a;
+    }
+
+    match (a, b) {
+        (Expr::Constant(a), Expr::Constant(b)) => Expr::Constant(a * b),
+        (Expr::Constant(a), Expr::Mul(factor, rest))
+        | (Expr::Mul(factor, rest), Expr::Constant(a))
+            if matches!(*factor, Expr::Constant(_)) =>
+        {
+            product(product(Expr::Constant(a), *factor), *rest)
+        }
+        (a, Expr::Constant(b)) => Expr::Constant(b) * a,
+        (a, b) => a * b,
+    }
+}
+
+fn negate(a: Expr) -> Expr {
+    match a {
+        Expr::Constant(value) => Expr::Constant(-value),
+        _ => -a,
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        complex::Function,
+        derivative::{Derivatives, NotHolomorphic, Variable},
+        formula::{Expr, Family, Variables},
+        parser::parse_expr,
+    };
+    use num_complex::Complex;
+
+    /// Step used for central differences, large enough to stay clear of `f32`
+    /// rounding.
+    const H: f32 = 1e-2;
+
+    fn vars(z: Complex<f32>, c: Complex<f32>) -> Variables {
+        Variables {
+            z,
+            previous_z: Complex::new(0.1, -0.2),
+            c,
+        }
+    }
+
+    /// Approximates the derivative of `expr` with respect to `variable` by a
+    /// central difference.
+    fn finite_difference(
+        expr: &Expr,
+        variable: Variable,
+        z: Complex<f32>,
+        c: Complex<f32>,
+    ) -> Complex<f32> {
+        let h = Complex::new(H, 0.0);
+        let (forward, backward) = match variable {
+            Variable::Z => (vars(z + h, c), vars(z - h, c)),
+            Variable::C => (vars(z, c + h), vars(z, c - h)),
+        };
+        (expr.evaluate(&forward) - expr.evaluate(&backward)) / (2.0 * H)
+    }
+
+    fn assert_matches_finite_difference(expr: &Expr, variable: Variable) {
+        let derivative = expr.derivative(variable).unwrap();
+        for &(z, c) in &[
+            (Complex::new(0.3, 0.4), Complex::new(-0.7, 0.2)),
+            (Complex::new(-1.1, 0.5), Complex::new(0.25, -0.5)),
+            (Complex::new(0.8, -0.9), Complex::new(0.4, 0.6)),
+        ] {
+            let exact = derivative.evaluate(&vars(z, c));
+            let approximate = finite_difference(expr, variable, z, c);
+            assert!(
+                (exact - approximate).norm() <= 1e-2 * exact.norm().max(1.0),
+                "d/d{:?} {:?} at z = {}, c = {}: {} != {}",
+                variable,
+                expr,
+                z,
+                c,
+                exact,
+                approximate
+            );
+        }
+    }
+
+    #[test]
+    fn expressions_match_finite_differences() {
+        for source in &[
+            "z^3 - 2z + 1",
+            "(z + c) / (z^2 + 1)",
+            "z^2.5 + c^3",
+            "z^(1 + 2i) + c",
+            "3i(z - c)^4 / (c + 2)",
+            "z^c",
+            "exp(z) sin(c z) + log(z + 2)",
+            "sqrt(z + c) / cos(z)",
+            "tan(z) - sinh(2z) cosh(c) + recip(z - c)",
+        ] {
+            let expr = parse_expr(source).unwrap();
+            assert_matches_finite_difference(&expr, Variable::Z);
+            assert_matches_finite_difference(&expr, Variable::C);
+        }
+    }
+
+    #[test]
+    fn families_match_finite_differences() {
+        for &family in &[
+            Family::Multibrot { power: 2.0 },
+            Family::Multibrot { power: 5.0 },
+            Family::ComplexMultibrot {
+                power: Complex::new(2.0, 0.5),
+            },
+            Family::Phoenix {
+                p: Complex::new(-0.5, 0.0),
+            },
+            Family::MagnetI,
+            Family::MagnetII,
+        ] {
+            let step = family.formula().steps.remove(0);
+            let derivatives = Derivatives::of(&step).unwrap();
+            assert_matches_finite_difference(&step, Variable::Z);
+            assert_matches_finite_difference(&step, Variable::C);
+            assert_matches_finite_difference(&derivatives.dz, Variable::Z);
+            assert_matches_finite_difference(&derivatives.dz, Variable::C);
+        }
+    }
+
+    #[test]
+    fn mandelbrot_derivatives() {
+        let derivatives =
+            Derivatives::of(&Family::Multibrot { power: 2.0 }.formula().steps[0]).unwrap();
+        assert_eq!(derivatives.dz, Expr::constant(2.0, 0.0) * Expr::Z);
+        assert_eq!(derivatives.dc, Expr::constant(1.0, 0.0));
+        assert_eq!(derivatives.dzdz, Expr::constant(2.0, 0.0));
+        assert_eq!(derivatives.dzdc, Expr::constant(0.0, 0.0));
+    }
+
+    #[test]
+    fn non_holomorphic_derivatives() {
+        assert_eq!(Expr::Z.conj().derivative(Variable::Z), Err(NotHolomorphic));
+        assert_eq!(
+            Expr::Z.apply(Function::Arg).derivative(Variable::Z),
+            Err(NotHolomorphic)
+        );
+        assert_eq!(
+            Expr::C.abs().derivative(Variable::Z),
+            Ok(Expr::constant(0.0, 0.0))
+        );
+        assert_eq!(
+            Derivatives::of(&Family::BurningShip.formula().steps[0]),
+            Err(NotHolomorphic)
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    formula::Expr,
+    generator::{float_literal, write_frag_main_start},
+};
+use std::fmt::Write;
+
+/// Plots a complex function as a phase portrait: the argument of `f(z)`
+/// picks the hue and its modulus is shown by brightness rings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainColoring {
+    /// The function plotted, in terms of `z`.
+    pub function: Expr,
+    /// How much the rings at each power of two of the modulus darken, from 0
+    /// for no rings to 1.
+    pub contour_strength: f32,
+    /// When set, draws lines wherever the real or imaginary part of `f(z)` is
+    /// a multiple of this spacing.
+    pub grid_spacing: Option<f32>,
+}
+
+impl DomainColoring {
+    /// Writes the plotted function and the fragment stage.
+    pub fn write_shader(&self, out: &mut String) {
+        write!(
+            out,
+            r#"
+fn g(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {{
+    return {};
+}}
+"#,
+            self.function.to_wgsl()
+        )
+        .unwrap();
+
+        write_frag_main_start(out);
+        write!(
+            out,
+            r#"    let w = g(pixel, vec2<f32>(0.0, 0.0));
+
+    // Hue goes once around the color wheel as the argument goes around the
+    // origin, starting with red on the positive real axis.
+    let hue = fract(atan2(w.y, w.x) / 6.2831855 + 1.0);
+
+    // Rings where the modulus passes each power of two, brightest just
+    // before it does.
+    let ring = fract(log2(length(w)));
+    var brightness = 1.0 - {contour_strength} * (1.0 - ring);
+"#,
+            contour_strength = float_literal(self.contour_strength)
+        )
+        .unwrap();
+
+        if let Some(spacing) = self.grid_spacing {
+            write!(
+                out,
+                r#"
+    // Lines one pixel wide where the real or imaginary part is a multiple of
+    // the grid spacing. The pixel's footprint is measured by differencing
+    // with its neighbours, as `fwidth` can't be used after the bounds check.
+    let spacing = {};
+    let cell = w / spacing;
+    let to_line = abs(cell - round(cell));
+    let step_x = g(pixel + vec2<f32>(uniforms.view.image_scale.x, 0.0), vec2<f32>(0.0, 0.0)) - w;
+    let step_y = g(pixel + vec2<f32>(0.0, uniforms.view.image_scale.y), vec2<f32>(0.0, 0.0)) - w;
+    let line_width = (abs(step_x) + abs(step_y)) / spacing;
+    if (to_line.x < line_width.x || to_line.y < line_width.y) {{
+        brightness = brightness * 0.5;
+    }}
+"#,
+                float_literal(spacing)
+            )
+            .unwrap();
+        }
+
+        out.push_str("\n    return fromHSB(hue, 1.0, brightness, 1.0);\n}\n");
+    }
+}
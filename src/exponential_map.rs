@@ -0,0 +1,275 @@
+use crate::{plane_transform::PlaneTransform, view::View};
+use num_complex::Complex;
+use std::{f32::consts::PI, path::PathBuf};
+
+/// Renders a zoom as one tall strip in log-polar coordinates, where `x` is
+/// the angle around the zoom's center and `y` is how far the zoom has gone,
+/// so every frame of the zoom can be resampled from the strip rather than
+/// rendered separately.
+///
+/// Rows are spaced so that pixels are square, which makes each row `2π /
+/// width` deeper than the last. Depths are natural logarithms of the zoom
+/// factor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExponentialMap {
+    /// The point zoomed towards.
+    pub center: Complex<f32>,
+    /// The distance from the center at the top of the strip.
+    pub radius: f32,
+    /// How much the radius shrinks from the top of the strip to the bottom,
+    /// which is finite and greater than 1. Kept as an `f64` since deep zooms
+    /// overflow an `f32`.
+    pub zoom: f64,
+    /// Resamples the strip into frames when set.
+    pub frames: Option<ZoomFrames>,
+}
+
+/// The most rows a strip is rendered with, which bounds its memory. Deeper
+/// zooms stop at this depth.
+pub const MAX_STRIP_HEIGHT: usize = 1 << 15;
+
+/// Describes the zoom video assembled from an exponential map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoomFrames {
+    pub frame_rate: f32,
+    /// The length of the zoom, in seconds.
+    pub duration: f32,
+    /// The directory frames are written to.
+    pub path: PathBuf,
+}
+
+impl ExponentialMap {
+    /// The transform from strip pixels onto the complex plane.
+    pub fn transform(&self) -> PlaneTransform {
+        PlaneTransform::LogPolar {
+            center: self.center,
+            radius: self.radius,
+        }
+    }
+
+    /// The number of rows in a strip `width` pixels wide, at most
+    /// [`MAX_STRIP_HEIGHT`].
+    pub fn strip_height(&self, width: usize) -> usize {
+        let rows = (self.zoom.ln() / row_depth(width) as f64).ceil();
+        (rows as usize).min(MAX_STRIP_HEIGHT)
+    }
+
+    /// The view of the whole strip, before the transform.
+    pub fn view(&self, width: usize) -> View {
+        View {
+            image_width: width,
+            image_height: self.strip_height(width),
+            image_x: 0,
+            image_y: 0,
+            image_scale_x: row_depth(width),
+            image_scale_y: row_depth(width),
+            plane_start_x: 0.0,
+            plane_start_y: 0.0,
+        }
+    }
+}
+
+impl ZoomFrames {
+    /// The number of frames in the zoom.
+    pub fn count(&self) -> usize {
+        ((self.frame_rate * self.duration).round() as usize).max(1)
+    }
+
+    /// The depth of the corners of each frame, spread evenly so the last
+    /// frame's center pixels are still within the strip.
+    pub fn depths(
+        &self,
+        strip_width: usize,
+        strip_height: usize,
+        frame_width: usize,
+        frame_height: usize,
+    ) -> Vec<f32> {
+        let corner = (frame_width as f32).hypot(frame_height as f32) / 2.0;
+        let max_depth = (strip_height as f32 * row_depth(strip_width) - corner.ln()).max(0.0);
+        let count = self.count();
+        (0..count)
+            .map(|frame| match count {
+                1 => 0.0,
+                _ => max_depth * frame as f32 / (count - 1) as f32,
+            })
+            .collect()
+    }
+}
+
+/// How much deeper each row of a strip `width` pixels wide is than the last.
+fn row_depth(width: usize) -> f32 {
+    2.0 * PI / width as f32
+}
+
+/// Resamples an RGBA8 strip into a frame whose corners are `depth` below the
+/// top of the strip.
+///
+/// Points deeper than the bottom of the strip use its last row, and columns
+/// wrap around since they cover a full turn.
+pub fn assemble_frame(
+    strip: &[u8],
+    strip_width: usize,
+    strip_height: usize,
+    depth: f32,
+    frame_width: usize,
+    frame_height: usize,
+) -> Vec<u8> {
+    let row_depth = row_depth(strip_width);
+    let corner = (frame_width as f32).hypot(frame_height as f32) / 2.0;
+    let texel = |x: usize, y: usize| {
+        let index = (y * strip_width + x) * 4;
+        &strip[index..index + 4]
+    };
+
+    let mut frame = Vec::with_capacity(frame_width * frame_height * 4);
+    for y in 0..frame_height {
+        for x in 0..frame_width {
+            let dx = x as f32 + 0.5 - frame_width as f32 / 2.0;
+            let dy = y as f32 + 0.5 - frame_height as f32 / 2.0;
+            let angle = dy.atan2(dx).rem_euclid(2.0 * PI);
+            let pixel_depth = depth + (corner / dx.hypot(dy)).ln();
+
+            // Strip pixel centers are at whole multiples of the row depth.
+            let strip_x = angle / row_depth;
+            let strip_y = (pixel_depth / row_depth).clamp(0.0, (strip_height - 1) as f32);
+            let (x0, y0) = (strip_x.floor(), strip_y.floor());
+            let (fx, fy) = (strip_x - x0, strip_y - y0);
+            let x0 = x0 as usize % strip_width;
+            let x1 = (x0 + 1) % strip_width;
+            let y0 = y0 as usize;
+            let y1 = (y0 + 1).min(strip_height - 1);
+
+            for channel in 0..4 {
+                let top =
+                    texel(x0, y0)[channel] as f32 * (1.0 - fx) + texel(x1, y0)[channel] as f32 * fx;
+                let bottom =
+                    texel(x0, y1)[channel] as f32 * (1.0 - fx) + texel(x1, y1)[channel] as f32 * fx;
+                frame.push((top * (1.0 - fy) + bottom * fy).round() as u8);
+            }
+        }
+    }
+    frame
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        exponential_map::{assemble_frame, ExponentialMap, ZoomFrames, MAX_STRIP_HEIGHT},
+        projection::Projection,
+    };
+    use num_complex::Complex;
+    use std::{f32::consts::PI, path::PathBuf};
+
+    fn map() -> ExponentialMap {
+        ExponentialMap {
+            center: Complex::new(-0.75, 0.1),
+            radius: 2.0,
+            zoom: 1e5,
+            frames: None,
+        }
+    }
+
+    #[test]
+    fn strip_size() {
+        let map = map();
+        let height = map.strip_height(100);
+        assert_eq!(
+            height,
+            (1e5_f64.ln() / (2.0 * PI as f64 / 100.0)).ceil() as usize
+        );
+
+        let view = map.view(100);
+        assert_eq!(view.image_height, height);
+        assert_eq!(view.image_scale_x, view.image_scale_y);
+    }
+
+    #[test]
+    fn strip_height_is_capped() {
+        let map = ExponentialMap {
+            zoom: 1e300,
+            ..map()
+        };
+        assert_eq!(map.strip_height(1000), MAX_STRIP_HEIGHT);
+    }
+
+    #[test]
+    fn strip_pixels_map_to_plane() {
+        let map = map();
+        let view = map.view(100);
+
+        // The first row is at the full radius, a quarter turn along.
+        let point = view
+            .get_transformed_plane_coordinates((25, 0), &Projection::Flat, &[map.transform()])
+            .unwrap();
+        assert!((point - Complex::new(-0.75, 2.1)).norm() < 1e-5);
+
+        // Each row is a constant factor closer.
+        let row = view
+            .get_transformed_plane_coordinates((0, 10), &Projection::Flat, &[map.transform()])
+            .unwrap();
+        let expected = 2.0 * (-10.0 * 2.0 * PI / 100.0).exp();
+        assert!(((row - map.center).norm() - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn frame_depths() {
+        let frames = ZoomFrames {
+            frame_rate: 30.0,
+            duration: 2.0,
+            path: PathBuf::from("frames"),
+        };
+        assert_eq!(frames.count(), 60);
+
+        let depths = frames.depths(100, 200, 10, 10);
+        assert_eq!(depths.len(), 60);
+        assert_eq!(depths[0], 0.0);
+        let max_depth = 200.0 * 2.0 * PI / 100.0 - 50.0_f32.sqrt().ln();
+        assert!((depths[59] - max_depth).abs() < 1e-4);
+    }
+
+    #[test]
+    fn assemble_frame_angles() {
+        // Each column of the strip has its own color, and each row the same.
+        let (width, height) = (4, 3);
+        let mut strip = vec![];
+        for _ in 0..height {
+            for x in 0..width {
+                strip.extend_from_slice(&[x as u8 * 60, 0, 0, 255]);
+            }
+        }
+
+        let frame = assemble_frame(&strip, width, height, 0.0, 4, 4);
+        assert_eq!(frame.len(), 4 * 4 * 4);
+        let pixel = |x: usize, y: usize| &frame[(y * 4 + x) * 4..(y * 4 + x) * 4 + 4];
+
+        // Pixels below and right of the center are an eighth of a turn along,
+        // halfway between the first two columns.
+        assert_eq!(pixel(2, 2), &[30, 0, 0, 255]);
+        // Pixels above and right of the center are seven eighths of a turn
+        // along, between the last column and the first.
+        assert_eq!(pixel(2, 1), &[90, 0, 0, 255]);
+    }
+
+    #[test]
+    fn assemble_frame_depths() {
+        // Each row of the strip has its own color.
+        let (width, height) = (4, 8);
+        let mut strip = vec![];
+        for y in 0..height {
+            for _ in 0..width {
+                strip.extend_from_slice(&[0, y as u8 * 30, 0, 255]);
+            }
+        }
+
+        // Corner pixels are near the top row, half the radius of the frame's
+        // corners away from the center, while deeper points clamp to the
+        // bottom row.
+        let frame = assemble_frame(&strip, width, height, 0.0, 2, 2);
+        let y = 2.0_f32.ln() / (2.0 * PI / 4.0);
+        assert_eq!(&frame[..4], &[0, (y * 30.0).round() as u8, 0, 255]);
+        let deep = assemble_frame(&strip, width, height, 100.0, 2, 2);
+        assert_eq!(&deep[..4], &[0, 210, 0, 255]);
+    }
+}
@@ -0,0 +1,525 @@
+use crate::{
+    density::{log_density, write_histogram, RANDOM_SOURCE, WORKGROUP_SIZE},
+    generator::float_literal,
+    palette::Palette,
+    scene::UnknownOption,
+    view::View,
+};
+use num_complex::Complex;
+use std::{fmt::Write, str::FromStr};
+
+/// Points each invocation plots, after settling onto the attractor.
+pub const POINTS_PER_INVOCATION: u32 = 1024;
+
+/// Iterations each stream takes before plotting.
+pub const SETTLE_ITERATIONS: u32 = 20;
+
+/// Workgroups dispatched per batch of points.
+pub const WORKGROUPS_PER_BATCH: u32 = 1024;
+
+/// Points plotted by each dispatch.
+pub const POINTS_PER_BATCH: u64 =
+    WORKGROUP_SIZE as u64 * POINTS_PER_INVOCATION as u64 * WORKGROUPS_PER_BATCH as u64;
+
+/// The histogram channels: the red, green and blue sums of the colors of the
+/// points plotted in each pixel, and the number of points.
+pub const CHANNELS: u32 = 4;
+
+/// The number of colors in a flame's palette.
+pub const PALETTE_SIZE: usize = 256;
+
+/// A nonlinear function applied after a transform's affine map.
+///
+/// Formulas are from "The Fractal Flame Algorithm" by Scott Draves and Erik
+/// Reckase, with `r` the distance from the origin, `theta` the angle
+/// `atan2(x, y)` and `phi` the angle `atan2(y, x)`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Variation {
+    Linear,
+    Sinusoidal,
+    Spherical,
+    Swirl,
+    Horseshoe,
+    Polar,
+    Handkerchief,
+    Heart,
+    Disc,
+    Spiral,
+    Hyperbolic,
+    Diamond,
+    Fisheye,
+    Exponential,
+    Julia,
+}
+
+/// One of a flame's weighted transforms, or its final transform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    /// The relative chance of picking this transform.
+    pub weight: f32,
+    /// The palette position this transform pulls points' colors toward.
+    pub color: f32,
+    /// How far toward `color` each application moves a point's color.
+    pub color_speed: f32,
+    /// The affine map `(a x + b y + c, d x + e y + f)` as `[a, b, c, d, e, f]`.
+    pub affine: [f32; 6],
+    /// An affine map applied after the variations.
+    pub post_affine: Option<[f32; 6]>,
+    /// The variations summed after the affine map, with their weights.
+    pub variations: Vec<(Variation, f32)>,
+}
+
+/// A fractal flame: an iterated function system whose transforms are
+/// affine maps followed by nonlinear variations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flame {
+    pub transforms: Vec<Transform>,
+    /// Applied to points as they are plotted, without feeding back into the
+    /// iteration.
+    pub final_transform: Option<Transform>,
+    /// [`PALETTE_SIZE`] RGB colors.
+    pub palette: Vec<[u8; 3]>,
+    /// The center of the view.
+    pub center: Complex<f32>,
+    /// The width of the plane shown.
+    pub width: f32,
+    /// Scales the log density, relative to flam3's default of 4.
+    pub brightness: f32,
+    pub gamma: f32,
+    /// How much gamma correction is applied to the density rather than each
+    /// color channel, which keeps colors saturated, from 0 to 1.
+    pub vibrancy: f32,
+}
+
+/// Renders a flame by playing the chaos game in a compute pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FractalFlame {
+    pub flame: Flame,
+    /// The number of points plotted.
+    pub points: u64,
+}
+
+impl Variation {
+    fn wgsl_name(self) -> &'static str {
+        match self {
+            Variation::Linear => "variation_linear",
+            Variation::Sinusoidal => "variation_sinusoidal",
+            Variation::Spherical => "variation_spherical",
+            Variation::Swirl => "variation_swirl",
+            Variation::Horseshoe => "variation_horseshoe",
+            Variation::Polar => "variation_polar",
+            Variation::Handkerchief => "variation_handkerchief",
+            Variation::Heart => "variation_heart",
+            Variation::Disc => "variation_disc",
+            Variation::Spiral => "variation_spiral",
+            Variation::Hyperbolic => "variation_hyperbolic",
+            Variation::Diamond => "variation_diamond",
+            Variation::Fisheye => "variation_fisheye",
+            Variation::Exponential => "variation_exponential",
+            Variation::Julia => "variation_julia",
+        }
+    }
+
+    /// The body of the variation's WGSL function, given `p`, `r`, `theta`
+    /// and `phi`.
+    fn wgsl_body(self) -> &'static str {
+        match self {
+            Variation::Linear => "return p;",
+            Variation::Sinusoidal => "return sin(p);",
+            Variation::Spherical => "return p / (r * r);",
+            Variation::Swirl => {
+                "let s = sin(r * r);\n    let c = cos(r * r);\n    return vec2<f32>(p.x * s - p.y * c, p.x * c + p.y * s);"
+            }
+            Variation::Horseshoe => "return vec2<f32>((p.x - p.y) * (p.x + p.y), 2.0 * p.x * p.y) / r;",
+            Variation::Polar => "return vec2<f32>(theta / 3.1415927, r - 1.0);",
+            Variation::Handkerchief => "return r * vec2<f32>(sin(theta + r), cos(theta - r));",
+            Variation::Heart => "return r * vec2<f32>(sin(theta * r), -cos(theta * r));",
+            Variation::Disc => {
+                "return theta / 3.1415927 * vec2<f32>(sin(3.1415927 * r), cos(3.1415927 * r));"
+            }
+            Variation::Spiral => "return vec2<f32>(cos(theta) + sin(r), sin(theta) - cos(r)) / r;",
+            Variation::Hyperbolic => "return vec2<f32>(sin(theta) / r, r * cos(theta));",
+            Variation::Diamond => "return vec2<f32>(sin(theta) * cos(r), cos(theta) * sin(r));",
+            Variation::Fisheye => "return 2.0 / (r + 1.0) * p.yx;",
+            Variation::Exponential => {
+                "return exp(p.x - 1.0) * vec2<f32>(cos(3.1415927 * p.y), sin(3.1415927 * p.y));"
+            }
+            Variation::Julia => {
+                "let omega = select(0.0, 3.1415927, random() < 0.5);\n    return sqrt(r) * vec2<f32>(cos(0.5 * theta + omega), sin(0.5 * theta + omega));"
+            }
+        }
+    }
+
+    /// Every variation, in the order their functions are written.
+    pub const ALL: [Variation; 15] = [
+        Variation::Linear,
+        Variation::Sinusoidal,
+        Variation::Spherical,
+        Variation::Swirl,
+        Variation::Horseshoe,
+        Variation::Polar,
+        Variation::Handkerchief,
+        Variation::Heart,
+        Variation::Disc,
+        Variation::Spiral,
+        Variation::Hyperbolic,
+        Variation::Diamond,
+        Variation::Fisheye,
+        Variation::Exponential,
+        Variation::Julia,
+    ];
+}
+
+impl Flame {
+    /// A Sierpinski triangle with a swirled corner, used when no flame file
+    /// is given.
+    pub fn sierpinski() -> Flame {
+        let corner = |color: f32, c: f32, f: f32, variations: Vec<(Variation, f32)>| Transform {
+            weight: 1.0,
+            color,
+            color_speed: 0.5,
+            affine: [0.5, 0.0, c, 0.0, 0.5, f],
+            post_affine: None,
+            variations,
+        };
+
+        Flame {
+            transforms: vec![
+                corner(0.0, 0.0, 0.0, vec![(Variation::Linear, 1.0)]),
+                corner(0.5, 0.5, 0.0, vec![(Variation::Linear, 1.0)]),
+                corner(
+                    1.0,
+                    0.0,
+                    0.5,
+                    vec![(Variation::Linear, 0.7), (Variation::Swirl, 0.3)],
+                ),
+            ],
+            final_transform: None,
+            palette: (0..PALETTE_SIZE)
+                .map(|i| Palette::Fire.color(0.25 + 0.75 * i as f32 / (PALETTE_SIZE - 1) as f32))
+                .collect(),
+            center: Complex::new(0.5, 0.5),
+            width: 1.5,
+            brightness: 4.0,
+            gamma: 2.2,
+            vibrancy: 1.0,
+        }
+    }
+
+    /// The view of the flame's plane.
+    pub fn view(&self, image_width: usize, image_height: usize) -> View {
+        View::new_uniform(
+            image_width,
+            image_height,
+            self.width,
+            self.center.re,
+            self.center.im,
+        )
+    }
+
+    fn uses(&self, variation: Variation) -> bool {
+        self.transforms
+            .iter()
+            .chain(self.final_transform.iter())
+            .any(|transform| transform.variations.iter().any(|&(v, _)| v == variation))
+    }
+}
+
+impl FractalFlame {
+    /// The number of compute dispatches needed to plot every point.
+    pub fn batches(&self) -> u64 {
+        (self.points as f64 / POINTS_PER_BATCH as f64).ceil() as u64
+    }
+
+    /// Writes the histogram binding, palette, transforms and compute stage
+    /// after the template.
+    pub fn write_shader(&self, out: &mut String) {
+        let flame = &self.flame;
+        write_histogram(out, CHANNELS);
+        out.push_str(RANDOM_SOURCE);
+
+        write!(
+            out,
+            "\nvar<private> flame_palette: array<vec3<f32>, {}u> = array<vec3<f32>, {}u>(\n",
+            flame.palette.len(),
+            flame.palette.len()
+        )
+        .unwrap();
+        for (i, color) in flame.palette.iter().enumerate() {
+            let separator = if i + 1 < flame.palette.len() { "," } else { "" };
+            writeln!(
+                out,
+                "    vec3<f32>({}, {}, {}){}",
+                float_literal(color[0] as f32 / 255.0),
+                float_literal(color[1] as f32 / 255.0),
+                float_literal(color[2] as f32 / 255.0),
+                separator
+            )
+            .unwrap();
+        }
+        out.push_str(");\n");
+
+        // Only the variations the flame uses are included.
+        for &variation in Variation::ALL.iter().filter(|&&v| flame.uses(v)) {
+            write!(
+                out,
+                r#"
+fn {}(p: vec2<f32>, r: f32, theta: f32, phi: f32) -> vec2<f32> {{
+    {}
+}}
+"#,
+                variation.wgsl_name(),
+                variation.wgsl_body()
+            )
+            .unwrap();
+        }
+
+        for (i, transform) in flame.transforms.iter().enumerate() {
+            write_transform(out, &format!("transform_{}", i), transform);
+        }
+        if let Some(transform) = &flame.final_transform {
+            write_transform(out, "final_transform", transform);
+        }
+
+        write!(
+            out,
+            r#"
+[[stage(compute), workgroup_size({workgroup_size})]]
+fn comp_main([[builtin(global_invocation_id)]] id: vec3<u32>) {{
+    rng_state = hash(id.x ^ hash(uniforms.sampling.seed));
+
+    var p = vec2<f32>(random(), random()) * 2.0 - 1.0;
+    var color = random();
+    for (var i: u32 = 0u; i < {iterations}u; i = i + 1u) {{
+        let choice = random();
+"#,
+            workgroup_size = WORKGROUP_SIZE,
+            iterations = SETTLE_ITERATIONS + POINTS_PER_INVOCATION,
+        )
+        .unwrap();
+
+        // Picks a transform by comparing against the running total of the
+        // normalized weights.
+        let total_weight: f32 = flame.transforms.iter().map(|t| t.weight).sum();
+        let mut threshold = 0.0;
+        for (i, transform) in flame.transforms.iter().enumerate() {
+            let indent = "    ".repeat(i + 2);
+            if i + 1 < flame.transforms.len() {
+                threshold += transform.weight / total_weight;
+                writeln!(
+                    out,
+                    "{}if (choice < {}) {{",
+                    indent,
+                    float_literal(threshold)
+                )
+                .unwrap();
+            }
+            write!(
+                out,
+                "{indent}    p = transform_{i}(p);\n{indent}    color = mix(color, {color}, {speed});\n",
+                indent = indent,
+                i = i,
+                color = float_literal(transform.color),
+                speed = float_literal(transform.color_speed),
+            )
+            .unwrap();
+            if i + 1 < flame.transforms.len() {
+                writeln!(out, "{}}} else {{", indent).unwrap();
+            }
+        }
+        for i in (0..flame.transforms.len().saturating_sub(1)).rev() {
+            writeln!(out, "{}}}", "    ".repeat(i + 2)).unwrap();
+        }
+
+        let plotted = if flame.final_transform.is_some() {
+            "final_transform(p)"
+        } else {
+            "p"
+        };
+        write!(
+            out,
+            r#"
+        // Restart streams that have overflowed.
+        if (!(length_sqr(p) < 10000000000.0)) {{
+            p = vec2<f32>(random(), random()) * 2.0 - 1.0;
+            continue;
+        }}
+
+        if (i >= {settle_iterations}u) {{
+            let q = {plotted};
+            let rgb = flame_palette[u32(clamp(color, 0.0, 1.0) * {last_color})] * 255.0;
+            let index = histogram_index(q);
+            if (index >= 0) {{
+                // Every channel is added atomically, so the color sums never
+                // fall out of step with the count they're averaged over.
+                let red = atomicAdd(&histogram.counts[u32(index)], u32(rgb.x));
+                let green = atomicAdd(&histogram.counts[u32(index) + 1u], u32(rgb.y));
+                let blue = atomicAdd(&histogram.counts[u32(index) + 2u], u32(rgb.z));
+                let count = atomicAdd(&histogram.counts[u32(index) + 3u], 1u);
+            }}
+        }}
+    }}
+}}
+"#,
+            settle_iterations = SETTLE_ITERATIONS,
+            plotted = plotted,
+            last_color = float_literal((flame.palette.len() - 1) as f32),
+        )
+        .unwrap();
+    }
+}
+
+/// Writes a function applying a transform's affine map, variations and post
+/// affine map.
+fn write_transform(out: &mut String, name: &str, transform: &Transform) {
+    write!(
+        out,
+        r#"
+fn {}(p: vec2<f32>) -> vec2<f32> {{
+    let q = {};
+    let r = sqrt(length_sqr(q)) + 0.0000001;
+    let theta = atan2(q.x, q.y);
+    let phi = atan2(q.y, q.x);
+    var result = vec2<f32>(0.0, 0.0);
+"#,
+        name,
+        affine_wgsl(&transform.affine, "p")
+    )
+    .unwrap();
+    for &(variation, weight) in &transform.variations {
+        writeln!(
+            out,
+            "    result = result + {} * {}(q, r, theta, phi);",
+            float_literal(weight),
+            variation.wgsl_name()
+        )
+        .unwrap();
+    }
+    match &transform.post_affine {
+        Some(post) => writeln!(out, "    return {};\n}}", affine_wgsl(post, "result")),
+        None => writeln!(out, "    return result;\n}}"),
+    }
+    .unwrap();
+}
+
+fn affine_wgsl(affine: &[f32; 6], p: &str) -> String {
+    let [a, b, c, d, e, f] = affine;
+    format!(
+        "vec2<f32>({a} * {p}.x + {b} * {p}.y + {c}, {d} * {p}.x + {e} * {p}.y + {f})",
+        a = float_literal(*a),
+        b = float_literal(*b),
+        c = float_literal(*c),
+        d = float_literal(*d),
+        e = float_literal(*e),
+        f = float_literal(*f),
+        p = p
+    )
+}
+
+/// Maps the histogram's color sums and densities to RGBA8 pixels.
+///
+/// Each pixel's average color is scaled by its log density, normalized so
+/// the densest pixel is at full brightness, then gamma corrected. Vibrancy
+/// blends between correcting the density, which keeps colors saturated, and
+/// correcting each channel.
+pub fn tone_map(counts: &[u32], flame: &Flame) -> Vec<u8> {
+    let channels = CHANNELS as usize;
+    let max = counts
+        .chunks(channels)
+        .map(|pixel| pixel[channels - 1])
+        .max()
+        .unwrap_or(0);
+    let inverse_gamma = 1.0 / flame.gamma;
+
+    let mut image = Vec::with_capacity(counts.len() / channels * 4);
+    for pixel in counts.chunks(channels) {
+        let count = pixel[channels - 1];
+        if count == 0 {
+            image.extend_from_slice(&[0, 0, 0, 255]);
+            continue;
+        }
+
+        let alpha = (flame.brightness / 4.0 * log_density(count, max)).clamp(0.0, 1.0);
+        let alpha_gamma = alpha.powf(inverse_gamma);
+        for &sum in &pixel[..3] {
+            let average = sum as f32 / count as f32 / 255.0;
+            let value = flame.vibrancy * average * alpha_gamma
+                + (1.0 - flame.vibrancy) * (average * alpha).powf(inverse_gamma);
+            image.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+        image.push(255);
+    }
+    image
+}
+
+impl FromStr for Variation {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Variation::Linear),
+            "sinusoidal" => Ok(Variation::Sinusoidal),
+            "spherical" => Ok(Variation::Spherical),
+            "swirl" => Ok(Variation::Swirl),
+            "horseshoe" => Ok(Variation::Horseshoe),
+            "polar" => Ok(Variation::Polar),
+            "handkerchief" => Ok(Variation::Handkerchief),
+            "heart" => Ok(Variation::Heart),
+            "disc" => Ok(Variation::Disc),
+            "spiral" => Ok(Variation::Spiral),
+            "hyperbolic" => Ok(Variation::Hyperbolic),
+            "diamond" => Ok(Variation::Diamond),
+            "fisheye" => Ok(Variation::Fisheye),
+            "exponential" => Ok(Variation::Exponential),
+            "julia" => Ok(Variation::Julia),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::flame::{tone_map, Flame, FractalFlame, Variation, POINTS_PER_BATCH};
+
+    #[test]
+    fn tone_map_scales_color_by_density() {
+        let mut flame = Flame::sierpinski();
+        flame.gamma = 1.0;
+        let image = tone_map(&[765, 0, 382, 3, 0, 0, 0, 0, 2040, 2040, 2040, 8], &flame);
+        assert_eq!(
+            image,
+            vec![161, 0, 80, 255, 0, 0, 0, 255, 255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn tone_map_vibrancy() {
+        let mut flame = Flame::sierpinski();
+        flame.gamma = 2.0;
+        let counts = [765, 255, 0, 3, 2040, 2040, 2040, 8];
+
+        // Gamma correcting the density keeps the ratio between channels,
+        // while correcting each channel brightens the weaker ones.
+        flame.vibrancy = 1.0;
+        assert_eq!(&tone_map(&counts, &flame)[..4], &[203, 68, 0, 255]);
+        flame.vibrancy = 0.0;
+        assert_eq!(&tone_map(&counts, &flame)[..4], &[203, 117, 0, 255]);
+    }
+
+    #[test]
+    fn batches_cover_points() {
+        let fractal_flame = FractalFlame {
+            flame: Flame::sierpinski(),
+            points: POINTS_PER_BATCH + 1,
+        };
+        assert_eq!(fractal_flame.batches(), 2);
+    }
+
+    #[test]
+    fn parse_variation() {
+        assert_eq!("swirl".parse(), Ok(Variation::Swirl));
+        assert!("blob".parse::<Variation>().is_err());
+    }
+}
@@ -0,0 +1,483 @@
+use crate::{
+    flame::{Flame, Transform, Variation, PALETTE_SIZE},
+    palette::Palette,
+};
+use num_complex::Complex;
+use std::str::FromStr;
+
+/// Error potentially returned when reading a `.flame` file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FlameFileError {
+    /// The file isn't well formed XML, starting at this byte offset.
+    Malformed(usize),
+    /// An element is missing an attribute it needs.
+    MissingAttribute {
+        element: String,
+        attribute: &'static str,
+    },
+    /// An attribute's value couldn't be parsed.
+    InvalidValue { attribute: String, value: String },
+    /// The file has no `flame` elements.
+    NoFlames,
+}
+
+/// The subset of XML that `.flame` files use: elements with attributes,
+/// child elements and text.
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+/// Attributes of `xform` elements that aren't variations.
+const TRANSFORM_ATTRIBUTES: [&str; 12] = [
+    "weight",
+    "color",
+    "coefs",
+    "post",
+    "symmetry",
+    "color_speed",
+    "opacity",
+    "animate",
+    "var_color",
+    "chaos",
+    "name",
+    "plotmode",
+];
+
+/// Reads every flame in the source of a `.flame` file, in the format used
+/// by flam3 and Apophysis.
+///
+/// Only the variations in [`Variation`] are supported; others are skipped
+/// with a warning. Flame rotation isn't supported either.
+pub fn parse_flames(source: &str) -> Result<Vec<Flame>, FlameFileError> {
+    let mut reader = Reader {
+        source,
+        position: 0,
+    };
+    let (elements, _) = reader.content(None)?;
+
+    let mut flame_elements = vec![];
+    find_elements(&elements, "flame", &mut flame_elements);
+    if flame_elements.is_empty() {
+        return Err(FlameFileError::NoFlames);
+    }
+    flame_elements.into_iter().map(flame).collect()
+}
+
+fn find_elements<'a>(elements: &'a [Element], name: &str, found: &mut Vec<&'a Element>) {
+    for element in elements {
+        if element.name == name {
+            found.push(element);
+        } else {
+            find_elements(&element.children, name, found);
+        }
+    }
+}
+
+fn flame(element: &Element) -> Result<Flame, FlameFileError> {
+    let [width, _]: [f32; 2] = element.numbers("size")?;
+    let scale: f32 = element.number("scale")?;
+    let [center_x, center_y] = element.numbers_or("center", [0.0, 0.0])?;
+
+    let mut transforms = vec![];
+    let mut final_transform = None;
+    for child in &element.children {
+        match child.name.as_str() {
+            "xform" => transforms.push(transform(child, child.number("weight")?)?),
+            "finalxform" => final_transform = Some(transform(child, 1.0)?),
+            _ => {}
+        }
+    }
+
+    Ok(Flame {
+        transforms,
+        final_transform,
+        palette: palette(element)?,
+        center: Complex::new(center_x, center_y),
+        width: width / scale,
+        brightness: element.number_or("brightness", 4.0)?,
+        gamma: element.number_or("gamma", 4.0)?,
+        vibrancy: element.number_or("vibrancy", 1.0)?,
+    })
+}
+
+fn transform(element: &Element, weight: f32) -> Result<Transform, FlameFileError> {
+    let color_speed = match element.attribute("color_speed") {
+        Some(_) => element.number("color_speed")?,
+        None => (1.0 - element.number_or("symmetry", 0.0)?) / 2.0,
+    };
+    let post_affine = match element.attribute("post") {
+        Some(_) => Some(affine(element.numbers("post")?)),
+        None => None,
+    };
+
+    let mut variations = vec![];
+    for (name, value) in &element.attributes {
+        if TRANSFORM_ATTRIBUTES.contains(&name.as_str()) {
+            continue;
+        }
+        match name.parse::<Variation>() {
+            Ok(variation) => variations.push((variation, parse_number(name, value)?)),
+            Err(_) => warn!("Skipping unsupported flame attribute {}", name),
+        }
+    }
+
+    Ok(Transform {
+        weight,
+        color: element.number_or("color", 0.0)?,
+        color_speed,
+        affine: affine(element.numbers("coefs")?),
+        post_affine,
+        variations,
+    })
+}
+
+/// Converts flam3's coefficients, which are listed by column, to rows.
+fn affine(coefficients: [f32; 6]) -> [f32; 6] {
+    let [xx, xy, yx, yy, ox, oy] = coefficients;
+    [xx, yx, ox, xy, yy, oy]
+}
+
+/// Reads a palette from `color` elements or a hex `palette` element, falling
+/// back to grayscale if the flame has neither.
+fn palette(element: &Element) -> Result<Vec<[u8; 3]>, FlameFileError> {
+    let mut palette: Vec<[u8; 3]> = (0..PALETTE_SIZE)
+        .map(|i| Palette::Grayscale.color(i as f32 / (PALETTE_SIZE - 1) as f32))
+        .collect();
+
+    for child in &element.children {
+        match child.name.as_str() {
+            "color" => {
+                let index: usize = child.number("index")?;
+                let [r, g, b]: [f32; 3] = child.numbers("rgb")?;
+                if let Some(color) = palette.get_mut(index) {
+                    *color = [r as u8, g as u8, b as u8];
+                }
+            }
+            "palette" => {
+                let digits: Vec<char> = child.text.chars().filter(|c| !c.is_whitespace()).collect();
+                for (color, hex) in palette.iter_mut().zip(digits.chunks(6)) {
+                    for (channel, pair) in color.iter_mut().zip(hex.chunks(2)) {
+                        let pair: String = pair.iter().collect();
+                        *channel = u8::from_str_radix(&pair, 16).map_err(|_| {
+                            FlameFileError::InvalidValue {
+                                attribute: String::from("palette"),
+                                value: pair.clone(),
+                            }
+                        })?;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(palette)
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn number<T: FromStr>(&self, name: &'static str) -> Result<T, FlameFileError> {
+        match self.attribute(name) {
+            Some(value) => parse_number(name, value),
+            None => Err(self.missing(name)),
+        }
+    }
+
+    fn number_or<T: FromStr>(&self, name: &'static str, default: T) -> Result<T, FlameFileError> {
+        match self.attribute(name) {
+            Some(value) => parse_number(name, value),
+            None => Ok(default),
+        }
+    }
+
+    /// Reads an attribute holding a fixed number of space separated numbers.
+    fn numbers<const N: usize>(&self, name: &'static str) -> Result<[f32; N], FlameFileError> {
+        let value = self.attribute(name).ok_or_else(|| self.missing(name))?;
+        let invalid = || FlameFileError::InvalidValue {
+            attribute: String::from(name),
+            value: String::from(value),
+        };
+
+        let mut numbers = [0.0; N];
+        let mut words = value.split_whitespace();
+        for number in numbers.iter_mut() {
+            *number = words
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+        }
+        match words.next() {
+            Some(_) => Err(invalid()),
+            None => Ok(numbers),
+        }
+    }
+
+    fn numbers_or<const N: usize>(
+        &self,
+        name: &'static str,
+        default: [f32; N],
+    ) -> Result<[f32; N], FlameFileError> {
+        match self.attribute(name) {
+            Some(_) => self.numbers(name),
+            None => Ok(default),
+        }
+    }
+
+    fn missing(&self, attribute: &'static str) -> FlameFileError {
+        FlameFileError::MissingAttribute {
+            element: self.name.clone(),
+            attribute,
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, FlameFileError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| FlameFileError::InvalidValue {
+            attribute: String::from(name),
+            value: String::from(value),
+        })
+}
+
+/// Reads XML elements, skipping declarations, comments and doctypes.
+struct Reader<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Reads elements and text until the closing tag of `parent`, or the end
+    /// of the source if there is no parent.
+    fn content(&mut self, parent: Option<&str>) -> Result<(Vec<Element>, String), FlameFileError> {
+        let mut children = vec![];
+        let mut text = String::new();
+        loop {
+            let rest = &self.source[self.position..];
+            if rest.is_empty() {
+                return match parent {
+                    Some(_) => Err(FlameFileError::Malformed(self.position)),
+                    None => Ok((children, text)),
+                };
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">")?;
+            } else if rest.starts_with("</") {
+                let start = self.position;
+                self.position += 2;
+                let name = self.name();
+                self.skip_whitespace();
+                if Some(name) != parent || !self.source[self.position..].starts_with('>') {
+                    return Err(FlameFileError::Malformed(start));
+                }
+                self.position += 1;
+                return Ok((children, text));
+            } else if rest.starts_with('<') {
+                children.push(self.element()?);
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                text.push_str(&decode_entities(&rest[..end]));
+                self.position += end;
+            }
+        }
+    }
+
+    fn element(&mut self) -> Result<Element, FlameFileError> {
+        let start = self.position;
+        self.position += 1;
+        let name = self.name().to_string();
+        if name.is_empty() {
+            return Err(FlameFileError::Malformed(start));
+        }
+
+        let mut attributes = vec![];
+        loop {
+            self.skip_whitespace();
+            let rest = &self.source[self.position..];
+            if rest.starts_with("/>") {
+                self.position += 2;
+                return Ok(Element {
+                    name,
+                    attributes,
+                    children: vec![],
+                    text: String::new(),
+                });
+            } else if rest.starts_with('>') {
+                self.position += 1;
+                let (children, text) = self.content(Some(&name))?;
+                return Ok(Element {
+                    name,
+                    attributes,
+                    children,
+                    text,
+                });
+            }
+            attributes.push(self.attribute()?);
+        }
+    }
+
+    fn attribute(&mut self) -> Result<(String, String), FlameFileError> {
+        let start = self.position;
+        let name = self.name().to_string();
+        self.skip_whitespace();
+        if name.is_empty() || !self.source[self.position..].starts_with('=') {
+            return Err(FlameFileError::Malformed(start));
+        }
+        self.position += 1;
+        self.skip_whitespace();
+
+        let quote = match self.source[self.position..].chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => return Err(FlameFileError::Malformed(self.position)),
+        };
+        self.position += 1;
+        let length = self.source[self.position..]
+            .find(quote)
+            .ok_or(FlameFileError::Malformed(start))?;
+        let value = decode_entities(&self.source[self.position..self.position + length]);
+        self.position += length + 1;
+        Ok((name, value))
+    }
+
+    fn name(&mut self) -> &'a str {
+        let rest = &self.source[self.position..];
+        let length = rest
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=')
+            .unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<(), FlameFileError> {
+        match self.source[self.position..].find(end) {
+            Some(index) => {
+                self.position += index + end.len();
+                Ok(())
+            }
+            None => Err(FlameFileError::Malformed(self.position)),
+        }
+    }
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        flame::Variation,
+        flame_file::{parse_flames, FlameFileError},
+    };
+    use num_complex::Complex;
+
+    const FLAMES: &str = r#"<?xml version="1.0"?>
+<!-- Exported from a flame editor. -->
+<flames name="test &amp; sample">
+  <flame name="first" size="800 600" center="0.5 -0.25" scale="200" brightness="3" gamma="2.5">
+    <xform weight="0.5" color="0" linear="0.75" swirl="0.25" coefs="1 2 3 4 5 6"/>
+    <xform weight="1" color="1" symmetry="1" spherical="1" blob="1" blob_low="0.2" coefs="0.5 0 0 0.5 0 0" post="1 0 0 1 0.5 0"/>
+    <finalxform color="0" color_speed="0" julia="1" coefs="1 0 0 1 0 0"/>
+    <color index="0" rgb="255 128 0"/>
+  </flame>
+  <flame size="100 100" scale="50">
+    <xform weight="1" linear="1" coefs="1 0 0 1 0 0"></xform>
+    <palette count="2" format="RGB">
+      FF0000 00ff80
+    </palette>
+  </flame>
+</flames>
+"#;
+
+    #[test]
+    fn parse_flame_file() {
+        let flames = parse_flames(FLAMES).unwrap();
+        assert_eq!(flames.len(), 2);
+
+        let flame = &flames[0];
+        assert_eq!(flame.center, Complex::new(0.5, -0.25));
+        assert_eq!(flame.width, 4.0);
+        assert_eq!(flame.brightness, 3.0);
+        assert_eq!(flame.gamma, 2.5);
+        assert_eq!(flame.vibrancy, 1.0);
+        assert_eq!(flame.palette[0], [255, 128, 0]);
+        assert_eq!(flame.palette[255], [255, 255, 255]);
+
+        let first = &flame.transforms[0];
+        assert_eq!(first.weight, 0.5);
+        assert_eq!(first.affine, [1.0, 3.0, 5.0, 2.0, 4.0, 6.0]);
+        assert_eq!(first.color_speed, 0.5);
+        assert_eq!(
+            first.variations,
+            vec![(Variation::Linear, 0.75), (Variation::Swirl, 0.25)]
+        );
+
+        // Unsupported variations and their parameters are skipped.
+        let second = &flame.transforms[1];
+        assert_eq!(second.color_speed, 0.0);
+        assert_eq!(second.variations, vec![(Variation::Spherical, 1.0)]);
+        assert_eq!(second.post_affine, Some([1.0, 0.0, 0.5, 0.0, 1.0, 0.0]));
+
+        let final_transform = flame.final_transform.as_ref().unwrap();
+        assert_eq!(final_transform.variations, vec![(Variation::Julia, 1.0)]);
+    }
+
+    #[test]
+    fn parse_hex_palette() {
+        let flame = &parse_flames(FLAMES).unwrap()[1];
+        assert_eq!(flame.palette[0], [255, 0, 0]);
+        assert_eq!(flame.palette[1], [0, 255, 128]);
+        assert_eq!(flame.final_transform, None);
+    }
+
+    #[test]
+    fn parse_flame_errors() {
+        assert_eq!(
+            parse_flames("<flames></flames>"),
+            Err(FlameFileError::NoFlames)
+        );
+        assert_eq!(
+            parse_flames("<flames><flame></flames>"),
+            Err(FlameFileError::Malformed(15))
+        );
+        assert_eq!(
+            parse_flames(r#"<flame size="1 1"/>"#),
+            Err(FlameFileError::MissingAttribute {
+                element: String::from("flame"),
+                attribute: "scale",
+            })
+        );
+        assert_eq!(
+            parse_flames(r#"<flame size="1" scale="1"/>"#),
+            Err(FlameFileError::InvalidValue {
+                attribute: String::from("size"),
+                value: String::from("1"),
+            })
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    generator::{complex_literal, float_literal, write_frag_main_start},
+    view::View,
+};
+use num_complex::Complex;
+use std::fmt::Write;
+
+/// A 2D fractal made by iterating geometric folds, scalings and inversions
+/// rather than a complex polynomial.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Fold {
+    /// A 2D slice of the Mandelbox, colored by escape time with the pixel
+    /// added after each fold.
+    Mandelbox {
+        scale: f32,
+        min_radius: f32,
+        fixed_radius: f32,
+    },
+    /// A kaleidoscopic iterated function system, which mirrors the plane
+    /// into one wedge, rotates it and scales it about an offset.
+    Kifs {
+        scale: f32,
+        /// The rotation after folding, in degrees.
+        angle: f32,
+        offset: Complex<f32>,
+    },
+    /// The Apollonian gasket, the limit set of folding into a square and
+    /// inverting in the unit circle.
+    Apollonian {
+        /// Scales each inversion, with 1 giving the classic gasket.
+        scale: f32,
+    },
+    /// The limit set of a Kleinian group from the Maskit slice, drawn with
+    /// Jos Leys' algorithm.
+    Kleinian {
+        /// The trace of the group's generator.
+        trace: Complex<f32>,
+    },
+}
+
+/// Renders a fold fractal.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FoldFractal {
+    pub fold: Fold,
+    /// The most times the transforms are applied to each pixel.
+    pub iterations: u32,
+}
+
+impl FoldFractal {
+    /// A view framing the fractal.
+    pub fn view(&self, image_width: usize, image_height: usize) -> View {
+        match self.fold {
+            Fold::Mandelbox { .. } => View::new_centered_uniform(image_width, image_height, 12.0),
+            Fold::Kifs { .. } => View::new_centered_uniform(image_width, image_height, 4.0),
+            Fold::Apollonian { .. } => View::new_centered_uniform(image_width, image_height, 3.0),
+            // The limit set lies between the real axis and the trace's real
+            // part.
+            Fold::Kleinian { trace } => {
+                View::new_uniform(image_width, image_height, 2.5, 0.0, trace.re / 2.0)
+            }
+        }
+    }
+
+    /// Writes the fragment stage.
+    pub fn write_shader(&self, out: &mut String) {
+        write_frag_main_start(out);
+        match self.fold {
+            Fold::Mandelbox {
+                scale,
+                min_radius,
+                fixed_radius,
+            } => write!(
+                out,
+                r#"    let min_radius_sqr = {min_radius_sqr};
+    let fixed_radius_sqr = {fixed_radius_sqr};
+    var z = pixel;
+    var n: u32 = 0u;
+    for (; n < {iterations}u; n = n + 1u) {{
+        // Box fold.
+        z = clamp(z, vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0)) * 2.0 - z;
+
+        // Circle fold.
+        let r2 = length_sqr(z);
+        if (r2 < min_radius_sqr) {{
+            z = z * (fixed_radius_sqr / min_radius_sqr);
+        }} else {{
+            if (r2 < fixed_radius_sqr) {{
+                z = z * (fixed_radius_sqr / r2);
+            }}
+        }}
+
+        z = {scale} * z + pixel;
+        if (length_sqr(z) > 10000.0) {{
+            break;
+        }}
+    }}
+
+    if (n >= {iterations}u) {{
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }}
+    let v = f32(n);
+    return fromHSB((v * 3.3 / 256.0) % 1.0, 1.0, (v / 16.0) % 1.0, 1.0);
+}}
+"#,
+                iterations = self.iterations,
+                min_radius_sqr = float_literal(min_radius * min_radius),
+                fixed_radius_sqr = float_literal(fixed_radius * fixed_radius),
+                scale = float_literal(scale),
+            ),
+            Fold::Kifs {
+                scale,
+                angle,
+                offset,
+            } => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                write!(
+                    out,
+                    r#"    let offset = {offset};
+    var z = pixel;
+    var size = 1.0;
+    for (var n: u32 = 0u; n < {iterations}u; n = n + 1u) {{
+        // Mirror into the wedge above the diagonal in the first quadrant.
+        z = abs(z);
+        if (z.x < z.y) {{
+            z = z.yx;
+        }}
+
+        z = vec2<f32>({cos} * z.x - {sin} * z.y, {sin} * z.x + {cos} * z.y);
+        z = {scale} * z - offset * ({scale} - 1.0);
+        size = size * {scale};
+    }}
+
+    // The distance to the fractal, measured in pixels.
+    let distance = length(z) / size / uniforms.view.image_scale.x;
+    return fromHSB(0.6, 0.5, 1.0 / (1.0 + distance), 1.0);
+}}
+"#,
+                    iterations = self.iterations,
+                    offset = complex_literal(offset),
+                    sin = float_literal(sin),
+                    cos = float_literal(cos),
+                    scale = float_literal(scale),
+                )
+            }
+            Fold::Apollonian { scale } => write!(
+                out,
+                r#"    var z = pixel;
+    var size = 1.0;
+    for (var n: u32 = 0u; n < {iterations}u; n = n + 1u) {{
+        // Fold into the square from -1 to 1, then invert in the unit circle.
+        z = -1.0 + 2.0 * fract(0.5 * z + 0.5);
+        let k = {scale} / length_sqr(z);
+        z = z * k;
+        size = size * k;
+    }}
+
+    // The distance to the gasket, measured in pixels.
+    let distance = 0.25 * abs(z.y) / size / uniforms.view.image_scale.x;
+    return fromHSB(0.08, 0.5, 1.0 / (1.0 + distance), 1.0);
+}}
+"#,
+                iterations = self.iterations,
+                scale = float_literal(scale),
+            ),
+            Fold::Kleinian { trace } => write!(
+                out,
+                r#"    // From "A fast rendering method for limit sets of Kleinian groups" by
+    // Jos Leys.
+    let a = {a};
+    let b = {b};
+    let f = sign(b);
+    var z = pixel;
+    var last_z = z + vec2<f32>(1.0, 0.0);
+    var second_last_z = z - vec2<f32>(1.0, 0.0);
+    var escaped = false;
+    var n: u32 = 0u;
+    for (; n < {iterations}u; n = n + 1u) {{
+        // Translate into the fundamental strip, along its slanted sides.
+        z.x = z.x + f * b / a * z.y;
+        z.x = z.x - 2.0 * floor((z.x + 1.0) / 2.0);
+        z.x = z.x - f * b / a * z.y;
+
+        // Points above the separation line are rotated half a turn about
+        // its center.
+        let side = z.x + b * 0.5;
+        let separation = a * 0.5 + f * (2.0 * a - 1.95) / 4.0 * sign(side) * (1.0 - exp(-(7.2 - (1.95 - a) * 15.0) * abs(side)));
+        if (z.y >= separation) {{
+            z = vec2<f32>(-b, a) - z;
+        }}
+
+        // Apply the generator a.
+        z = -z / length_sqr(z);
+        z = vec2<f32>(-b - z.x, a + z.y);
+
+        // Points settling into a 2-cycle are in the limit set.
+        if (length_sqr(z - second_last_z) < 0.000001) {{
+            break;
+        }}
+
+        if (z.y < 0.0 || z.y > a) {{
+            escaped = true;
+            break;
+        }}
+        second_last_z = last_z;
+        last_z = z;
+    }}
+
+    if (!escaped) {{
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }}
+    return fromHSB((f32(n) * 0.07) % 1.0, 0.6, 1.0, 1.0);
+}}
+"#,
+                iterations = self.iterations,
+                a = float_literal(trace.re),
+                b = float_literal(trace.im),
+            ),
+        }
+        .unwrap();
+    }
+}
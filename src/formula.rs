@@ -0,0 +1,617 @@
+use crate::{
+    complex::{self, Function},
+    generator::{complex_literal, float_literal},
+};
+use num_complex::Complex;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// An expression over complex numbers describing one step of an iteration.
+///
+/// Real-valued operations such as `Re`, `Im` and `Abs` produce complex numbers
+/// with a zero imaginary part, so every expression has the same type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// The current value of `z`.
+    Z,
+    /// The value of `z` before the current one.
+    PreviousZ,
+    C,
+    Constant(Complex<f32>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    /// Raises the first expression to the power of the second.
+    Pow(Box<Expr>, Box<Expr>),
+    Conj(Box<Expr>),
+    Re(Box<Expr>),
+    Im(Box<Expr>),
+    /// The modulus of an expression.
+    Abs(Box<Expr>),
+    /// Builds a complex number from the real parts of two expressions.
+    Complex(Box<Expr>, Box<Expr>),
+    /// Calls a function from the complex library.
+    Function(Function, Box<Expr>),
+}
+
+/// The values of the variables an expression can reference.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Variables {
+    pub z: Complex<f32>,
+    pub previous_z: Complex<f32>,
+    pub c: Complex<f32>,
+}
+
+/// An escape-time formula.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    /// The expressions computing the next `z`, applied in turn. Most formulas
+    /// have one, while hybrids alternate between several.
+    pub steps: Vec<Expr>,
+    /// The starting `z` when rendering the parameter plane.
+    pub critical_point: Complex<f32>,
+    /// The magnitude of `z` past which a point counts as escaped.
+    pub bailout: f32,
+    /// The symmetries of the parameter plane, declared by each family.
+    pub symmetries: Vec<Symmetry>,
+    /// The order of the rotational symmetry about 0 every Julia plane has,
+    /// declared by each family, where 1 is none. Turning `z` by an nth of a
+    /// turn leaves the next `z` unchanged, and `previous_z` is unused, so
+    /// orbits agree from the first step on.
+    pub julia_rotation: u32,
+}
+
+/// A symmetry of a plane that rendering can use to skip work.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Symmetry {
+    /// Conjugating `z`, `previous_z` and `c` conjugates the next `z`, so the
+    /// parameter plane, and the Julia plane of a real `c`, are mirrored about
+    /// the real axis.
+    RealAxis,
+    /// Turning `z`, `previous_z` and `c` by an nth of a turn about 0 turns the
+    /// next `z` the same way, so the parameter plane looks the same after
+    /// that turn when the critical point is 0. Rendering uses the half turn
+    /// that even orders include.
+    Rotational(u32),
+}
+
+/// The built-in formulas.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Family {
+    /// `z^p + c` with a real power.
+    Multibrot {
+        power: f32,
+    },
+    /// `z^p + c` with a complex power.
+    ComplexMultibrot {
+        power: Complex<f32>,
+    },
+    BurningShip,
+    /// `conj(z)^2 + c`, also known as the Mandelbar.
+    Tricorn,
+    Celtic,
+    Buffalo,
+    PerpendicularMandelbrot,
+    PerpendicularBurningShip,
+    PerpendicularCeltic,
+    PerpendicularBuffalo,
+    /// `z^2 + c + p * previous_z`.
+    Phoenix {
+        p: Complex<f32>,
+    },
+    MagnetI,
+    MagnetII,
+}
+
+impl Expr {
+    pub fn constant(re: f32, im: f32) -> Expr {
+        Expr::Constant(Complex::new(re, im))
+    }
+
+    pub fn pow(self, exponent: Expr) -> Expr {
+        Expr::Pow(Box::new(self), Box::new(exponent))
+    }
+
+    pub fn sqr(self) -> Expr {
+        self.pow(Expr::constant(2.0, 0.0))
+    }
+
+    pub fn conj(self) -> Expr {
+        Expr::Conj(Box::new(self))
+    }
+
+    pub fn re(self) -> Expr {
+        Expr::Re(Box::new(self))
+    }
+
+    pub fn im(self) -> Expr {
+        Expr::Im(Box::new(self))
+    }
+
+    pub fn abs(self) -> Expr {
+        Expr::Abs(Box::new(self))
+    }
+
+    pub fn complex(re: Expr, im: Expr) -> Expr {
+        Expr::Complex(Box::new(re), Box::new(im))
+    }
+
+    pub fn apply(self, function: Function) -> Expr {
+        Expr::Function(function, Box::new(self))
+    }
+
+    /// Evaluates this expression on the CPU, using the same formulas as the
+    /// shader.
+    pub fn evaluate(&self, vars: &Variables) -> Complex<f32> {
+        match self {
+            Expr::Z => vars.z,
+            Expr::PreviousZ => vars.previous_z,
+            Expr::C => vars.c,
+            Expr::Constant(value) => *value,
+            Expr::Add(a, b) => a.evaluate(vars) + b.evaluate(vars),
+            Expr::Sub(a, b) => a.evaluate(vars) - b.evaluate(vars),
+            Expr::Mul(a, b) => a.evaluate(vars) * b.evaluate(vars),
+            Expr::Div(a, b) => a.evaluate(vars) / b.evaluate(vars),
+            Expr::Neg(a) => -a.evaluate(vars),
+            Expr::Pow(a, b) => match **b {
+                Expr::Constant(exponent) if exponent.im == 0.0 => {
+                    complex::pow_real(a.evaluate(vars), exponent.re)
+                }
+                _ => complex::pow(a.evaluate(vars), b.evaluate(vars)),
+            },
+            Expr::Conj(a) => complex::conj(a.evaluate(vars)),
+            Expr::Re(a) => Complex::new(a.evaluate(vars).re, 0.0),
+            Expr::Im(a) => Complex::new(a.evaluate(vars).im, 0.0),
+            Expr::Abs(a) => complex::abs(a.evaluate(vars)),
+            Expr::Complex(a, b) => Complex::new(a.evaluate(vars).re, b.evaluate(vars).re),
+            Expr::Function(function, a) => function.apply(a.evaluate(vars)),
+        }
+    }
+
+    /// Converts this expression into a WGSL expression.
+    pub fn to_wgsl(&self) -> String {
+        match self {
+            Expr::Z => "z".to_string(),
+            Expr::PreviousZ => "previous_z".to_string(),
+            Expr::C => "c".to_string(),
+            Expr::Constant(value) => complex_literal(*value),
+            Expr::Add(a, b) => format!("({} + {})", a.to_wgsl(), b.to_wgsl()),
+            Expr::Sub(a, b) => format!("({} - {})", a.to_wgsl(), b.to_wgsl()),
+            Expr::Mul(a, b) => format!("complex_multiply({}, {})", a.to_wgsl(), b.to_wgsl()),
+            Expr::Div(a, b) => format!("complex_divide({}, {})", a.to_wgsl(), b.to_wgsl()),
+            Expr::Neg(a) => format!("(-{})", a.to_wgsl()),
+            Expr::Pow(a, b) => match **b {
+                Expr::Constant(exponent) if exponent == Complex::new(2.0, 0.0) => {
+                    format!("complex_sqr({})", a.to_wgsl())
+                }
+                Expr::Constant(exponent) if exponent.im == 0.0 => format!(
+                    "complex_pow_real({}, {})",
+                    a.to_wgsl(),
+                    float_literal(exponent.re)
+                ),
+                _ => format!("complex_pow({}, {})", a.to_wgsl(), b.to_wgsl()),
+            },
+            Expr::Conj(a) => format!("complex_conj({})", a.to_wgsl()),
+            Expr::Re(a) => format!("vec2<f32>({}.x, 0.0)", a.to_wgsl()),
+            Expr::Im(a) => format!("vec2<f32>({}.y, 0.0)", a.to_wgsl()),
+            Expr::Abs(a) => format!("complex_abs({})", a.to_wgsl()),
+            Expr::Complex(a, b) => format!("vec2<f32>({}.x, {}.x)", a.to_wgsl(), b.to_wgsl()),
+            Expr::Function(function, a) => format!("{}({})", function.wgsl_name(), a.to_wgsl()),
+        }
+    }
+}
+
+impl Add for Expr {
+    type Output = Expr;
+
+    fn add(self, rhs: Expr) -> Expr {
+        Expr::Add(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Sub for Expr {
+    type Output = Expr;
+
+    fn sub(self, rhs: Expr) -> Expr {
+        Expr::Sub(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Mul for Expr {
+    type Output = Expr;
+
+    fn mul(self, rhs: Expr) -> Expr {
+        Expr::Mul(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Div for Expr {
+    type Output = Expr;
+
+    fn div(self, rhs: Expr) -> Expr {
+        Expr::Div(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        Expr::Neg(Box::new(self))
+    }
+}
+
+impl Formula {
+    /// Builds a hybrid formula that applies each formula's step in turn,
+    /// so the `n`th iteration uses `formulas[n % formulas.len()]`.
+    ///
+    /// The hybrid starts from the first formula's critical point and uses the
+    /// largest bailout.
+    pub fn hybrid(formulas: &[Formula]) -> Formula {
+        Formula {
+            steps: formulas
+                .iter()
+                .flat_map(|formula| formula.steps.iter().cloned())
+                .collect(),
+            critical_point: formulas[0].critical_point,
+            bailout: formulas
+                .iter()
+                .map(|formula| formula.bailout)
+                .fold(0.0, f32::max),
+            // Hybrids only keep the symmetries every step has.
+            symmetries: shared_symmetries(formulas),
+            julia_rotation: formulas
+                .iter()
+                .map(|formula| formula.julia_rotation)
+                .fold(0, gcd),
+        }
+    }
+
+    /// The order of the formula's rotational symmetry about 0 in the
+    /// parameter plane, where 1 is none.
+    pub fn rotation(&self) -> u32 {
+        self.symmetries
+            .iter()
+            .find_map(|symmetry| match *symmetry {
+                Symmetry::Rotational(order) => Some(order),
+                Symmetry::RealAxis => None,
+            })
+            .unwrap_or(1)
+    }
+
+    /// Computes the next `z` on the CPU, using the step for iteration `n`.
+    pub fn step(&self, n: usize, vars: &Variables) -> Complex<f32> {
+        self.steps[n % self.steps.len()].evaluate(vars)
+    }
+}
+
+impl Family {
+    /// Builds this family's formula.
+    pub fn formula(&self) -> Formula {
+        use Expr::{C, Z};
+
+        let x = || Z.re();
+        let y = || Z.im();
+        let two = || Expr::constant(2.0, 0.0);
+        // x^2 - y^2, the real part of z^2.
+        let real_sqr = || x().sqr() - y().sqr();
+
+        let step = match *self {
+            Family::Multibrot { power } => Z.pow(Expr::constant(power, 0.0)) + C,
+            Family::ComplexMultibrot { power } => Z.pow(Expr::Constant(power)) + C,
+            Family::BurningShip => Expr::complex(x().abs(), y().abs()).sqr() + C,
+            Family::Tricorn => Z.conj().sqr() + C,
+            Family::Celtic => Expr::complex(Z.sqr().re().abs(), Z.sqr().im()) + C,
+            Family::Buffalo => Expr::complex(Z.sqr().re().abs(), Z.sqr().im().abs()) + C,
+            Family::PerpendicularMandelbrot => {
+                Expr::complex(real_sqr(), -(two() * x().abs() * y())) + C
+            }
+            Family::PerpendicularBurningShip => {
+                Expr::complex(real_sqr(), -(two() * x() * y().abs())) + C
+            }
+            Family::PerpendicularCeltic => {
+                Expr::complex(real_sqr().abs(), -(two() * x().abs() * y())) + C
+            }
+            Family::PerpendicularBuffalo => {
+                Expr::complex(real_sqr().abs(), -(two() * x() * y().abs())) + C
+            }
+            Family::Phoenix { p } => Z.sqr() + C + Expr::Constant(p) * Expr::PreviousZ,
+            Family::MagnetI => {
+                let one = || Expr::constant(1.0, 0.0);
+                let numerator = Z.sqr() + C - one();
+                let denominator = two() * Z + C - two();
+                (numerator / denominator).sqr()
+            }
+            Family::MagnetII => {
+                let one = || Expr::constant(1.0, 0.0);
+                let three = || Expr::constant(3.0, 0.0);
+                let c_minus_one = || C - one();
+                let c_minus_two = || C - two();
+                let numerator =
+                    Z.pow(three()) + three() * c_minus_one() * Z + c_minus_one() * c_minus_two();
+                let denominator = three() * Z.sqr()
+                    + three() * c_minus_two() * Z
+                    + c_minus_one() * c_minus_two()
+                    + one();
+                (numerator / denominator).sqr()
+            }
+        };
+
+        let bailout = match self {
+            Family::MagnetI | Family::MagnetII => 100.0,
+            _ => 4.0,
+        };
+
+        // Formulas taking the absolute value of the imaginary part on its own
+        // lose the mirror symmetry, as do complex constants.
+        let mut symmetries = match *self {
+            Family::ComplexMultibrot { power } if power.im != 0.0 => vec![],
+            Family::Phoenix { p } if p.im != 0.0 => vec![],
+            Family::BurningShip
+            | Family::Buffalo
+            | Family::PerpendicularBurningShip
+            | Family::PerpendicularBuffalo => vec![],
+            _ => vec![Symmetry::RealAxis],
+        };
+
+        // Turning `z` by a root of unity `w` multiplies `z^d` by `w^d`, which
+        // is `w` when `w^(d - 1) = 1`, turning the next `z` along with `c`, and
+        // 1 when `w^d = 1`, leaving it unchanged in Julia planes. Folding the
+        // signs of both parts, or conjugating, before squaring also ignores
+        // half turns, as does folding afterwards, where the Buffalo's folds
+        // also ignore the quarter turns that negate `z^2`. The Tricorn's
+        // conjugate turns with thirds of a turn.
+        let whole_power = match *self {
+            Family::Multibrot { power } if power.fract() == 0.0 => Some(power as i32),
+            _ => None,
+        };
+        let rotation = match *self {
+            Family::Tricorn => 3,
+            _ => whole_power.map_or(1, |d| (d - 1).unsigned_abs()),
+        };
+        if rotation >= 2 {
+            symmetries.push(Symmetry::Rotational(rotation));
+        }
+        let julia_rotation = match *self {
+            Family::BurningShip | Family::Tricorn | Family::Celtic => 2,
+            Family::Buffalo => 4,
+            _ => whole_power.map_or(1, |d| d.unsigned_abs().max(1)),
+        };
+
+        Formula {
+            steps: vec![step],
+            critical_point: Complex::new(0.0, 0.0),
+            bailout,
+            symmetries,
+            julia_rotation,
+        }
+    }
+}
+
+/// The symmetries every formula has, keeping the rotations they share.
+fn shared_symmetries(formulas: &[Formula]) -> Vec<Symmetry> {
+    let mut symmetries = vec![];
+    if formulas
+        .iter()
+        .all(|formula| formula.symmetries.contains(&Symmetry::RealAxis))
+    {
+        symmetries.push(Symmetry::RealAxis);
+    }
+    let rotation = formulas.iter().map(Formula::rotation).fold(0, gcd);
+    if rotation >= 2 {
+        symmetries.push(Symmetry::Rotational(rotation));
+    }
+    symmetries
+}
+
+/// The greatest common divisor of two numbers, where `gcd(0, n)` is `n`.
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::formula::{Family, Formula, Symmetry, Variables};
+    use num_complex::Complex;
+
+    fn step(family: Family, z: Complex<f32>, c: Complex<f32>) -> Complex<f32> {
+        family.formula().step(
+            0,
+            &Variables {
+                z,
+                previous_z: Complex::new(0.0, 0.0),
+                c,
+            },
+        )
+    }
+
+    fn assert_close(a: Complex<f32>, b: Complex<f32>) {
+        assert!((a - b).norm() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn mandelbrot_step() {
+        let z = Complex::new(0.5, -0.25);
+        let c = Complex::new(-0.75, 0.1);
+        assert_close(step(Family::Multibrot { power: 2.0 }, z, c), z * z + c);
+    }
+
+    #[test]
+    fn burning_ship_step() {
+        let z = Complex::new(-0.5, -0.25);
+        let c = Complex::new(0.1, 0.2);
+        let folded = Complex::new(0.5, 0.25);
+        assert_close(step(Family::BurningShip, z, c), folded * folded + c);
+    }
+
+    #[test]
+    fn tricorn_step() {
+        let z = Complex::new(0.3, 0.4);
+        let c = Complex::new(0.1, 0.2);
+        assert_close(step(Family::Tricorn, z, c), z.conj() * z.conj() + c);
+    }
+
+    #[test]
+    fn phoenix_step() {
+        let family = Family::Phoenix {
+            p: Complex::new(-0.5, 0.0),
+        };
+        let z = Complex::new(0.3, 0.4);
+        let previous_z = Complex::new(0.2, -0.1);
+        let c = Complex::new(0.56, 0.0);
+        let next = family.formula().step(0, &Variables { z, previous_z, c });
+        assert_close(next, z * z + c - 0.5 * previous_z);
+    }
+
+    #[test]
+    fn perpendicular_mandelbrot_step() {
+        let z = Complex::new(-0.3, 0.4);
+        let c = Complex::new(0.1, 0.2);
+        let expected = Complex::new(0.3 * 0.3 - 0.4 * 0.4, -2.0 * 0.3 * 0.4) + c;
+        assert_close(step(Family::PerpendicularMandelbrot, z, c), expected);
+    }
+
+    #[test]
+    fn hybrid_step() {
+        let hybrid = Formula::hybrid(&[
+            Family::Multibrot { power: 2.0 }.formula(),
+            Family::BurningShip.formula(),
+            Family::BurningShip.formula(),
+        ]);
+        assert_eq!(hybrid.steps.len(), 3);
+        assert_eq!(hybrid.bailout, 4.0);
+        assert_eq!(hybrid.symmetries, vec![]);
+        assert_eq!(hybrid.julia_rotation, 2);
+
+        let c = Complex::new(0.1, 0.2);
+        let mut z = Complex::new(-0.5, -0.25);
+        let mut expected = z;
+        for n in 0..6 {
+            let vars = Variables {
+                z,
+                previous_z: Complex::new(0.0, 0.0),
+                c,
+            };
+            z = hybrid.step(n, &vars);
+            if n % 3 != 0 {
+                expected = Complex::new(expected.re.abs(), expected.im.abs());
+            }
+            expected = expected * expected + c;
+            assert_close(z, expected);
+        }
+    }
+
+    const FAMILIES: [Family; 16] = [
+        Family::Multibrot { power: 2.0 },
+        Family::Multibrot { power: 3.0 },
+        Family::Multibrot { power: -2.0 },
+        Family::Multibrot { power: 3.5 },
+        Family::ComplexMultibrot {
+            power: Complex::new(2.0, 0.5),
+        },
+        Family::BurningShip,
+        Family::Tricorn,
+        Family::Celtic,
+        Family::Buffalo,
+        Family::PerpendicularMandelbrot,
+        Family::PerpendicularBurningShip,
+        Family::PerpendicularCeltic,
+        Family::PerpendicularBuffalo,
+        Family::Phoenix {
+            p: Complex::new(-0.5, 0.0),
+        },
+        Family::MagnetI,
+        Family::MagnetII,
+    ];
+
+    #[test]
+    fn declared_symmetries_hold() {
+        let z = Complex::new(0.3, -0.45);
+        let previous_z = Complex::new(-0.2, 0.1);
+        let c = Complex::new(-0.6, 0.35);
+        for &family in &FAMILIES {
+            let formula = family.formula();
+            let next = formula.step(0, &Variables { z, previous_z, c });
+            let mirrored = formula.step(
+                0,
+                &Variables {
+                    z: z.conj(),
+                    previous_z: previous_z.conj(),
+                    c: c.conj(),
+                },
+            );
+            let symmetric = (mirrored - next.conj()).norm() < 1e-4;
+            assert_eq!(
+                symmetric,
+                formula.symmetries.contains(&Symmetry::RealAxis),
+                "{:?}",
+                family
+            );
+        }
+    }
+
+    #[test]
+    fn declared_rotations_hold() {
+        // Folding formulas act like other formulas on parts of the plane, so
+        // the turned points lie all around 0.
+        let points: Vec<_> = (0..8)
+            .map(|index| Complex::from_polar(0.5, 0.3 + index as f32 * 0.8))
+            .collect();
+        let previous_z = Complex::new(-0.2, 0.1);
+        let c = Complex::new(-0.6, 0.35);
+        for &family in &FAMILIES {
+            let formula = family.formula();
+            for order in 2..=6 {
+                let turn = Complex::from_polar(1.0, std::f32::consts::TAU / order as f32);
+
+                let turns_with_c = points.iter().all(|&z| {
+                    let next = formula.step(0, &Variables { z, previous_z, c });
+                    let turned = formula.step(
+                        0,
+                        &Variables {
+                            z: z * turn,
+                            previous_z: previous_z * turn,
+                            c: c * turn,
+                        },
+                    );
+                    (turned - next * turn).norm() < 1e-4
+                });
+                assert_eq!(
+                    turns_with_c,
+                    formula.rotation() % order == 0,
+                    "{:?} turned by 1/{}",
+                    family,
+                    order
+                );
+
+                // Julia planes keep `c` and must ignore `previous_z`.
+                let ignores_turn = points.iter().all(|&z| {
+                    let next = formula.step(0, &Variables { z, previous_z, c });
+                    let turned = formula.step(
+                        0,
+                        &Variables {
+                            z: z * turn,
+                            previous_z: -previous_z,
+                            c,
+                        },
+                    );
+                    (turned - next).norm() < 1e-4
+                });
+                assert_eq!(
+                    ignores_turn,
+                    formula.julia_rotation % order == 0,
+                    "{:?} Julia plane turned by 1/{}",
+                    family,
+                    order
+                );
+            }
+        }
+    }
+}
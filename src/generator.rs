@@ -0,0 +1,991 @@
+use crate::{
+    coloring::ExteriorColoring,
+    complex::write_library,
+    derivative::Derivatives,
+    formula::Expr,
+    plane_transform::{write_chain, PlaneTransform},
+    projection::Projection,
+    scene::{Plane, RenderMode, Scene},
+};
+use num_complex::Complex;
+use std::fmt::Write;
+
+const TEMPLATE_SOURCE: &str = include_str!("template.wgsl");
+
+/// Invocations in each workgroup of the `border_main` compute stage.
+pub const BORDER_WORKGROUP_SIZE: u32 = 64;
+
+/// Generates the WGSL source of the fractal shader for a scene.
+///
+/// The template supplies the uniforms, vertex stage and utility functions
+/// while the fragment stage is generated to include whatever per-iteration
+/// state the scene's coloring needs.
+pub fn generate_shader(scene: &Scene) -> String {
+    let mut body = String::new();
+    match &scene.mode {
+        RenderMode::EscapeTime => {
+            write_escape_time(&mut body, scene, &scene.projection, &scene.plane_transforms)
+        }
+        RenderMode::ExponentialMap(map) => {
+            // Strip pixels are mapped onto the plane before the scene's own
+            // transforms.
+            let mut transforms = vec![map.transform()];
+            transforms.extend_from_slice(&scene.plane_transforms);
+            write_escape_time(&mut body, scene, &Projection::Flat, &transforms);
+        }
+        RenderMode::RootFinding(root_finding) => root_finding.write_shader(&mut body),
+        RenderMode::DomainColoring(domain_coloring) => domain_coloring.write_shader(&mut body),
+        RenderMode::Buddhabrot(buddhabrot) => buddhabrot.write_shader(&mut body, scene),
+        RenderMode::Attractor(attractor) => attractor.write_shader(&mut body),
+        RenderMode::Lyapunov(lyapunov) => lyapunov.write_shader(&mut body),
+        RenderMode::RayMarch(ray_march) => ray_march.write_shader(&mut body),
+        RenderMode::Fold(fold) => fold.write_shader(&mut body),
+        RenderMode::Flame(flame) => flame.write_shader(&mut body),
+    }
+
+    // Only the complex functions the generated code calls are included.
+    let mut source = String::from(TEMPLATE_SOURCE);
+    writeln!(source, "\nlet iterations: i32 = {};", scene.iterations).unwrap();
+    write_library(&mut source, &body);
+    source.push_str(&body);
+    source
+}
+
+/// Writes the formula, colorings and fragment stage of an escape-time fractal
+/// whose pixels are projected by `projection` then remapped by `transforms`.
+fn write_escape_time(
+    out: &mut String,
+    scene: &Scene,
+    projection: &Projection,
+    transforms: &[PlaneTransform],
+) {
+    write_formula(out, scene);
+    projection.write_function(out);
+    write_chain(out, transforms);
+    scene.exterior.write_functions(out);
+    scene.interior.write_functions(out);
+    scene.applicable_interior_checks().write_functions(out);
+    write_frag_main(out, scene, projection);
+}
+
+/// Formats a float as a WGSL float literal.
+pub fn float_literal(value: f32) -> String {
+    let literal = format!("{:?}", value);
+
+    // Debug formatting leaves out the fraction when using an exponent.
+    match literal.find('e') {
+        Some(index) if !literal[..index].contains('.') => {
+            format!("{}.0{}", &literal[..index], &literal[index..])
+        }
+        _ => literal,
+    }
+}
+
+/// Formats a complex number as a WGSL `vec2<f32>` constructor.
+pub fn complex_literal(value: Complex<f32>) -> String {
+    format!(
+        "vec2<f32>({}, {})",
+        float_literal(value.re),
+        float_literal(value.im)
+    )
+}
+
+/// Writes the formula's bailout and the function `f` computing its next `z`,
+/// followed by its partial derivatives when the formula has them.
+///
+/// Each function takes the iteration number `n`, which hybrid formulas use
+/// to pick their step.
+pub fn write_formula(out: &mut String, scene: &Scene) {
+    let steps = &scene.formula.steps;
+    writeln!(
+        out,
+        "\nlet bailout: f32 = {};",
+        float_literal(scene.formula.bailout)
+    )
+    .unwrap();
+    write_step_function(
+        out,
+        "f",
+        &steps.iter().map(Expr::to_wgsl).collect::<Vec<_>>(),
+    );
+
+    let derivatives: Result<Vec<_>, _> = steps.iter().map(Derivatives::of).collect();
+    if let Ok(derivatives) = derivatives {
+        let wgsl = |derivative: fn(&Derivatives) -> &Expr| -> Vec<String> {
+            derivatives
+                .iter()
+                .map(|d| derivative(d).to_wgsl())
+                .collect()
+        };
+        write_step_function(out, "f_dz", &wgsl(|d| &d.dz));
+        write_step_function(out, "f_dc", &wgsl(|d| &d.dc));
+        write_step_function(out, "f_dzdz", &wgsl(|d| &d.dzdz));
+        write_step_function(out, "f_dzdc", &wgsl(|d| &d.dzdc));
+    }
+}
+
+/// Writes a function of `z`, `previous_z`, `c` and `n` returning one of the
+/// expressions, taken in turn as `n` increases.
+fn write_step_function(out: &mut String, name: &str, expressions: &[String]) {
+    write!(
+        out,
+        "\nfn {}(z: vec2<f32>, previous_z: vec2<f32>, c: vec2<f32>, n: i32) -> vec2<f32> {{\n",
+        name
+    )
+    .unwrap();
+    match expressions {
+        [expression] => writeln!(out, "    return {};", expression).unwrap(),
+        _ => {
+            writeln!(out, "    switch (n % {}) {{", expressions.len()).unwrap();
+            for (i, expression) in expressions.iter().enumerate() {
+                if i + 1 < expressions.len() {
+                    writeln!(out, "        case {}: {{", i).unwrap();
+                } else {
+                    out.push_str("        default: {\n");
+                }
+                writeln!(out, "            return {};\n        }}", expression).unwrap();
+            }
+            out.push_str("    }\n");
+        }
+    }
+    out.push_str("}\n");
+}
+
+/// Writes the start of the fragment stage, up to computing the pixel's
+/// position on the complex plane.
+pub fn write_frag_main_start(out: &mut String) {
+    out.push_str(
+        r#"
+[[stage(fragment)]]
+fn frag_main(data: FragmentData) -> [[location(0)]] vec4<f32> {
+    // Only generate fractals for the requested area.
+    if (data.position.x >= uniforms.view.image_size.x || data.position.y >= uniforms.view.image_size.y) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let pixel = uniforms.view.plane_start + (data.position.xy + offset) * uniforms.view.image_scale;
+"#,
+    );
+}
+
+/// Writes `c` and the starting `z` of the pixel at `pixel`.
+pub fn write_start_point(out: &mut String, scene: &Scene) {
+    match scene.plane {
+        Plane::Parameter => write!(
+            out,
+            "    let c = plane_transform(project(pixel));\n    var z = {};\n",
+            complex_literal(scene.formula.critical_point)
+        ),
+        Plane::Julia(c) => write!(
+            out,
+            "    let c = {};\n    var z = plane_transform(project(pixel));\n",
+            complex_literal(c)
+        ),
+    }
+    .unwrap();
+}
+
+/// Writes the `color` function computing the color of the pixel at a
+/// position in the image, followed by the fragment stage calling it, and the
+/// `border_main` compute stage when the scene is rendered with Mariani–Silver.
+fn write_frag_main(out: &mut String, scene: &Scene, projection: &Projection) {
+    out.push_str(
+        r#"
+fn color(position: vec2<f32>) -> vec4<f32> {
+    let pixel = uniforms.view.plane_start + (position + offset) * uniforms.view.image_scale;
+"#,
+    );
+    projection.write_miss_check(out);
+    write_start_point(out, scene);
+    out.push_str("    var previous_z = vec2<f32>(0.0, 0.0);\n");
+    scene.exterior.write_loop_state(out);
+    scene.interior.write_loop_state(out);
+    let interior_checks = scene.applicable_interior_checks();
+    out.push_str("\n    var n: i32 = 0;\n");
+    interior_checks.write_loop_state(out);
+    out.push_str(
+        r#"    for (; n < iterations; n = n + 1) {
+        if (length_sqr(z) > bailout * bailout) {
+            break;
+        }
+
+"#,
+    );
+    scene.exterior.write_iteration(out);
+    scene.interior.write_iteration(out);
+    out.push_str(
+        r#"        let z_next = f(z, previous_z, c, n);
+        previous_z = z;
+        z = z_next;
+"#,
+    );
+    scene.exterior.write_after_step(out);
+    interior_checks.write_after_step(out, scene.formula.steps.len());
+    write!(
+        out,
+        "    }}\n\n    if ({}) {{\n",
+        interior_checks.interior_condition()
+    )
+    .unwrap();
+    scene.interior.write_color(out);
+    out.push_str("    } else {\n");
+    scene.exterior.write_color(out);
+    out.push_str(
+        r#"    }
+}
+
+[[stage(fragment)]]
+fn frag_main(data: FragmentData) -> [[location(0)]] vec4<f32> {
+    // Only generate fractals for the requested area.
+    if (data.position.x >= uniforms.view.image_size.x || data.position.y >= uniforms.view.image_size.y) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return color(data.position.xy);
+}
+"#,
+    );
+
+    // Multi-pass rendering binds its own buffers in place of the border's.
+    match (&scene.multi_pass, &scene.mode) {
+        (Some(multi_pass), RenderMode::EscapeTime) => multi_pass.write_stages(out, scene),
+        _ => {
+            if scene.mariani_silver.is_some() {
+                write_border_main(out, compute_group(scene));
+            }
+        }
+    }
+}
+
+/// The bind group of the image trap's texture, after the uniforms.
+pub const TRAP_TEXTURE_GROUP: u32 = 1;
+
+/// The bind group of the buffers the `border_main` or multi-pass compute
+/// stages use, after the image trap's texture if there is one.
+pub fn compute_group(scene: &Scene) -> u32 {
+    match scene.exterior {
+        ExteriorColoring::ImageTrap(_) => TRAP_TEXTURE_GROUP + 1,
+        _ => 1,
+    }
+}
+
+/// Writes the `border_main` compute stage, which colors a list of pixels
+/// each packed as `x | y << 16`, writing RGBA8 colors packed the same way as
+/// the framebuffer.
+fn write_border_main(out: &mut String, group: u32) {
+    write!(
+        out,
+        r#"
+[[block]]
+struct PixelPositions {{
+    positions: array<u32>;
+}};
+
+[[block]]
+struct PixelColors {{
+    colors: array<u32>;
+}};
+
+[[group({group}), binding(0)]]
+var<storage, read> border_positions: PixelPositions;
+[[group({group}), binding(1)]]
+var<storage, read_write> border_colors: PixelColors;
+
+[[stage(compute), workgroup_size({workgroup_size})]]
+fn border_main([[builtin(global_invocation_id)]] id: vec3<u32>) {{
+    if (id.x >= arrayLength(&border_positions.positions)) {{
+        return;
+    }}
+    let packed = border_positions.positions[id.x];
+    let position = vec2<f32>(f32(packed & 65535u), f32(packed >> 16u)) + vec2<f32>(0.5, 0.5);
+    let channels = clamp(color(position), vec4<f32>(0.0, 0.0, 0.0, 0.0), vec4<f32>(1.0, 1.0, 1.0, 1.0)) * 255.0 + vec4<f32>(0.5, 0.5, 0.5, 0.5);
+    border_colors.colors[id.x] = u32(channels.r) | (u32(channels.g) << 8u) | (u32(channels.b) << 16u) | (u32(channels.a) << 24u);
+}}
+"#,
+        group = group,
+        workgroup_size = BORDER_WORKGROUP_SIZE,
+    )
+    .unwrap();
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        attractor::{Attractor, AttractorMap},
+        buddhabrot::Buddhabrot,
+        camera::Camera,
+        coloring::{
+            AverageStatistic, CycleDetection, ExteriorColoring, ImageTrap, InteriorColoring,
+            OrbitAverage, OrbitTrap, TrapBlend, TrapColoring, TrapShape,
+        },
+        domain_coloring::DomainColoring,
+        exponential_map::ExponentialMap,
+        flame::{Flame, FractalFlame, Transform, Variation},
+        fold::{Fold, FoldFractal},
+        formula::{Family, Formula},
+        generator::{float_literal, generate_shader},
+        interior_check::InteriorChecks,
+        lyapunov::{parse_sequence, Lyapunov},
+        mariani_silver::MarianiSilver,
+        multi_pass::MultiPass,
+        palette::Palette,
+        parser::parse_expr,
+        plane_transform::PlaneTransform,
+        projection::Projection,
+        ray_march::{Fractal3D, RayMarch},
+        root_finding::{RootFinding, RootMethod},
+        scene::{Plane, RenderMode, Scene},
+    };
+    use cgmath::Vector3;
+    use naga::{
+        front,
+        valid::{ValidationFlags, Validator},
+    };
+    use num_complex::Complex;
+
+    fn validate(scene: &Scene) {
+        let module = front::wgsl::parse_str(&generate_shader(scene)).unwrap();
+        Validator::new(ValidationFlags::all(), Default::default())
+            .validate(&module)
+            .unwrap();
+    }
+
+    fn exterior(exterior: ExteriorColoring) -> Scene {
+        Scene {
+            plane: Plane::Julia(Complex::new(0.16611, 0.59419)),
+            exterior,
+            ..Scene::test_mandelbrot()
+        }
+    }
+
+    #[test]
+    fn float_literals() {
+        assert_eq!(float_literal(2.0), "2.0");
+        assert_eq!(float_literal(0.25), "0.25");
+        assert_eq!(float_literal(1e-7), "1.0e-7");
+    }
+
+    #[test]
+    fn generate_iteration() {
+        validate(&exterior(ExteriorColoring::Iteration));
+    }
+
+    #[test]
+    fn generate_orbit_traps() {
+        for &shape in &[
+            TrapShape::Point,
+            TrapShape::Line,
+            TrapShape::Circle,
+            TrapShape::Cross,
+        ] {
+            for &coloring in &[
+                TrapColoring::Distance,
+                TrapColoring::Iteration,
+                TrapColoring::DistanceAndIteration,
+            ] {
+                validate(&exterior(ExteriorColoring::OrbitTrap(OrbitTrap {
+                    shape,
+                    coloring,
+                    position: Complex::new(0.0, 0.0),
+                    angle: 0.0,
+                    radius: 1.0,
+                    scale: 1.0,
+                })));
+            }
+        }
+    }
+
+    #[test]
+    fn generate_image_traps() {
+        for &blend in &[TrapBlend::FirstHit, TrapBlend::LastHit] {
+            validate(&exterior(ExteriorColoring::ImageTrap(ImageTrap {
+                path: "trap.png".into(),
+                blend,
+                position: Complex::new(-1.0, -1.0),
+                size: Complex::new(2.0, 2.0),
+                alpha_threshold: 0.5,
+            })));
+        }
+    }
+
+    #[test]
+    fn generate_averages() {
+        for &statistic in &[
+            AverageStatistic::Stripe,
+            AverageStatistic::TriangleInequality,
+            AverageStatistic::Curvature,
+        ] {
+            validate(&exterior(ExteriorColoring::Average(OrbitAverage {
+                statistic,
+                stripe_density: 5.0,
+                skip_iterations: 1,
+            })));
+        }
+    }
+
+    #[test]
+    fn generate_interiors() {
+        let cycle = CycleDetection {
+            epsilon: 0.0001,
+            max_period: 64,
+        };
+        for &interior in &[
+            InteriorColoring::Magnitude { scale: 1.0 },
+            InteriorColoring::Period(cycle),
+            InteriorColoring::DistanceEstimate { cycle, scale: 1.0 },
+            InteriorColoring::AtomDomain,
+        ] {
+            validate(&Scene {
+                interior,
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_distance_estimates() {
+        let cycle = CycleDetection {
+            epsilon: 0.0001,
+            max_period: 64,
+        };
+        for &family in &[
+            Family::Multibrot { power: 3.0 },
+            Family::ComplexMultibrot {
+                power: Complex::new(2.0, 0.5),
+            },
+            Family::MagnetI,
+            Family::MagnetII,
+        ] {
+            validate(&Scene {
+                formula: family.formula(),
+                interior: InteriorColoring::DistanceEstimate { cycle, scale: 1.0 },
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_families() {
+        for &family in &[
+            Family::Multibrot { power: 2.0 },
+            Family::Multibrot { power: 3.5 },
+            Family::ComplexMultibrot {
+                power: Complex::new(2.0, 0.5),
+            },
+            Family::BurningShip,
+            Family::Tricorn,
+            Family::Celtic,
+            Family::Buffalo,
+            Family::PerpendicularMandelbrot,
+            Family::PerpendicularBurningShip,
+            Family::PerpendicularCeltic,
+            Family::PerpendicularBuffalo,
+            Family::Phoenix {
+                p: Complex::new(-0.5, 0.0),
+            },
+            Family::MagnetI,
+            Family::MagnetII,
+        ] {
+            validate(&Scene {
+                formula: family.formula(),
+                plane: Plane::Parameter,
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_interior_checks() {
+        let checks = InteriorChecks {
+            cardioid: true,
+            periodicity: Some(1e-5),
+        };
+        let mandelbrot = Scene {
+            plane: Plane::Parameter,
+            interior_checks: checks,
+            ..exterior(ExteriorColoring::Iteration)
+        };
+        assert_eq!(mandelbrot.applicable_interior_checks(), checks);
+        let source = generate_shader(&mandelbrot);
+        assert!(source.contains("in_cardioid_or_bulb(c)"));
+        assert!(source.contains("cycle_found"));
+        validate(&mandelbrot);
+
+        // Julia sets and hybrids only get periodicity checking.
+        let julia = Scene {
+            interior_checks: checks,
+            ..exterior(ExteriorColoring::Iteration)
+        };
+        assert!(!julia.applicable_interior_checks().cardioid);
+        validate(&julia);
+        validate(&Scene {
+            formula: Formula::hybrid(&[
+                Family::Multibrot { power: 2.0 }.formula(),
+                Family::BurningShip.formula(),
+            ]),
+            ..mandelbrot.clone()
+        });
+        validate(&Scene {
+            interior: InteriorColoring::Period(CycleDetection {
+                epsilon: 0.0001,
+                max_period: 64,
+            }),
+            ..mandelbrot.clone()
+        });
+
+        // Colorings that need the whole orbit turn the checks off.
+        let magnitude = Scene {
+            interior: InteriorColoring::Magnitude { scale: 1.0 },
+            ..mandelbrot
+        };
+        assert_eq!(magnitude.applicable_interior_checks(), InteriorChecks::NONE);
+    }
+
+    #[test]
+    fn generate_border_main() {
+        let mariani_silver = Scene {
+            mariani_silver: Some(MarianiSilver { min_size: 8 }),
+            ..exterior(ExteriorColoring::Iteration)
+        };
+        assert!(generate_shader(&mariani_silver).contains("[[group(1), binding(0)]]"));
+        validate(&mariani_silver);
+
+        // Image traps already use the first group after the uniforms.
+        let image_trap = Scene {
+            exterior: ExteriorColoring::ImageTrap(ImageTrap {
+                path: "trap.png".into(),
+                blend: TrapBlend::FirstHit,
+                position: Complex::new(-1.0, -1.0),
+                size: Complex::new(2.0, 2.0),
+                alpha_threshold: 0.5,
+            }),
+            ..mariani_silver
+        };
+        assert!(generate_shader(&image_trap).contains("[[group(2), binding(0)]]"));
+        validate(&image_trap);
+    }
+
+    #[test]
+    fn colors_independent_of_limit() {
+        // Refined tiles are stitched next to tiles rendered at lower limits,
+        // so the limit may only decide which pixels are interior.
+        let colorings = vec![
+            ExteriorColoring::Iteration,
+            ExteriorColoring::OrbitTrap(OrbitTrap {
+                shape: TrapShape::Cross,
+                coloring: TrapColoring::DistanceAndIteration,
+                position: Complex::new(0.0, 0.0),
+                angle: 0.0,
+                radius: 1.0,
+                scale: 1.0,
+            }),
+        ];
+        let cycle = CycleDetection {
+            epsilon: 0.0001,
+            max_period: 64,
+        };
+        let interiors = [
+            InteriorColoring::Black,
+            InteriorColoring::Period(cycle),
+            InteriorColoring::DistanceEstimate { cycle, scale: 1.0 },
+        ];
+        for coloring in colorings {
+            for &interior in &interiors {
+                assert!(!interior.depends_on_limit());
+                let low = Scene {
+                    interior,
+                    ..exterior(coloring.clone())
+                };
+                let high = Scene {
+                    iterations: 1600,
+                    ..low.clone()
+                };
+                assert_eq!(
+                    generate_shader(&low).replace("let iterations: i32 = 200;", ""),
+                    generate_shader(&high).replace("let iterations: i32 = 1600;", "")
+                );
+            }
+        }
+
+        // The final `z` and the iteration closest to 0 move as the limit
+        // grows, so refinement rejects these.
+        assert!(InteriorColoring::Magnitude { scale: 1.0 }.depends_on_limit());
+        assert!(InteriorColoring::AtomDomain.depends_on_limit());
+    }
+
+    #[test]
+    fn generate_multi_pass() {
+        let multi_pass = Scene {
+            plane: Plane::Parameter,
+            interior_checks: InteriorChecks {
+                cardioid: true,
+                periodicity: Some(1e-5),
+            },
+            multi_pass: Some(MultiPass {
+                pass_iterations: 64,
+                time_limit: None,
+            }),
+            ..exterior(ExteriorColoring::Iteration)
+        };
+        let source = generate_shader(&multi_pass);
+        assert!(source.contains("fn iterate_main"));
+        assert!(source.contains("fn finish_main"));
+        assert!(!source.contains("cycle_found"));
+        validate(&multi_pass);
+
+        validate(&Scene {
+            plane: Plane::Julia(Complex::new(-0.8, 0.156)),
+            projection: Projection::Orthographic {
+                yaw: 0.0,
+                pitch: 30.0,
+            },
+            interior: InteriorColoring::DistanceEstimate {
+                cycle: CycleDetection {
+                    epsilon: 0.0001,
+                    max_period: 64,
+                },
+                scale: 1.0,
+            },
+            ..multi_pass.clone()
+        });
+
+        // Colorings' loop variables are kept in the pixel states.
+        let orbit_trap = Scene {
+            exterior: ExteriorColoring::OrbitTrap(OrbitTrap {
+                shape: TrapShape::Circle,
+                coloring: TrapColoring::DistanceAndIteration,
+                position: Complex::new(0.0, 0.0),
+                angle: 0.0,
+                radius: 1.0,
+                scale: 1.0,
+            }),
+            interior: InteriorColoring::AtomDomain,
+            ..multi_pass.clone()
+        };
+        let source = generate_shader(&orbit_trap);
+        assert!(source.contains("    closest_distance: f32;"));
+        assert!(source.contains("        atom_iteration = state.atom_iteration;"));
+        assert!(source.contains("    state.closest_iteration = closest_iteration;"));
+        validate(&orbit_trap);
+
+        for statistic in &[AverageStatistic::Stripe, AverageStatistic::Curvature] {
+            validate(&Scene {
+                exterior: ExteriorColoring::Average(OrbitAverage {
+                    statistic: *statistic,
+                    stripe_density: 5.0,
+                    skip_iterations: 1,
+                }),
+                ..multi_pass.clone()
+            });
+        }
+
+        // Image traps take the group after the uniforms.
+        let image_trap = Scene {
+            exterior: ExteriorColoring::ImageTrap(ImageTrap {
+                path: "trap.png".into(),
+                blend: TrapBlend::FirstHit,
+                position: Complex::new(-1.0, -1.0),
+                size: Complex::new(2.0, 2.0),
+                alpha_threshold: 0.5,
+            }),
+            ..multi_pass
+        };
+        assert!(generate_shader(&image_trap).contains("[[group(2), binding(2)]]"));
+        validate(&image_trap);
+    }
+
+    #[test]
+    fn generate_hybrids() {
+        let hybrid = Formula::hybrid(&[
+            Family::Multibrot { power: 2.0 }.formula(),
+            Family::BurningShip.formula(),
+            Family::BurningShip.formula(),
+        ]);
+        validate(&Scene {
+            formula: hybrid.clone(),
+            plane: Plane::Parameter,
+            ..exterior(ExteriorColoring::Iteration)
+        });
+        validate(&Scene {
+            mode: RenderMode::Buddhabrot(Buddhabrot {
+                iteration_limits: [1000, 100, 10],
+                samples: 1000,
+                sample_start: Complex::new(-2.0, -2.0),
+                sample_size: Complex::new(4.0, 4.0),
+            }),
+            formula: hybrid,
+            plane: Plane::Parameter,
+            ..exterior(ExteriorColoring::Iteration)
+        });
+
+        // Hybrids of holomorphic formulas have derivatives for each step.
+        let cycle = CycleDetection {
+            epsilon: 0.0001,
+            max_period: 64,
+        };
+        validate(&Scene {
+            formula: Formula::hybrid(&[
+                Family::Multibrot { power: 2.0 }.formula(),
+                Family::Multibrot { power: 3.0 }.formula(),
+            ]),
+            interior: InteriorColoring::DistanceEstimate { cycle, scale: 1.0 },
+            ..exterior(ExteriorColoring::Iteration)
+        });
+    }
+
+    #[test]
+    fn generate_plane_transforms() {
+        for &plane in &[Plane::Parameter, Plane::Julia(Complex::new(-0.8, 0.156))] {
+            validate(&Scene {
+                plane,
+                plane_transforms: vec![
+                    PlaneTransform::Inversion,
+                    PlaneTransform::Lambda,
+                    PlaneTransform::Mobius {
+                        a: Complex::new(1.0, 0.0),
+                        b: Complex::new(0.0, 0.5),
+                        c: Complex::new(0.25, 0.0),
+                        d: Complex::new(1.0, 0.0),
+                    },
+                ],
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_projections() {
+        for &projection in &[
+            Projection::Equirectangular {
+                yaw: 30.0,
+                pitch: -45.0,
+            },
+            Projection::Orthographic {
+                yaw: 0.0,
+                pitch: 90.0,
+            },
+        ] {
+            for &plane in &[Plane::Parameter, Plane::Julia(Complex::new(-0.8, 0.156))] {
+                validate(&Scene {
+                    plane,
+                    projection,
+                    ..exterior(ExteriorColoring::Iteration)
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn generate_exponential_map() {
+        validate(&Scene {
+            mode: RenderMode::ExponentialMap(ExponentialMap {
+                center: Complex::new(-0.7435669, 0.1314023),
+                radius: 2.0,
+                zoom: 100000.0,
+                frames: None,
+            }),
+            plane: Plane::Parameter,
+            plane_transforms: vec![PlaneTransform::Inversion],
+            ..exterior(ExteriorColoring::Iteration)
+        });
+    }
+
+    #[test]
+    fn generate_root_finding() {
+        for &method in &[RootMethod::Newton, RootMethod::Halley, RootMethod::Schroder] {
+            for &nova_start in &[None, Some(Complex::new(1.0, 0.0))] {
+                let root_finding = RootFinding::new(
+                    parse_expr("z^3 - 2z + 2").unwrap(),
+                    method,
+                    Complex::new(1.0, 0.0),
+                    nova_start,
+                    1e-4,
+                )
+                .unwrap();
+                validate(&Scene {
+                    mode: RenderMode::RootFinding(root_finding),
+                    ..exterior(ExteriorColoring::Iteration)
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn generate_complex_library() {
+        let root_finding = RootFinding::new(
+            parse_expr(
+                "sin(z) cosh(z) - tan(z) + exp(sqrt(z)) log(z) - recip(z) + sinh(z)^(1 + i)",
+            )
+            .unwrap(),
+            RootMethod::Halley,
+            Complex::new(1.0, 0.0),
+            None,
+            1e-4,
+        )
+        .unwrap();
+        validate(&Scene {
+            mode: RenderMode::RootFinding(root_finding),
+            ..exterior(ExteriorColoring::Iteration)
+        });
+    }
+
+    #[test]
+    fn generate_domain_coloring() {
+        for &grid_spacing in &[None, Some(0.5)] {
+            let domain_coloring = DomainColoring {
+                function: parse_expr("(z^2 - 1)(z - 2 - i)^2 / (z^2 + 2 + 2i)").unwrap(),
+                contour_strength: 0.3,
+                grid_spacing,
+            };
+            validate(&Scene {
+                mode: RenderMode::DomainColoring(domain_coloring),
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_buddhabrot() {
+        for &family in &[Family::Multibrot { power: 2.0 }, Family::BurningShip] {
+            validate(&Scene {
+                mode: RenderMode::Buddhabrot(Buddhabrot {
+                    iteration_limits: [5000, 500, 50],
+                    samples: 1_000_000,
+                    sample_start: Complex::new(-2.0, -2.0),
+                    sample_size: Complex::new(4.0, 4.0),
+                }),
+                formula: family.formula(),
+                plane: Plane::Parameter,
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_attractors() {
+        for &map in &[
+            AttractorMap::Clifford,
+            AttractorMap::DeJong,
+            AttractorMap::Henon,
+        ] {
+            validate(&Scene {
+                mode: RenderMode::Attractor(Attractor {
+                    map,
+                    parameters: map.default_parameters(),
+                    points: 1_000_000,
+                    palette: Palette::Fire,
+                }),
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_lyapunov() {
+        for &(sequence, palette) in &[
+            ("AABAB", Palette::Fire),
+            ("BBBBBBAAAAAA", Palette::Grayscale),
+        ] {
+            validate(&Scene {
+                mode: RenderMode::Lyapunov(Lyapunov {
+                    sequence: parse_sequence(sequence).unwrap(),
+                    iterations: 200,
+                    settle_iterations: 50,
+                    stable_palette: palette,
+                    chaotic_palette: Palette::Ice,
+                }),
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_ray_march() {
+        for &fractal in &[
+            Fractal3D::Mandelbulb { power: 8.0 },
+            Fractal3D::Mandelbox {
+                scale: -1.5,
+                min_radius: 0.5,
+                fixed_radius: 1.0,
+            },
+            Fractal3D::QuaternionJulia {
+                c: [-0.125, -0.256, 0.847, 0.0895],
+            },
+        ] {
+            validate(&Scene {
+                mode: RenderMode::RayMarch(RayMarch {
+                    fractal,
+                    camera: Camera {
+                        position: Vector3::new(0.0, 0.0, -2.5),
+                        yaw: 0.0,
+                        pitch: 0.0,
+                        field_of_view: 60.0,
+                    },
+                    iterations: 12,
+                    max_steps: 256,
+                    shadow_softness: 16.0,
+                    occlusion_strength: 1.0,
+                    fog_density: 0.05,
+                }),
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_folds() {
+        for &fold in &[
+            Fold::Mandelbox {
+                scale: -1.5,
+                min_radius: 0.5,
+                fixed_radius: 1.0,
+            },
+            Fold::Kifs {
+                scale: 2.0,
+                angle: 15.0,
+                offset: Complex::new(1.0, 1.0),
+            },
+            Fold::Apollonian { scale: 1.0 },
+            Fold::Kleinian {
+                trace: Complex::new(1.8462756, 0.09627581),
+            },
+        ] {
+            validate(&Scene {
+                mode: RenderMode::Fold(FoldFractal {
+                    fold,
+                    iterations: 30,
+                }),
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+
+    #[test]
+    fn generate_flames() {
+        let mut every_variation = Flame::sierpinski();
+        every_variation.transforms[0].variations = Variation::ALL
+            .iter()
+            .map(|&variation| (variation, 0.1))
+            .collect();
+        every_variation.transforms[1].post_affine = Some([1.0, 0.5, 0.0, -0.5, 1.0, 0.25]);
+        every_variation.final_transform = Some(Transform {
+            weight: 1.0,
+            color: 0.5,
+            color_speed: 0.0,
+            affine: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            post_affine: None,
+            variations: vec![(Variation::Spherical, 1.0)],
+        });
+
+        for flame in &[Flame::sierpinski(), every_variation] {
+            validate(&Scene {
+                mode: RenderMode::Flame(FractalFlame {
+                    flame: flame.clone(),
+                    points: 1_000_000,
+                }),
+                ..exterior(ExteriorColoring::Iteration)
+            });
+        }
+    }
+}
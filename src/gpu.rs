@@ -0,0 +1,307 @@
+use crate::{
+    buffer::{BufferWrapper, Encodable},
+    generator::{compute_group, TRAP_TEXTURE_GROUP},
+    scene::Scene,
+    trap_texture::TrapTexture,
+    uniforms::Uniforms,
+    util::copy_region,
+};
+use naga::{
+    back, front,
+    valid::{ValidationFlags, Validator},
+};
+use std::{
+    borrow::Cow,
+    mem::size_of,
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::{fs::File, io::AsyncWriteExt, task, task::JoinHandle};
+use wgpu::{
+    BackendBit, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferAddress, BufferBinding, BufferBindingType, BufferDescriptor, BufferUsage, ComputePass,
+    Device, Extent3d, Instance, Maintain, MapMode, Queue, RenderPass, RequestAdapterOptions,
+    ShaderFlags, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStage, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage, TextureView,
+};
+
+/// The device and queue every render uses, polled in the background until
+/// shut down.
+pub struct Gpu {
+    pub device: Arc<Device>,
+    pub queue: Queue,
+    status: Arc<AtomicBool>,
+    poll_task: JoinHandle<()>,
+}
+
+/// The uniforms buffer and the bind group exposing it at group 0.
+pub struct UniformBinding {
+    pub buffer: BufferWrapper<Uniforms>,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+/// The bind groups every pipeline over a scene starts with: the uniforms,
+/// then the image trap's texture at [`TRAP_TEXTURE_GROUP`] if the scene has
+/// one. A compute stage's own buffers follow at [`compute_group`].
+pub struct SceneBinding {
+    uniform_binding: UniformBinding,
+    trap_texture: Option<TrapTexture>,
+    stage_group: u32,
+}
+
+impl Gpu {
+    /// Requests a device and starts polling it.
+    pub async fn new() -> Gpu {
+        info!("Creating Instance...");
+        let instance = Instance::new(BackendBit::PRIMARY);
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: Default::default(),
+                compatible_surface: None,
+            })
+            .await
+            .unwrap();
+
+        info!("Requesting device...");
+        let (device, queue) = adapter
+            .request_device(&Default::default(), None)
+            .await
+            .unwrap();
+
+        info!("Creating device poll task...");
+        let device = Arc::new(device);
+        let poll_device = device.clone();
+        let status = Arc::new(AtomicBool::new(true));
+        let poll_status = status.clone();
+        let poll_task = tokio::spawn(async move {
+            while poll_status.load(Ordering::Relaxed) {
+                poll_device.poll(Maintain::Poll);
+                task::yield_now().await;
+            }
+        });
+
+        Gpu {
+            device,
+            queue,
+            status,
+            poll_task,
+        }
+    }
+
+    /// Stops polling the device.
+    pub async fn shutdown(self) {
+        self.status.store(false, Ordering::Relaxed);
+        self.poll_task.await.unwrap();
+    }
+
+    /// Blocks until all submitted work has finished.
+    pub fn wait(&self) {
+        self.device.poll(Maintain::Wait);
+    }
+
+    /// Parses, validates and creates a shader module from generated WGSL.
+    pub async fn create_shader_module(&self, source: &str) -> ShaderModule {
+        let shader = load_shaders(source).await;
+        self.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("Generated Shader"),
+            source: shader,
+            flags: ShaderFlags::VALIDATION | ShaderFlags::EXPERIMENTAL_TRANSLATION,
+        })
+    }
+
+    /// Uploads the uniforms and binds them for the given shader stages.
+    pub fn bind_uniforms(&self, uniforms: Uniforms, visibility: ShaderStage) -> UniformBinding {
+        let (buffer, uniforms_cb) =
+            BufferWrapper::from_data(&self.device, &[uniforms], BufferUsage::UNIFORM);
+        self.queue.submit([uniforms_cb]);
+
+        let bind_group_layout = self
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Uniforms bind group layout"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(NonZeroU64::new(Uniforms::size() as u64).unwrap()),
+                    },
+                    count: None,
+                }],
+            });
+
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Uniforms bind group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: buffer.buffer(),
+                    offset: 0,
+                    size: None,
+                }),
+            }],
+        });
+
+        UniformBinding {
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Binds the uniforms for the given shader stages, along with the trap
+    /// texture if the scene needs one.
+    pub fn bind_scene(
+        &self,
+        uniforms: Uniforms,
+        scene: &Scene,
+        visibility: ShaderStage,
+    ) -> SceneBinding {
+        SceneBinding {
+            uniform_binding: self.bind_uniforms(uniforms, visibility),
+            trap_texture: TrapTexture::for_scene(self, scene),
+            stage_group: compute_group(scene),
+        }
+    }
+
+    /// Reads back the contents of a buffer created with `MAP_READ` usage.
+    pub async fn read_buffer(&self, buffer: &Buffer) -> Vec<u8> {
+        let data = {
+            let buffer_slice = buffer.slice(..);
+            buffer_slice.map_async(MapMode::Read).await.unwrap();
+            buffer_slice.get_mapped_range().to_vec()
+        };
+        buffer.unmap();
+        data
+    }
+}
+
+impl UniformBinding {
+    /// Replaces the uploaded uniforms.
+    pub fn write(&self, queue: &Queue, uniforms: Uniforms) {
+        queue.write_buffer(self.buffer.buffer(), 0, bytemuck::bytes_of(&uniforms));
+    }
+}
+
+impl SceneBinding {
+    /// The bind group layouts of a pipeline using these bind groups, followed
+    /// by a compute stage's own if it has one.
+    pub fn layouts<'a>(&'a self, stage: Option<&'a BindGroupLayout>) -> Vec<&'a BindGroupLayout> {
+        let mut layouts = vec![&self.uniform_binding.bind_group_layout];
+        if let Some(trap_texture) = &self.trap_texture {
+            debug_assert_eq!(layouts.len(), TRAP_TEXTURE_GROUP as usize);
+            layouts.push(&trap_texture.bind_group_layout);
+        }
+        if let Some(stage) = stage {
+            debug_assert_eq!(layouts.len(), self.stage_group as usize);
+            layouts.push(stage);
+        }
+        layouts
+    }
+
+    /// Sets these bind groups in a render pass.
+    pub fn set_render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_bind_group(0, &self.uniform_binding.bind_group, &[]);
+        if let Some(trap_texture) = &self.trap_texture {
+            render_pass.set_bind_group(TRAP_TEXTURE_GROUP, &trap_texture.bind_group, &[]);
+        }
+    }
+
+    /// Sets these bind groups in a compute pass, followed by the stage's own.
+    pub fn set_compute<'a>(&'a self, compute_pass: &mut ComputePass<'a>, stage: &'a BindGroup) {
+        compute_pass.set_bind_group(0, &self.uniform_binding.bind_group, &[]);
+        if let Some(trap_texture) = &self.trap_texture {
+            compute_pass.set_bind_group(TRAP_TEXTURE_GROUP, &trap_texture.bind_group, &[]);
+        }
+        compute_pass.set_bind_group(self.stage_group, stage, &[]);
+    }
+}
+
+pub fn create_texture(device: &Device, width: u32, height: u32) -> (Texture, TextureView) {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Framebuffer"),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8Unorm,
+        usage: TextureUsage::COPY_SRC | TextureUsage::RENDER_ATTACHMENT,
+    });
+    let texture_view = texture.create_view(&Default::default());
+
+    (texture, texture_view)
+}
+
+pub fn create_texture_buffer(device: &Device, width: u32, height: u32) -> Buffer {
+    let size = width * height * size_of::<u32>() as u32;
+    let texture_buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Framebuffer Buffer"),
+        size: size as BufferAddress,
+        usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    texture_buffer
+}
+
+/// Copies the image out of a framebuffer whose rows are padded to
+/// `texture_width` pixels.
+pub fn crop_framebuffer(
+    data: &[u8],
+    texture_width: u32,
+    image_width: u32,
+    image_height: u32,
+) -> Vec<u8> {
+    let mut image_data = vec![0u8; image_width as usize * image_height as usize * size_of::<u32>()];
+    copy_region(
+        data,
+        texture_width as usize,
+        0,
+        0,
+        &mut image_data,
+        image_width as usize,
+        0,
+        0,
+        image_width as usize,
+        image_height as usize,
+    );
+    image_data
+}
+
+async fn load_shaders(source: &str) -> ShaderSource<'static> {
+    info!("Parsing generated shader...");
+    let module = front::wgsl::parse_str(source).unwrap();
+
+    info!("Validating module...");
+    let mut validator = Validator::new(ValidationFlags::all(), Default::default());
+    let module_info = validator.validate(&module).unwrap();
+
+    info!("Writing module as txt...");
+    let mut file = File::create("debug.txt").await.unwrap();
+    file.write_all(format!("{:#?}", &module).as_bytes())
+        .await
+        .unwrap();
+
+    info!("Compiling WGSL...");
+    let mut wgsl_str = String::new();
+    let mut writer = back::wgsl::Writer::new(&mut wgsl_str);
+    writer.write(&module, &module_info).unwrap();
+    writer.finish();
+
+    info!("Writing WGSL...");
+    let mut wgsl_file = File::create("debug.wgsl").await.unwrap();
+    wgsl_file.write_all(wgsl_str.as_bytes()).await.unwrap();
+
+    ShaderSource::Wgsl(Cow::Owned(wgsl_str))
+}
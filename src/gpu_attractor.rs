@@ -0,0 +1,27 @@
+use crate::scene::{RenderMode, Scene};
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector4;
+
+/// Parameters of a strange attractor's map.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUAttractor {
+    /// The parameters `a`, `b`, `c` and `d`.
+    pub parameters: Vector4<f32>,
+}
+
+impl GPUAttractor {
+    /// Gets the attractor uniforms for a scene, which are zeroed if the scene
+    /// isn't an attractor.
+    pub fn from_scene(scene: &Scene) -> GPUAttractor {
+        match &scene.mode {
+            RenderMode::Attractor(attractor) => GPUAttractor {
+                parameters: attractor.parameters.into(),
+            },
+            _ => GPUAttractor::zeroed(),
+        }
+    }
+}
+
+unsafe impl Zeroable for GPUAttractor {}
+unsafe impl Pod for GPUAttractor {}
@@ -0,0 +1,35 @@
+use crate::scene::{RenderMode, Scene};
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector4;
+
+/// The position and axes of a 3D camera, padded to `vec4`s.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUCamera {
+    pub position: Vector4<f32>,
+    pub right: Vector4<f32>,
+    pub up: Vector4<f32>,
+    pub forward: Vector4<f32>,
+}
+
+impl GPUCamera {
+    /// Gets the camera uniforms for a scene, which are zeroed if the scene
+    /// isn't 3D.
+    pub fn from_scene(scene: &Scene) -> GPUCamera {
+        match &scene.mode {
+            RenderMode::RayMarch(ray_march) => {
+                let basis = ray_march.camera.basis();
+                GPUCamera {
+                    position: ray_march.camera.position.extend(0.0),
+                    right: basis.right.extend(0.0),
+                    up: basis.up.extend(0.0),
+                    forward: basis.forward.extend(0.0),
+                }
+            }
+            _ => GPUCamera::zeroed(),
+        }
+    }
+}
+
+unsafe impl Zeroable for GPUCamera {}
+unsafe impl Pod for GPUCamera {}
@@ -0,0 +1,148 @@
+use crate::coloring::{ExteriorColoring, ImageTrap, InteriorColoring, OrbitAverage, OrbitTrap};
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUOrbitTrap {
+    pub position: Vector2<f32>,
+    /// Unit vector along the trap's angle.
+    pub direction: Vector2<f32>,
+    pub radius: f32,
+    pub scale: f32,
+    _padding: Vector2<f32>,
+}
+
+impl GPUOrbitTrap {
+    pub fn from_trap(trap: OrbitTrap) -> GPUOrbitTrap {
+        GPUOrbitTrap {
+            position: Vector2 {
+                x: trap.position.re,
+                y: trap.position.im,
+            },
+            direction: Vector2 {
+                x: trap.angle.cos(),
+                y: trap.angle.sin(),
+            },
+            radius: trap.radius,
+            scale: trap.scale,
+            _padding: Vector2 { x: 0.0, y: 0.0 },
+        }
+    }
+
+    /// Gets the trap uniforms for an exterior coloring, which are zeroed if
+    /// the coloring doesn't use a trap.
+    pub fn from_coloring(coloring: &ExteriorColoring) -> GPUOrbitTrap {
+        match coloring {
+            ExteriorColoring::OrbitTrap(trap) => GPUOrbitTrap::from_trap(*trap),
+            _ => GPUOrbitTrap::zeroed(),
+        }
+    }
+}
+
+impl From<OrbitTrap> for GPUOrbitTrap {
+    fn from(trap: OrbitTrap) -> Self {
+        GPUOrbitTrap::from_trap(trap)
+    }
+}
+
+unsafe impl Zeroable for GPUOrbitTrap {}
+unsafe impl Pod for GPUOrbitTrap {}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUImageTrap {
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    pub alpha_threshold: f32,
+    _padding: [f32; 3],
+}
+
+impl GPUImageTrap {
+    pub fn from_trap(trap: &ImageTrap) -> GPUImageTrap {
+        GPUImageTrap {
+            position: Vector2 {
+                x: trap.position.re,
+                y: trap.position.im,
+            },
+            size: Vector2 {
+                x: trap.size.re,
+                y: trap.size.im,
+            },
+            alpha_threshold: trap.alpha_threshold,
+            _padding: [0.0; 3],
+        }
+    }
+
+    /// Gets the image trap uniforms for an exterior coloring, which are zeroed
+    /// if the coloring doesn't use an image trap.
+    pub fn from_coloring(coloring: &ExteriorColoring) -> GPUImageTrap {
+        match coloring {
+            ExteriorColoring::ImageTrap(trap) => GPUImageTrap::from_trap(trap),
+            _ => GPUImageTrap::zeroed(),
+        }
+    }
+}
+
+unsafe impl Zeroable for GPUImageTrap {}
+unsafe impl Pod for GPUImageTrap {}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUAverage {
+    pub stripe_density: f32,
+    pub skip_iterations: i32,
+    _padding: Vector2<f32>,
+}
+
+impl GPUAverage {
+    pub fn from_average(average: OrbitAverage) -> GPUAverage {
+        GPUAverage {
+            stripe_density: average.stripe_density,
+            skip_iterations: average.skip_iterations as i32,
+            _padding: Vector2 { x: 0.0, y: 0.0 },
+        }
+    }
+
+    /// Gets the averaging uniforms for an exterior coloring, which are zeroed
+    /// if the coloring doesn't average over the orbit.
+    pub fn from_coloring(coloring: &ExteriorColoring) -> GPUAverage {
+        match coloring {
+            ExteriorColoring::Average(average) => GPUAverage::from_average(*average),
+            _ => GPUAverage::zeroed(),
+        }
+    }
+}
+
+unsafe impl Zeroable for GPUAverage {}
+unsafe impl Pod for GPUAverage {}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUInterior {
+    pub epsilon: f32,
+    pub max_period: i32,
+    pub scale: f32,
+    _padding: f32,
+}
+
+impl GPUInterior {
+    pub fn from_coloring(coloring: &InteriorColoring) -> GPUInterior {
+        let (cycle, scale) = match *coloring {
+            InteriorColoring::Magnitude { scale } => (None, scale),
+            InteriorColoring::Period(cycle) => (Some(cycle), 0.0),
+            InteriorColoring::DistanceEstimate { cycle, scale } => (Some(cycle), scale),
+            _ => (None, 0.0),
+        };
+
+        GPUInterior {
+            epsilon: cycle.map_or(0.0, |cycle| cycle.epsilon),
+            max_period: cycle.map_or(0, |cycle| cycle.max_period as i32),
+            scale,
+            _padding: 0.0,
+        }
+    }
+}
+
+unsafe impl Zeroable for GPUInterior {}
+unsafe impl Pod for GPUInterior {}
@@ -0,0 +1,53 @@
+use crate::{
+    attractor,
+    scene::{RenderMode, Scene},
+};
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector2;
+use num_complex::Complex;
+
+/// Parameters of compute passes that sample random points.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUSampling {
+    pub sample_start: Vector2<f32>,
+    pub sample_size: Vector2<f32>,
+    /// Seeds the random numbers of one batch, so every batch takes different
+    /// samples.
+    pub seed: u32,
+    _padding: [u32; 3],
+}
+
+impl GPUSampling {
+    /// Gets the sampling uniforms for a scene, which are zeroed if the scene
+    /// isn't rendered by sampling.
+    pub fn from_scene(scene: &Scene) -> GPUSampling {
+        match &scene.mode {
+            RenderMode::Buddhabrot(buddhabrot) => {
+                GPUSampling::new(buddhabrot.sample_start, buddhabrot.sample_size)
+            }
+            RenderMode::Attractor(_) => {
+                GPUSampling::new(attractor::START_CORNER, attractor::START_SIZE)
+            }
+            _ => GPUSampling::zeroed(),
+        }
+    }
+
+    fn new(sample_start: Complex<f32>, sample_size: Complex<f32>) -> GPUSampling {
+        GPUSampling {
+            sample_start: Vector2 {
+                x: sample_start.re,
+                y: sample_start.im,
+            },
+            sample_size: Vector2 {
+                x: sample_size.re,
+                y: sample_size.im,
+            },
+            seed: 0,
+            _padding: [0; 3],
+        }
+    }
+}
+
+unsafe impl Zeroable for GPUSampling {}
+unsafe impl Pod for GPUSampling {}
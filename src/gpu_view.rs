@@ -2,11 +2,13 @@ use crate::view::View;
 use bytemuck::{Pod, Zeroable};
 use cgmath::Vector2;
 
+#[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct GPUView {
     pub image_size: Vector2<f32>,
     pub image_scale: Vector2<f32>,
     pub plane_start: Vector2<f32>,
+    _padding: Vector2<f32>,
 }
 
 impl GPUView {
@@ -24,6 +26,7 @@ impl GPUView {
                 x: view.plane_start_x,
                 y: view.plane_start_y,
             },
+            _padding: Vector2 { x: 0.0, y: 0.0 },
         }
     }
 }
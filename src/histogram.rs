@@ -0,0 +1,150 @@
+use crate::{
+    gpu::{Gpu, UniformBinding},
+    uniforms::Uniforms,
+};
+use std::{mem::size_of, num::NonZeroU64};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferAddress, BufferBinding,
+    BufferBindingType, BufferDescriptor, BufferUsage, CommandEncoderDescriptor,
+    ComputePassDescriptor, ComputePipelineDescriptor, PipelineLayoutDescriptor, ShaderModule,
+    ShaderStage,
+};
+
+/// A storage buffer of counts per pixel and channel that compute passes
+/// increment, bound at group 1.
+pub struct Histogram {
+    buffer: Buffer,
+    size: BufferAddress,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl Histogram {
+    /// Creates a zeroed histogram for an image.
+    pub fn new(gpu: &Gpu, width: u32, height: u32, channels: u32) -> Histogram {
+        let size = width as BufferAddress
+            * height as BufferAddress
+            * channels as BufferAddress
+            * size_of::<u32>() as BufferAddress;
+
+        // Buffers mapped at creation start out zeroed.
+        let buffer = gpu.device.create_buffer(&BufferDescriptor {
+            label: Some("Histogram"),
+            size,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+            mapped_at_creation: true,
+        });
+        buffer.unmap();
+
+        let bind_group_layout = gpu
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Histogram bind group layout"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(size_of::<u32>() as u64),
+                    },
+                    count: None,
+                }],
+            });
+
+        let bind_group = gpu.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Histogram bind group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: None,
+                }),
+            }],
+        });
+
+        Histogram {
+            buffer,
+            size,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Runs a compute shader's `comp_main` once per batch, reseeding its
+    /// random numbers before each one and logging progress as batches finish.
+    pub fn accumulate(
+        &self,
+        gpu: &Gpu,
+        module: &ShaderModule,
+        uniforms: Uniforms,
+        batches: u64,
+        workgroups: u32,
+    ) {
+        let uniform_binding: UniformBinding = gpu.bind_uniforms(uniforms, ShaderStage::COMPUTE);
+
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Compute Pipeline Layout"),
+                bind_group_layouts: &[&uniform_binding.bind_group_layout, &self.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = gpu
+            .device
+            .create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some("Compute Pipeline"),
+                layout: Some(&pipeline_layout),
+                module,
+                entry_point: "comp_main",
+            });
+
+        for batch in 0..batches {
+            let mut batch_uniforms = uniforms;
+            batch_uniforms.sampling.seed = batch as u32;
+            uniform_binding.write(&gpu.queue, batch_uniforms);
+
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Compute Encoder"),
+                });
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("Compute Pass"),
+                });
+                compute_pass.set_pipeline(&pipeline);
+                compute_pass.set_bind_group(0, &uniform_binding.bind_group, &[]);
+                compute_pass.set_bind_group(1, &self.bind_group, &[]);
+                compute_pass.dispatch(workgroups, 1, 1);
+            }
+            gpu.queue.submit(Some(encoder.finish()));
+            gpu.wait();
+
+            info!("Finished batch {} of {}", batch + 1, batches);
+        }
+    }
+
+    /// Copies the counts back from the GPU.
+    pub async fn read(&self, gpu: &Gpu) -> Vec<u32> {
+        let read_buffer = gpu.device.create_buffer(&BufferDescriptor {
+            label: Some("Histogram Read Buffer"),
+            size: self.size,
+            usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Histogram Read Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &read_buffer, 0, self.size);
+        gpu.queue.submit(Some(encoder.finish()));
+
+        bytemuck::cast_slice(&gpu.read_buffer(&read_buffer).await).to_vec()
+    }
+}
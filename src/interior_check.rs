@@ -0,0 +1,248 @@
+use crate::{formula::Variables, generator::float_literal, scene::Scene, view::View};
+use num_complex::Complex;
+use std::fmt::Write;
+
+/// Shortcuts that stop iterating points early once they are known to be
+/// interior, rather than running them to the iteration limit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InteriorChecks {
+    /// Skips points inside the Mandelbrot set's main cardioid and period-2
+    /// bulb without iterating them at all.
+    pub cardioid: bool,
+    /// Stops once `z` and `previous_z` return within this distance of the
+    /// values saved at the last power of two iterations, which is Brent's
+    /// cycle detection.
+    pub periodicity: Option<f32>,
+}
+
+/// Side length of the grid of pixels [`InteriorChecks::estimate_speedup`]
+/// samples.
+const SAMPLE_GRID: usize = 64;
+
+impl InteriorChecks {
+    pub const NONE: InteriorChecks = InteriorChecks {
+        cardioid: false,
+        periodicity: None,
+    };
+
+    /// Writes the `in_cardioid_or_bulb` function when the cardioid check is
+    /// used.
+    pub fn write_functions(&self, out: &mut String) {
+        if self.cardioid {
+            out.push_str(
+                r#"
+fn in_cardioid_or_bulb(c: vec2<f32>) -> bool {
+    let x = c.x - 0.25;
+    let q = x * x + c.y * c.y;
+    return q * (q + x) <= 0.25 * c.y * c.y || length_sqr(c + vec2<f32>(1.0, 0.0)) <= 0.0625;
+}
+"#,
+            );
+        }
+    }
+
+    /// Writes the variables the checks keep across loop iterations, after `n`
+    /// has been declared.
+    pub fn write_loop_state(&self, out: &mut String) {
+        if self.cardioid {
+            out.push_str(
+                r#"    if (in_cardioid_or_bulb(c)) {
+        n = iterations;
+    }
+"#,
+            );
+        }
+        if self.periodicity.is_some() {
+            out.push_str(
+                r#"    var cycle_found = false;
+    var check_z = z;
+    var check_previous_z = previous_z;
+    var check_n: i32 = 0;
+    var check_length: i32 = 1;
+"#,
+            );
+        }
+    }
+
+    /// Writes the end of a loop iteration, which leaves the loop once `z` has
+    /// returned to its saved value.
+    ///
+    /// Hybrids only count a return after a whole number of rounds through
+    /// their `steps`, since only then does the same step come next.
+    pub fn write_after_step(&self, out: &mut String, steps: usize) {
+        let epsilon = match self.periodicity {
+            Some(epsilon) => epsilon,
+            None => return,
+        };
+        let same_step = match steps {
+            1 => String::new(),
+            _ => format!(" && (n + 1 - check_n) % {} == 0", steps),
+        };
+        write!(
+            out,
+            r#"        if (length_sqr(z - check_z) < {epsilon_sqr} && length_sqr(previous_z - check_previous_z) < {epsilon_sqr}{same_step}) {{
+            cycle_found = true;
+            n = n + 1;
+            break;
+        }}
+        if (n + 1 - check_n == check_length) {{
+            check_z = z;
+            check_previous_z = previous_z;
+            check_n = n + 1;
+            check_length = check_length * 2;
+        }}
+"#,
+            epsilon_sqr = float_literal(epsilon * epsilon),
+            same_step = same_step,
+        )
+        .unwrap();
+    }
+
+    /// The condition under which the loop ended on an interior point.
+    pub fn interior_condition(&self) -> &'static str {
+        match self.periodicity {
+            Some(_) => "n >= iterations || cycle_found",
+            None => "n >= iterations",
+        }
+    }
+
+    /// Estimates how many times fewer iterations an escape-time render of
+    /// `view` takes with these checks, by iterating a grid of its pixels on
+    /// the CPU both ways.
+    pub fn estimate_speedup(&self, scene: &Scene, view: &View, iterations: u32) -> f32 {
+        let mut without = 0;
+        let mut with = 0;
+        for (z, c) in scene.orbit_starts(view, SAMPLE_GRID) {
+            without += InteriorChecks::NONE.count_iterations(scene, z, c, iterations);
+            with += self.count_iterations(scene, z, c, iterations);
+        }
+        without.max(1) as f32 / with.max(1) as f32
+    }
+
+    /// Counts the iterations the shader's loop runs for a point, matching
+    /// the generated checks.
+    pub fn count_iterations(
+        &self,
+        scene: &Scene,
+        z: Complex<f32>,
+        c: Complex<f32>,
+        iterations: u32,
+    ) -> u32 {
+        if self.cardioid && in_cardioid_or_bulb(c) {
+            return 0;
+        }
+
+        let formula = &scene.formula;
+        let steps = formula.steps.len() as u32;
+        let mut variables = Variables {
+            z,
+            previous_z: Complex::new(0.0, 0.0),
+            c,
+        };
+        let (mut check_z, mut check_previous_z) = (variables.z, variables.previous_z);
+        let (mut check_n, mut check_length) = (0, 1);
+        for n in 0..iterations {
+            if variables.z.norm_sqr() > formula.bailout * formula.bailout {
+                return n;
+            }
+            let z_next = formula.step(n as usize, &variables);
+            variables.previous_z = variables.z;
+            variables.z = z_next;
+
+            if let Some(epsilon) = self.periodicity {
+                let epsilon_sqr = epsilon * epsilon;
+                if (variables.z - check_z).norm_sqr() < epsilon_sqr
+                    && (variables.previous_z - check_previous_z).norm_sqr() < epsilon_sqr
+                    && (n + 1 - check_n) % steps == 0
+                {
+                    return n + 1;
+                }
+                if n + 1 - check_n == check_length {
+                    check_z = variables.z;
+                    check_previous_z = variables.previous_z;
+                    check_n = n + 1;
+                    check_length *= 2;
+                }
+            }
+        }
+        iterations
+    }
+}
+
+/// Whether `c` is inside the main cardioid or period-2 bulb of the
+/// Mandelbrot set.
+fn in_cardioid_or_bulb(c: Complex<f32>) -> bool {
+    let x = c.re - 0.25;
+    let q = x * x + c.im * c.im;
+    q * (q + x) <= 0.25 * c.im * c.im || (c + 1.0).norm_sqr() <= 0.0625
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        interior_check::{in_cardioid_or_bulb, InteriorChecks},
+        scene::Scene,
+        view::View,
+    };
+    use num_complex::Complex;
+
+    fn mandelbrot(interior_checks: InteriorChecks) -> Scene {
+        Scene {
+            interior_checks,
+            ..Scene::test_mandelbrot()
+        }
+    }
+
+    #[test]
+    fn cardioid_and_bulb() {
+        assert!(in_cardioid_or_bulb(Complex::new(0.0, 0.0)));
+        assert!(in_cardioid_or_bulb(Complex::new(0.2, 0.3)));
+        assert!(in_cardioid_or_bulb(Complex::new(-1.1, 0.1)));
+        assert!(!in_cardioid_or_bulb(Complex::new(0.3, 0.0)));
+        assert!(!in_cardioid_or_bulb(Complex::new(-0.12, 0.75)));
+        assert!(!in_cardioid_or_bulb(Complex::new(-1.5, 0.0)));
+    }
+
+    #[test]
+    fn checks_keep_escape_times() {
+        let checks = InteriorChecks {
+            cardioid: true,
+            periodicity: Some(1e-5),
+        };
+        let scene = mandelbrot(checks);
+        let z = Complex::new(0.0, 0.0);
+
+        // Escaping points run the same number of iterations either way.
+        for &c in &[Complex::new(0.3, 0.0), Complex::new(-0.75, 0.1)] {
+            assert_eq!(
+                checks.count_iterations(&scene, z, c, 200),
+                InteriorChecks::NONE.count_iterations(&scene, z, c, 200)
+            );
+        }
+
+        // The cardioid is skipped, and an attracting cycle outside it is
+        // found well before the limit.
+        assert_eq!(
+            checks.count_iterations(&scene, z, Complex::new(-0.1, 0.1), 200),
+            0
+        );
+        let periodic = checks.count_iterations(&scene, z, Complex::new(-0.12, 0.75), 200);
+        assert!(periodic < 200, "{}", periodic);
+    }
+
+    #[test]
+    fn estimated_speedup() {
+        let view = View::new_centered_uniform(100, 100, 3.0);
+        let checks = InteriorChecks {
+            cardioid: true,
+            periodicity: Some(1e-5),
+        };
+        assert!(checks.estimate_speedup(&mandelbrot(checks), &view, 200) > 1.5);
+        assert_eq!(
+            InteriorChecks::NONE.estimate_speedup(&mandelbrot(InteriorChecks::NONE), &view, 200),
+            1.0
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{
+    gpu::{Gpu, SceneBinding},
+    multi_pass::{pixel_state_size, MultiPass, WORKGROUP_SIDE},
+    scene::Scene,
+    uniforms::Uniforms,
+    view::View,
+};
+use std::{
+    mem::size_of,
+    num::NonZeroU64,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferAddress, BufferBinding,
+    BufferBindingType, BufferDescriptor, BufferUsage, CommandEncoderDescriptor,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, PipelineLayoutDescriptor,
+    ShaderModule, ShaderStage,
+};
+
+/// Runs the `iterate_main` compute stage over a view until every pixel has
+/// finished, then colors them with `finish_main`. The pixel states, final
+/// colors and count of finished pixels are bound after the uniforms and the
+/// image trap's texture, if there is one.
+pub struct IterationPasses {
+    scene_binding: SceneBinding,
+    /// Only the shader reads the states, so this just keeps them alive.
+    _states_buffer: Buffer,
+    progress_buffer: Buffer,
+    progress_read_buffer: Buffer,
+    colors_buffer: Buffer,
+    colors_read_buffer: Buffer,
+    bind_group: BindGroup,
+    iterate_pipeline: ComputePipeline,
+    finish_pipeline: ComputePipeline,
+    colors_size: BufferAddress,
+    width: u32,
+    height: u32,
+}
+
+impl IterationPasses {
+    pub fn new(
+        gpu: &Gpu,
+        module: &ShaderModule,
+        uniforms: Uniforms,
+        scene: &Scene,
+        view: View,
+    ) -> IterationPasses {
+        let device = &gpu.device;
+        let scene_binding = gpu.bind_scene(uniforms, scene, ShaderStage::COMPUTE);
+
+        let pixels = (view.image_width * view.image_height) as BufferAddress;
+        let state_size = pixel_state_size(scene);
+        let colors_size = pixels * size_of::<u32>() as BufferAddress;
+        let progress_size = size_of::<u32>() as BufferAddress;
+
+        // Mapping at creation zeroes the states, marking every pixel
+        // unstarted, and the count of finished pixels.
+        let states_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Pixel States"),
+            size: pixels * state_size,
+            usage: BufferUsage::STORAGE,
+            mapped_at_creation: true,
+        });
+        states_buffer.unmap();
+        let progress_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Finished Pixels"),
+            size: progress_size,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+            mapped_at_creation: true,
+        });
+        progress_buffer.unmap();
+        let progress_read_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Finished Pixels Read Buffer"),
+            size: progress_size,
+            usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let colors_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Final Colors"),
+            size: colors_size,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let colors_read_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Final Colors Read Buffer"),
+            size: colors_size,
+            usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let storage_entry = |binding, min_size| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStage::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(min_size),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Iteration passes bind group layout"),
+            entries: &[
+                storage_entry(0, state_size),
+                storage_entry(1, size_of::<u32>() as u64),
+                storage_entry(2, progress_size),
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Iteration passes bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &states_buffer,
+                        offset: 0,
+                        size: None,
+                    }),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &colors_buffer,
+                        offset: 0,
+                        size: None,
+                    }),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: &progress_buffer,
+                        offset: 0,
+                        size: None,
+                    }),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Iteration Passes Pipeline Layout"),
+            bind_group_layouts: &scene_binding.layouts(Some(&bind_group_layout)),
+            push_constant_ranges: &[],
+        });
+        let iterate_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Iterate Pipeline"),
+            layout: Some(&pipeline_layout),
+            module,
+            entry_point: "iterate_main",
+        });
+        let finish_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Finish Pipeline"),
+            layout: Some(&pipeline_layout),
+            module,
+            entry_point: "finish_main",
+        });
+
+        IterationPasses {
+            scene_binding,
+            _states_buffer: states_buffer,
+            progress_buffer,
+            progress_read_buffer,
+            colors_buffer,
+            colors_read_buffer,
+            bind_group,
+            iterate_pipeline,
+            finish_pipeline,
+            colors_size,
+            width: view.image_width as u32,
+            height: view.image_height as u32,
+        }
+    }
+
+    /// Iterates pass by pass, reporting how many pixels have finished after
+    /// each, and stops early once they all have, the time limit has passed
+    /// or `cancel` is set. Returns the view's pixels as RGBA8.
+    ///
+    /// Only the count of finished pixels is read back between passes.
+    pub async fn render(
+        &self,
+        gpu: &Gpu,
+        multi_pass: &MultiPass,
+        iterations: u32,
+        cancel: &AtomicBool,
+    ) -> Vec<u8> {
+        let start = Instant::now();
+        let pixels = (self.width * self.height) as usize;
+        let pass_count = multi_pass.pass_count(iterations);
+        for pass in 1..=pass_count {
+            self.dispatch(
+                gpu,
+                &self.iterate_pipeline,
+                &self.progress_buffer,
+                &self.progress_read_buffer,
+                size_of::<u32>() as BufferAddress,
+            );
+
+            let progress = gpu.read_buffer(&self.progress_read_buffer).await;
+            let finished = bytemuck::cast_slice::<u8, u32>(&progress)[0] as usize;
+            info!(
+                "Pass {} of {}: {} of {} pixels finished ({:.1}%)",
+                pass,
+                pass_count,
+                finished,
+                pixels,
+                finished as f32 * 100.0 / pixels as f32
+            );
+            if finished == pixels || pass == pass_count {
+                break;
+            }
+            if cancel.load(Ordering::Relaxed) {
+                warn!(
+                    "Render cancelled with {} pixels unfinished",
+                    pixels - finished
+                );
+                break;
+            }
+            if let Some(time_limit) = multi_pass.time_limit {
+                if start.elapsed() >= time_limit {
+                    warn!(
+                        "Time limit of {:.2?} reached, cancelling with {} pixels unfinished",
+                        time_limit,
+                        pixels - finished
+                    );
+                    break;
+                }
+            }
+        }
+
+        info!("Coloring pixels...");
+        self.dispatch(
+            gpu,
+            &self.finish_pipeline,
+            &self.colors_buffer,
+            &self.colors_read_buffer,
+            self.colors_size,
+        );
+        gpu.read_buffer(&self.colors_read_buffer).await
+    }
+
+    /// Runs a compute stage over every pixel, then copies one of the buffers
+    /// it writes to where it can be read back.
+    fn dispatch(
+        &self,
+        gpu: &Gpu,
+        pipeline: &ComputePipeline,
+        source: &Buffer,
+        read_buffer: &Buffer,
+        size: BufferAddress,
+    ) {
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Iteration Passes Encoder"),
+            });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Iteration Pass"),
+            });
+            compute_pass.set_pipeline(pipeline);
+            self.scene_binding
+                .set_compute(&mut compute_pass, &self.bind_group);
+            compute_pass.dispatch(
+                (self.width + WORKGROUP_SIDE - 1) / WORKGROUP_SIDE,
+                (self.height + WORKGROUP_SIDE - 1) / WORKGROUP_SIDE,
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(source, 0, read_buffer, 0, size);
+        gpu.queue.submit(Some(encoder.finish()));
+    }
+}
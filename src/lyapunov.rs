@@ -0,0 +1,154 @@
+use crate::{
+    generator::{float_literal, write_frag_main_start},
+    palette::Palette,
+    scene::UnknownOption,
+    util::smallest_multiple_containing,
+    view::View,
+};
+use std::{fmt::Write, str::FromStr};
+
+/// Which of the pixel's coordinates the logistic map's rate is taken from.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Rate {
+    /// The real coordinate.
+    A,
+    /// The imaginary coordinate.
+    B,
+}
+
+/// Plots the Lyapunov exponent of the logistic map `x' = r x (1 - x)` as its
+/// rate `r` is switched between `a` and `b` by a repeating sequence, with
+/// `(a, b)` taken from the pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lyapunov {
+    /// The repeating sequence of rates, compiled into the shader.
+    pub sequence: Vec<Rate>,
+    /// The number of iterations the exponent is averaged over.
+    pub iterations: u32,
+    /// The number of iterations taken before averaging, so the exponent
+    /// isn't skewed by `x` settling down.
+    pub settle_iterations: u32,
+    /// Colors negative exponents, where `x` is stable.
+    pub stable_palette: Palette,
+    /// Colors positive exponents, where `x` is chaotic.
+    pub chaotic_palette: Palette,
+}
+
+impl Lyapunov {
+    /// The view of `[2, 4]` by `[2, 4]`, where the logistic map's interesting
+    /// rates are.
+    pub fn view(image_width: usize, image_height: usize) -> View {
+        View::new_uniform(image_width, image_height, 2.0, 3.0, 3.0)
+    }
+
+    /// Writes the palettes and the fragment stage.
+    pub fn write_shader(&self, out: &mut String) {
+        self.stable_palette.write_wgsl(out, "stable_palette");
+        self.chaotic_palette.write_wgsl(out, "chaotic_palette");
+
+        write_frag_main_start(out);
+        out.push_str(
+            r#"    let a = pixel.x;
+    let b = pixel.y;
+    var x = 0.5;
+    var sum = 0.0;
+"#,
+        );
+
+        // Iterations are rounded up to whole repeats of the sequence, which
+        // is unrolled inside each loop.
+        let length = self.sequence.len() as u32;
+        let settle_repeats = smallest_multiple_containing(self.settle_iterations, length) / length;
+        let repeats = (smallest_multiple_containing(self.iterations, length) / length).max(1);
+
+        writeln!(
+            out,
+            "\n    for (var n: u32 = 0u; n < {}u; n = n + 1u) {{",
+            settle_repeats
+        )
+        .unwrap();
+        for &rate in &self.sequence {
+            writeln!(out, "        x = {} * x * (1.0 - x);", rate_name(rate)).unwrap();
+        }
+        out.push_str("    }\n");
+
+        writeln!(
+            out,
+            "\n    for (var n: u32 = 0u; n < {}u; n = n + 1u) {{",
+            repeats
+        )
+        .unwrap();
+        for &rate in &self.sequence {
+            let r = rate_name(rate);
+            writeln!(
+                out,
+                "        sum = sum + log(abs({r} * (1.0 - 2.0 * x)));\n        x = {r} * x * (1.0 - x);",
+                r = r
+            )
+            .unwrap();
+        }
+        write!(
+            out,
+            r#"    }}
+
+    let exponent = sum / {};
+    if (exponent < 0.0) {{
+        return stable_palette(1.0 - exp(exponent));
+    }}
+    // The logistic map's exponent is at most ln 2.
+    return chaotic_palette(exponent / 0.6931472);
+}}
+"#,
+            float_literal((repeats * length) as f32)
+        )
+        .unwrap();
+    }
+}
+
+fn rate_name(rate: Rate) -> &'static str {
+    match rate {
+        Rate::A => "a",
+        Rate::B => "b",
+    }
+}
+
+/// Parses a sequence of rates like `AABAB`, ignoring case.
+pub fn parse_sequence(s: &str) -> Result<Vec<Rate>, UnknownOption> {
+    if s.is_empty() {
+        return Err(UnknownOption);
+    }
+    s.chars().map(|c| c.to_string().parse()).collect()
+}
+
+impl FromStr for Rate {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "A" | "a" => Ok(Rate::A),
+            "B" | "b" => Ok(Rate::B),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::lyapunov::{parse_sequence, Rate};
+
+    #[test]
+    fn parse_rate_sequence() {
+        assert_eq!(
+            parse_sequence("AaBAb"),
+            Ok(vec![Rate::A, Rate::A, Rate::B, Rate::A, Rate::B])
+        );
+    }
+
+    #[test]
+    fn parse_invalid_sequence() {
+        assert!(parse_sequence("").is_err());
+        assert!(parse_sequence("ABC").is_err());
+    }
+}
@@ -2,49 +2,84 @@
 extern crate log;
 
 use crate::{
-    buffer::{BufferWrapper, Encodable},
-    gpu_view::GPUView,
+    border_evaluator::BorderEvaluator,
+    exponential_map::{assemble_frame, ExponentialMap, ZoomFrames},
+    formula::Symmetry,
+    generator::generate_shader,
+    gpu::{create_texture, create_texture_buffer, crop_framebuffer, Gpu},
+    histogram::Histogram,
+    interior_check::InteriorChecks,
+    iteration_passes::IterationPasses,
+    lyapunov::Lyapunov,
+    refinement::Refinement,
+    scene::{RenderMode, Scene},
+    summary::RenderSummary,
     uniforms::Uniforms,
     util::copy_region,
     view::View,
 };
 use core::num::NonZeroU32;
 use image::{ImageBuffer, Rgba};
-use naga::{
-    back, front,
-    valid::{ValidationFlags, Validator},
-};
 use std::{
-    borrow::Cow,
     convert::TryFrom,
     mem::size_of,
-    num::NonZeroU64,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
 };
-use tokio::{fs::File, io::AsyncWriteExt, task};
+use tokio::signal;
 use wgpu::{
-    BackendBit, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferAddress,
-    BufferBinding, BufferBindingType, BufferDescriptor, BufferUsage, Color, ColorTargetState,
-    ColorWrite, CommandEncoderDescriptor, Device, Extent3d, Face, FragmentState, FrontFace,
-    ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Instance, LoadOp, Maintain, MapMode,
+    BlendState, Color, ColorTargetState, ColorWrite, CommandEncoderDescriptor, Extent3d, Face,
+    FragmentState, FrontFace, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, LoadOp,
     MultisampleState, Operations, Origin3d, PipelineLayoutDescriptor, PolygonMode, PrimitiveState,
     PrimitiveTopology, RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
-    RequestAdapterOptions, ShaderFlags, ShaderModuleDescriptor, ShaderSource, ShaderStage, Texture,
-    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage, TextureView, VertexState,
+    ShaderModule, ShaderStage, TextureFormat, VertexState,
 };
 
+mod attractor;
+mod auto_iterations;
+mod border_evaluator;
+mod buddhabrot;
 mod buffer;
+mod camera;
+mod coloring;
+mod complex;
+mod density;
+mod derivative;
+mod domain_coloring;
+mod exponential_map;
+mod flame;
+mod flame_file;
+mod fold;
+mod formula;
+mod generator;
+mod gpu;
+mod gpu_attractor;
+mod gpu_camera;
+mod gpu_coloring;
+mod gpu_sampling;
 mod gpu_view;
+mod histogram;
+mod interior_check;
+mod iteration_passes;
+mod lyapunov;
+mod mariani_silver;
+mod multi_pass;
+mod palette;
+mod parser;
+mod plane_transform;
+mod projection;
+mod ray_march;
+mod refinement;
+mod root_finding;
+mod scene;
+mod summary;
+mod trap_texture;
 mod uniforms;
 mod util;
 mod view;
 
-const TEMPLATE_SOURCE: &str = include_str!("template.wgsl");
-
 const IMAGE_WIDTH: u32 = 907;
 const IMAGE_HEIGHT: u32 = 907;
 
@@ -59,88 +94,178 @@ async fn main() {
     dotenv::dotenv().ok();
     env_logger::init();
 
+    info!("Reading scene...");
+    let mut scene = Scene::from_env().unwrap();
+
     info!("Creating View...");
-    let view = View::new_centered_uniform(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize, 3.0);
-
-    info!("Creating Instance...");
-    let instance = Instance::new(BackendBit::PRIMARY);
-    let adapter = instance
-        .request_adapter(&RequestAdapterOptions {
-            power_preference: Default::default(),
-            compatible_surface: None,
-        })
-        .await
-        .unwrap();
-
-    info!("Requesting device...");
-    let (device, queue) = adapter
-        .request_device(&Default::default(), None)
-        .await
-        .unwrap();
-
-    info!("Creating device poll task...");
-    let device = Arc::new(device);
-    let poll_device = device.clone();
-    let status = Arc::new(AtomicBool::new(true));
-    let poll_status = status.clone();
-    let poll_task = tokio::spawn(async move {
-        while poll_status.load(Ordering::Relaxed) {
-            poll_device.poll(Maintain::Poll);
-            task::yield_now().await;
-        }
-    });
+    let view = match &scene.mode {
+        RenderMode::EscapeTime => scene
+            .projection
+            .view(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize),
+        RenderMode::Lyapunov(_) => Lyapunov::view(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize),
+        RenderMode::Fold(fold) => fold.view(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize),
+        RenderMode::ExponentialMap(map) => map.view(IMAGE_WIDTH as usize),
+        RenderMode::Flame(flame) => flame
+            .flame
+            .view(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize),
+        RenderMode::RayMarch(ray_march) => ray_march
+            .camera
+            .view(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize),
+        _ => View::new_centered_uniform(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize, 3.0),
+    };
 
-    info!("Creating framebuffer...");
-    let (texture, texture_view) = create_texture(&device, TEXTURE_WIDTH, TEXTURE_HEIGHT);
-    let buffer = create_texture_buffer(&device, TEXTURE_WIDTH, TEXTURE_HEIGHT);
+    if let Some(auto_iterations) = scene.auto_iterations {
+        info!("Choosing iteration limit...");
+        scene.iterations = auto_iterations.choose(&scene, &view);
+    }
+
+    let gpu = Gpu::new().await;
 
     info!("Creating shader module...");
-    let shader = load_shaders().await;
-    let module = device.create_shader_module(&ShaderModuleDescriptor {
-        label: Some("Vertex Shader"),
-        source: shader,
-        flags: ShaderFlags::VALIDATION | ShaderFlags::EXPERIMENTAL_TRANSLATION,
-    });
+    let module = gpu.create_shader_module(&generate_shader(&scene)).await;
 
     info!("Creating uniforms...");
-    let uniforms = Uniforms {
-        view: GPUView::from_view(view),
+    let uniforms = Uniforms::new(view, &scene);
+
+    let mut summary = RenderSummary::start();
+    if let Some(auto_iterations) = scene.auto_iterations {
+        summary.add(
+            "Iteration limit",
+            format!("{} (chosen by {:?})", scene.iterations, auto_iterations),
+        );
+    }
+    if scene.mode == RenderMode::EscapeTime {
+        let interior_checks = scene.applicable_interior_checks();
+        if interior_checks != InteriorChecks::NONE {
+            summary.add(
+                "Interior check speedup",
+                format!(
+                    "{:.2}x fewer iterations (estimated)",
+                    interior_checks.estimate_speedup(&scene, &view, scene.iterations)
+                ),
+            );
+        }
+    }
+
+    // Ctrl-C stops iterating passes early, coloring what they reached.
+    let cancel = Arc::new(AtomicBool::new(false));
+    if scene.multi_pass.is_some() {
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            if signal::ctrl_c().await.is_ok() {
+                warn!("Cancelling iteration passes...");
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+    }
+
+    let mut image_data = match &scene.mode {
+        RenderMode::Buddhabrot(buddhabrot) => {
+            let counts = accumulate_density(
+                &gpu,
+                &module,
+                uniforms,
+                3,
+                buddhabrot.batches(),
+                buddhabrot::WORKGROUPS_PER_BATCH,
+            )
+            .await;
+            buddhabrot::tone_map(&counts)
+        }
+        RenderMode::Attractor(attractor) => {
+            let counts = accumulate_density(
+                &gpu,
+                &module,
+                uniforms,
+                1,
+                attractor.batches(),
+                attractor::WORKGROUPS_PER_BATCH,
+            )
+            .await;
+            attractor::tone_map(&counts, attractor.palette)
+        }
+        RenderMode::Flame(flame) => {
+            let counts = accumulate_density(
+                &gpu,
+                &module,
+                uniforms,
+                flame::CHANNELS,
+                flame.batches(),
+                flame::WORKGROUPS_PER_BATCH,
+            )
+            .await;
+            flame::tone_map(&counts, &flame.flame)
+        }
+        RenderMode::ExponentialMap(_) => render_strip(&gpu, &module, uniforms, &scene, view).await,
+        RenderMode::EscapeTime => {
+            render_symmetric(&gpu, &module, uniforms, &scene, view, &cancel).await
+        }
+        _ => render_fragment(&gpu, &module, uniforms, &scene).await,
     };
-    let (uniforms_buffer, uniforms_cb) =
-        BufferWrapper::from_data(&device, &[uniforms], BufferUsage::UNIFORM);
-    queue.submit([uniforms_cb]);
-
-    let uniform_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-        label: Some("Uniforms bind group layout"),
-        entries: &[BindGroupLayoutEntry {
-            binding: 0,
-            visibility: ShaderStage::VERTEX_FRAGMENT,
-            ty: BindingType::Buffer {
-                ty: BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: Some(NonZeroU64::new(Uniforms::size() as u64).unwrap()),
-            },
-            count: None,
-        }],
-    });
 
-    let uniform_bind_group = device.create_bind_group(&BindGroupDescriptor {
-        label: Some("Uniforms bind group"),
-        layout: &uniform_bind_group_layout,
-        entries: &[BindGroupEntry {
-            binding: 0,
-            resource: BindingResource::Buffer(BufferBinding {
-                buffer: uniforms_buffer.buffer(),
-                offset: 0,
-                size: None,
-            }),
-        }],
-    });
+    if let (RenderMode::EscapeTime, Some(refinement)) = (&scene.mode, &scene.refinement) {
+        let refined = refine_tiles(
+            &gpu,
+            uniforms,
+            &scene,
+            view,
+            refinement,
+            &mut image_data,
+            &cancel,
+        )
+        .await;
+        summary.add(
+            "Refined tiles",
+            format!(
+                "{} of {}",
+                refined,
+                view.subdivide_rectangles(refinement.tile_size, refinement.tile_size)
+                    .len()
+            ),
+        );
+    }
+
+    summary.log();
+
+    info!("Writing image...");
+    let image =
+        ImageBuffer::<Rgba<u8>, _>::from_raw(IMAGE_WIDTH, view.image_height as u32, image_data)
+            .unwrap();
+    image.save("output.png").unwrap();
+
+    if let RenderMode::ExponentialMap(ExponentialMap {
+        frames: Some(frames),
+        ..
+    }) = &scene.mode
+    {
+        write_zoom_frames(frames, image.as_raw(), view.image_height);
+    }
+
+    info!("Shutting down...");
+    gpu.shutdown().await;
+
+    info!("Done.");
+}
+
+/// Renders the image by running the fragment stage once per pixel.
+async fn render_fragment(
+    gpu: &Gpu,
+    module: &ShaderModule,
+    uniforms: Uniforms,
+    scene: &Scene,
+) -> Vec<u8> {
+    let device = &gpu.device;
+
+    info!("Creating framebuffer...");
+    let (texture, texture_view) = create_texture(device, TEXTURE_WIDTH, TEXTURE_HEIGHT);
+    let buffer = create_texture_buffer(device, TEXTURE_WIDTH, TEXTURE_HEIGHT);
+
+    let scene_binding = gpu.bind_scene(uniforms, scene, ShaderStage::VERTEX_FRAGMENT);
 
     info!("Creating render pipeline...");
     let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
         label: Some("Render Pipeline Layout"),
-        bind_group_layouts: &[&uniform_bind_group_layout],
+        bind_group_layouts: &scene_binding.layouts(None),
         push_constant_ranges: &[],
     });
 
@@ -148,12 +273,12 @@ async fn main() {
         label: Some("Render Pipeline"),
         layout: Some(&render_pipeline_layout),
         vertex: VertexState {
-            module: &module,
+            module,
             entry_point: "vert_main",
             buffers: &[],
         },
         fragment: Some(FragmentState {
-            module: &module,
+            module,
             entry_point: "frag_main",
             targets: &[ColorTargetState {
                 format: TextureFormat::Rgba8Unorm,
@@ -203,7 +328,7 @@ async fn main() {
         });
 
         render_pass.set_pipeline(&render_pipeline);
-        render_pass.set_bind_group(0, &uniform_bind_group, &[]);
+        scene_binding.set_render(&mut render_pass);
         render_pass.draw(0..6, 0..1);
     }
 
@@ -231,100 +356,300 @@ async fn main() {
     );
 
     info!("Submitting command buffer...");
-    queue.submit(Some(encoder.finish()));
+    gpu.queue.submit(Some(encoder.finish()));
 
-    {
-        info!("Reading framebuffer...");
-        let buffer_slice = buffer.slice(..);
-        buffer_slice.map_async(MapMode::Read).await.unwrap();
+    info!("Reading framebuffer...");
+    let data = gpu.read_buffer(&buffer).await;
 
-        let data = buffer_slice.get_mapped_range();
+    info!("Copying image...");
+    crop_framebuffer(&data, TEXTURE_WIDTH, IMAGE_WIDTH, IMAGE_HEIGHT)
+}
 
-        info!("Copying image...");
-        let mut image_data =
-            vec![0u8; IMAGE_WIDTH as usize * IMAGE_HEIGHT as usize * size_of::<u32>()];
-        copy_region(
-            data.as_ref(),
-            TEXTURE_WIDTH as usize,
-            0,
-            0,
-            &mut image_data,
-            IMAGE_WIDTH as usize,
-            0,
-            0,
-            IMAGE_WIDTH as usize,
-            IMAGE_HEIGHT as usize,
+/// Renders an exponential map's strip, which is usually far taller than a
+/// texture can be, in tiles the size of the image.
+async fn render_strip(
+    gpu: &Gpu,
+    module: &ShaderModule,
+    uniforms: Uniforms,
+    scene: &Scene,
+    strip: View,
+) -> Vec<u8> {
+    let tiles = strip.subdivide_rectangles(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize);
+    let tile_count = tiles.len();
+    let mut data = Vec::with_capacity(strip.image_width * strip.image_height * size_of::<u32>());
+    for (index, tile) in tiles.enumerate() {
+        info!("Rendering tile {} of {}...", index + 1, tile_count);
+        let tile_uniforms = Uniforms {
+            view: tile.into(),
+            ..uniforms
+        };
+        let tile_data = render_fragment(gpu, module, tile_uniforms, scene).await;
+        data.extend_from_slice(
+            &tile_data[..tile.image_width * tile.image_height * size_of::<u32>()],
         );
-
-        info!("Writing image...");
-        let image =
-            ImageBuffer::<Rgba<u8>, _>::from_raw(IMAGE_WIDTH, IMAGE_HEIGHT, image_data).unwrap();
-        image.save("output.png").unwrap();
     }
-    buffer.unmap();
+    data
+}
 
-    info!("Shutting down...");
+/// Renders an escape-time view no larger than the framebuffer, returning
+/// just its pixels, by iterating in passes or subdividing rectangles when the
+/// scene asks for them and otherwise running the fragment stage once per
+/// pixel. Setting `cancel` stops iterating passes early.
+async fn render_tile(
+    gpu: &Gpu,
+    module: &ShaderModule,
+    uniforms: Uniforms,
+    scene: &Scene,
+    tile: View,
+    cancel: &AtomicBool,
+) -> Vec<u8> {
+    let tile_uniforms = Uniforms {
+        view: tile.into(),
+        ..uniforms
+    };
+    if let Some(multi_pass) = &scene.multi_pass {
+        info!("Creating iteration passes...");
+        let passes = IterationPasses::new(gpu, module, tile_uniforms, scene, tile);
+        return passes
+            .render(gpu, multi_pass, scene.iterations, cancel)
+            .await;
+    }
 
-    status.store(false, Ordering::Relaxed);
-    poll_task.await.unwrap();
+    let mariani_silver = match scene.mariani_silver {
+        Some(mariani_silver) => mariani_silver,
+        None => {
+            let data = render_fragment(gpu, module, tile_uniforms, scene).await;
+            let mut tile_data = vec![0u8; tile.image_width * tile.image_height * size_of::<u32>()];
+            copy_region(
+                &data,
+                IMAGE_WIDTH as usize,
+                0,
+                0,
+                &mut tile_data,
+                tile.image_width,
+                0,
+                0,
+                tile.image_width,
+                tile.image_height,
+            );
+            return tile_data;
+        }
+    };
 
-    info!("Done.");
+    info!("Creating border evaluator...");
+    let evaluator = BorderEvaluator::new(gpu, module, tile_uniforms, scene);
+    let mut subdivision = mariani_silver.subdivide(tile);
+    let mut level = 0;
+    while let Some(positions) = subdivision.positions() {
+        level += 1;
+        info!(
+            "Evaluating {} pixels at level {}...",
+            positions.len(),
+            level
+        );
+        let colors = evaluator.evaluate(gpu, positions).await;
+        subdivision.advance(&colors);
+    }
+    info!(
+        "Evaluated {} of {} pixels",
+        subdivision.evaluations(),
+        tile.image_width * tile.image_height
+    );
+    bytemuck::cast_slice(&subdivision.into_colors()).to_vec()
 }
 
-fn create_texture(device: &Device, width: u32, height: u32) -> (Texture, TextureView) {
-    let texture = device.create_texture(&TextureDescriptor {
-        label: Some("Framebuffer"),
-        size: Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        },
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: TextureDimension::D2,
-        format: TextureFormat::Rgba8Unorm,
-        usage: TextureUsage::COPY_SRC | TextureUsage::RENDER_ATTACHMENT,
-    });
-    let texture_view = texture.create_view(&Default::default());
-
-    (texture, texture_view)
+/// Renders an escape-time view, skipping the pixels the scene's symmetries
+/// can fill in.
+async fn render_symmetric(
+    gpu: &Gpu,
+    module: &ShaderModule,
+    uniforms: Uniforms,
+    scene: &Scene,
+    view: View,
+    cancel: &AtomicBool,
+) -> Vec<u8> {
+    let symmetries = scene.symmetries();
+    let half_turn = symmetries
+        .iter()
+        .any(|symmetry| matches!(symmetry, Symmetry::Rotational(order) if order % 2 == 0));
+    let reflection = view.point_reflection().filter(|_| half_turn);
+    let mirror = view
+        .real_axis_mirror()
+        .filter(|_| symmetries.contains(&Symmetry::RealAxis));
+    if let Some(reflection) = reflection {
+        info!(
+            "Reflecting {} of {} pixels through 0...",
+            reflection.reflected.image_width * reflection.reflected.image_height,
+            view.image_width * view.image_height
+        );
+        let mut data = render_parts(
+            gpu,
+            module,
+            uniforms,
+            scene,
+            view,
+            &reflection.rendered,
+            cancel,
+        )
+        .await;
+        reflection.reflect_pixels(&mut data, &view);
+        data
+    } else if let Some(mirror) = mirror {
+        info!(
+            "Mirroring {} of {} rows about the real axis...",
+            view.image_height - mirror.rendered.image_height,
+            view.image_height
+        );
+        let mut data = render_parts(
+            gpu,
+            module,
+            uniforms,
+            scene,
+            view,
+            &[mirror.rendered],
+            cancel,
+        )
+        .await;
+        mirror.mirror_rows(&mut data, &view);
+        data
+    } else {
+        render_tile(gpu, module, uniforms, scene, view, cancel).await
+    }
 }
 
-fn create_texture_buffer(device: &Device, width: u32, height: u32) -> Buffer {
-    let size = width * height * size_of::<u32>() as u32;
-    let texture_buffer = device.create_buffer(&BufferDescriptor {
-        label: Some("Framebuffer Buffer"),
-        size: size as BufferAddress,
-        usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
-        mapped_at_creation: false,
-    });
-
-    texture_buffer
+/// Renders parts of a view in tiles into an image of the whole view, leaving
+/// the rest for symmetry to fill in.
+async fn render_parts(
+    gpu: &Gpu,
+    module: &ShaderModule,
+    uniforms: Uniforms,
+    scene: &Scene,
+    view: View,
+    parts: &[View],
+    cancel: &AtomicBool,
+) -> Vec<u8> {
+    let tiles: Vec<View> = parts
+        .iter()
+        .flat_map(|part| part.subdivide_rectangles(IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize))
+        .collect();
+    let tile_count = tiles.len();
+    let mut data = vec![0u8; view.image_width * view.image_height * size_of::<u32>()];
+    for (index, tile) in tiles.into_iter().enumerate() {
+        info!("Rendering tile {} of {}...", index + 1, tile_count);
+        let tile_data = render_tile(gpu, module, uniforms, scene, tile, cancel).await;
+        copy_region(
+            &tile_data,
+            tile.image_width,
+            0,
+            0,
+            &mut data,
+            view.image_width,
+            tile.image_x - view.image_x,
+            tile.image_y - view.image_y,
+            tile.image_width,
+            tile.image_height,
+        );
+    }
+    data
 }
 
-async fn load_shaders() -> ShaderSource<'static> {
-    info!("Loading utility functions...");
-    let module = front::wgsl::parse_str(TEMPLATE_SOURCE).unwrap();
-
-    info!("Validating module...");
-    let mut validator = Validator::new(ValidationFlags::all(), Default::default());
-    let module_info = validator.validate(&module).unwrap();
-
-    info!("Writing module as txt...");
-    let mut file = File::create("debug.txt").await.unwrap();
-    file.write_all(format!("{:#?}", &module).as_bytes())
-        .await
-        .unwrap();
-
-    info!("Compiling WGSL...");
-    let mut wgsl_str = String::new();
-    let mut writer = back::wgsl::Writer::new(&mut wgsl_str);
-    writer.write(&module, &module_info).unwrap();
-    writer.finish();
+/// Renders the tiles a refinement plan picks again at their higher limits,
+/// one shader per limit, and copies them over the image. Returns the number
+/// of tiles refined.
+async fn refine_tiles(
+    gpu: &Gpu,
+    uniforms: Uniforms,
+    scene: &Scene,
+    view: View,
+    refinement: &Refinement,
+    data: &mut [u8],
+    cancel: &AtomicBool,
+) -> usize {
+    info!("Planning refinement...");
+    let refined = refinement.plan(scene, view);
+    let mut limits: Vec<u32> = refined.iter().map(|refined| refined.iterations).collect();
+    limits.sort_unstable();
+    limits.dedup();
+
+    for iterations in limits {
+        let group: Vec<_> = refined
+            .iter()
+            .filter(|refined| refined.iterations == iterations)
+            .collect();
+        info!(
+            "Refining {} tiles at {} iterations...",
+            group.len(),
+            iterations
+        );
+        let limit_scene = Scene {
+            iterations,
+            ..scene.clone()
+        };
+        let module = gpu
+            .create_shader_module(&generate_shader(&limit_scene))
+            .await;
+        for refined in group {
+            let tile = refined.tile;
+            let tile_data = render_tile(gpu, &module, uniforms, &limit_scene, tile, cancel).await;
+            copy_region(
+                &tile_data,
+                tile.image_width,
+                0,
+                0,
+                data,
+                view.image_width,
+                tile.image_x - view.image_x,
+                tile.image_y - view.image_y,
+                tile.image_width,
+                tile.image_height,
+            );
+        }
+    }
+    refined.len()
+}
 
-    info!("Writing WGSL...");
-    let mut wgsl_file = File::create("debug.wgsl").await.unwrap();
-    wgsl_file.write_all(wgsl_str.as_bytes()).await.unwrap();
+/// Resamples an exponential map's strip into the frames of a zoom video.
+fn write_zoom_frames(frames: &ZoomFrames, strip: &[u8], strip_height: usize) {
+    std::fs::create_dir_all(&frames.path).unwrap();
+    let depths = frames.depths(
+        IMAGE_WIDTH as usize,
+        strip_height,
+        IMAGE_WIDTH as usize,
+        IMAGE_HEIGHT as usize,
+    );
+    for (index, &depth) in depths.iter().enumerate() {
+        info!("Writing frame {} of {}...", index + 1, depths.len());
+        let data = assemble_frame(
+            strip,
+            IMAGE_WIDTH as usize,
+            strip_height,
+            depth,
+            IMAGE_WIDTH as usize,
+            IMAGE_HEIGHT as usize,
+        );
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(IMAGE_WIDTH, IMAGE_HEIGHT, data).unwrap();
+        image
+            .save(frames.path.join(format!("frame_{:05}.png", index)))
+            .unwrap();
+    }
+}
 
-    ShaderSource::Wgsl(Cow::Owned(wgsl_str))
+/// Accumulates point densities into a histogram by running the compute stage
+/// in batches.
+async fn accumulate_density(
+    gpu: &Gpu,
+    module: &ShaderModule,
+    uniforms: Uniforms,
+    channels: u32,
+    batches: u64,
+    workgroups: u32,
+) -> Vec<u32> {
+    info!("Creating histogram...");
+    let histogram = Histogram::new(gpu, IMAGE_WIDTH, IMAGE_HEIGHT, channels);
+
+    info!("Accumulating points...");
+    histogram.accumulate(gpu, module, uniforms, batches, workgroups);
+
+    info!("Reading histogram...");
+    histogram.read(gpu).await
 }
@@ -0,0 +1,280 @@
+use crate::view::View;
+
+/// Renders an image by evaluating only the border pixels of rectangles. A
+/// rectangle whose border is all one color is filled with it, while others
+/// are split in four and their borders evaluated in turn, so large solid
+/// regions like the interior cost little more than their outline.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MarianiSilver {
+    /// Rectangles this wide or tall are evaluated pixel by pixel rather than
+    /// split again.
+    pub min_size: usize,
+}
+
+/// The progress of a Mariani–Silver render, one level of rectangles at a
+/// time.
+///
+/// Each level asks for the colors of a list of pixels through
+/// [`Subdivision::positions`], which are then handed back in the same order
+/// to [`Subdivision::advance`].
+pub struct Subdivision {
+    view: View,
+    min_size: usize,
+    colors: Vec<u32>,
+    evaluated: Vec<bool>,
+    /// Rectangles whose borders are being evaluated.
+    pending: Vec<View>,
+    /// Rectangles small enough that every pixel is being evaluated.
+    small: Vec<View>,
+    positions: Vec<(usize, usize)>,
+    evaluations: usize,
+}
+
+impl MarianiSilver {
+    /// Starts rendering a view, beginning with its own border.
+    pub fn subdivide(&self, view: View) -> Subdivision {
+        let mut subdivision = Subdivision {
+            view,
+            min_size: self.min_size.max(2),
+            colors: vec![0; view.image_width * view.image_height],
+            evaluated: vec![false; view.image_width * view.image_height],
+            pending: vec![],
+            small: vec![],
+            positions: vec![],
+            evaluations: 0,
+        };
+        subdivision.push(view);
+        subdivision
+    }
+}
+
+impl Subdivision {
+    /// The pixels, relative to the view, whose colors are needed next, or
+    /// `None` once every pixel has a color.
+    pub fn positions(&mut self) -> Option<&[(usize, usize)]> {
+        if self.pending.is_empty() && self.small.is_empty() {
+            return None;
+        }
+
+        self.positions.clear();
+        let mut queued = vec![false; self.evaluated.len()];
+        for rectangle in &self.pending {
+            for position in border(rectangle) {
+                Self::queue(
+                    position,
+                    &self.view,
+                    &self.evaluated,
+                    &mut queued,
+                    &mut self.positions,
+                );
+            }
+        }
+        for rectangle in &self.small {
+            for y in rectangle.image_y..rectangle.image_y + rectangle.image_height {
+                for x in rectangle.image_x..rectangle.image_x + rectangle.image_width {
+                    Self::queue(
+                        (x, y),
+                        &self.view,
+                        &self.evaluated,
+                        &mut queued,
+                        &mut self.positions,
+                    );
+                }
+            }
+        }
+        Some(&self.positions)
+    }
+
+    /// Stores the colors of the pixels from [`Subdivision::positions`], then
+    /// fills or splits each rectangle whose border is now known.
+    pub fn advance(&mut self, colors: &[u32]) {
+        assert_eq!(colors.len(), self.positions.len());
+        for (&(x, y), &color) in self.positions.iter().zip(colors) {
+            let index = y * self.view.image_width + x;
+            self.colors[index] = color;
+            self.evaluated[index] = true;
+        }
+        self.evaluations += colors.len();
+        self.small.clear();
+
+        for rectangle in std::mem::take(&mut self.pending) {
+            let mut colors = border(&rectangle).map(|position| self.color(position));
+            let first = colors.next().unwrap();
+            if colors.all(|color| color == first) {
+                self.fill(&rectangle, first);
+            } else {
+                let half_width = rectangle.image_width / 2 + rectangle.image_width % 2;
+                let half_height = rectangle.image_height / 2 + rectangle.image_height % 2;
+                for quarter in rectangle.subdivide_rectangles(half_width, half_height) {
+                    self.push(quarter);
+                }
+            }
+        }
+    }
+
+    /// The colors of every pixel in the view, once finished.
+    pub fn into_colors(self) -> Vec<u32> {
+        self.colors
+    }
+
+    /// The number of pixels whose colors were evaluated rather than filled
+    /// in.
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    fn push(&mut self, rectangle: View) {
+        if rectangle.image_width <= self.min_size || rectangle.image_height <= self.min_size {
+            self.small.push(rectangle);
+        } else {
+            self.pending.push(rectangle);
+        }
+    }
+
+    fn queue(
+        (x, y): (usize, usize),
+        view: &View,
+        evaluated: &[bool],
+        queued: &mut [bool],
+        positions: &mut Vec<(usize, usize)>,
+    ) {
+        let (x, y) = (x - view.image_x, y - view.image_y);
+        let index = y * view.image_width + x;
+        if !evaluated[index] && !queued[index] {
+            queued[index] = true;
+            positions.push((x, y));
+        }
+    }
+
+    fn color(&self, (x, y): (usize, usize)) -> u32 {
+        self.colors[(y - self.view.image_y) * self.view.image_width + x - self.view.image_x]
+    }
+
+    fn fill(&mut self, rectangle: &View, color: u32) {
+        for y in rectangle.image_y..rectangle.image_y + rectangle.image_height {
+            let row = (y - self.view.image_y) * self.view.image_width;
+            for x in rectangle.image_x..rectangle.image_x + rectangle.image_width {
+                let index = row + x - self.view.image_x;
+                self.colors[index] = color;
+                self.evaluated[index] = true;
+            }
+        }
+    }
+}
+
+/// The image coordinates of the pixels around the edge of a rectangle.
+fn border(rectangle: &View) -> impl Iterator<Item = (usize, usize)> {
+    let (left, top) = (rectangle.image_x, rectangle.image_y);
+    let right = left + rectangle.image_width - 1;
+    let bottom = top + rectangle.image_height - 1;
+    let rows = (left..=right).flat_map(move |x| vec![(x, top), (x, bottom)]);
+    let columns = (top + 1..bottom).flat_map(move |y| vec![(left, y), (right, y)]);
+    rows.chain(columns)
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        formula::{Family, Variables},
+        mariani_silver::MarianiSilver,
+        view::View,
+    };
+    use num_complex::Complex;
+
+    /// Renders a view on the CPU, counting the pixels evaluated.
+    fn render(
+        mariani_silver: MarianiSilver,
+        view: View,
+        color: impl Fn(usize, usize) -> u32,
+    ) -> (Vec<u32>, usize) {
+        let mut subdivision = mariani_silver.subdivide(view);
+        while let Some(positions) = subdivision.positions() {
+            let colors: Vec<u32> = positions.iter().map(|&(x, y)| color(x, y)).collect();
+            subdivision.advance(&colors);
+        }
+        let evaluations = subdivision.evaluations();
+        (subdivision.into_colors(), evaluations)
+    }
+
+    #[test]
+    fn matches_full_render() {
+        // A large disc and a band, neither of which can hide inside a
+        // rectangle without touching its border.
+        let color = |x: usize, y: usize| {
+            let (dx, dy) = (x as f32 - 60.0, y as f32 - 45.0);
+            if dx * dx + dy * dy < 900.0 {
+                0xff0000ff
+            } else if x > 100 {
+                0xff00ff00
+            } else {
+                0xff000000
+            }
+        };
+        let view = View::new_centered_uniform(150, 97, 3.0);
+        let full: Vec<u32> = (0..97)
+            .flat_map(|y| (0..150).map(move |x| (x, y)))
+            .map(|(x, y)| color(x, y))
+            .collect();
+
+        let (colors, evaluations) = render(MarianiSilver { min_size: 4 }, view, color);
+        assert_eq!(colors, full);
+        assert!(evaluations < full.len() / 2, "{}", evaluations);
+    }
+
+    #[test]
+    fn matches_full_mandelbrot_render() {
+        // Colors by escape time, like the iteration coloring.
+        let formula = Family::Multibrot { power: 2.0 }.formula();
+        let view = View::new_uniform(120, 90, 3.0, -0.6, 0.1);
+        let color = |x: usize, y: usize| {
+            let mut variables = Variables {
+                z: Complex::new(0.0, 0.0),
+                previous_z: Complex::new(0.0, 0.0),
+                c: view.get_local_plane_coordinates((x, y)),
+            };
+            let mut n = 0;
+            while n < 100 && variables.z.norm_sqr() <= 16.0 {
+                let z = formula.step(n, &variables);
+                variables.previous_z = variables.z;
+                variables.z = z;
+                n += 1;
+            }
+            n as u32
+        };
+        let full: Vec<u32> = (0..90)
+            .flat_map(|y| (0..120).map(move |x| (x, y)))
+            .map(|(x, y)| color(x, y))
+            .collect();
+
+        // Filaments thinner than a pixel can slip between border pixels, so
+        // a few pixels may differ.
+        let (colors, evaluations) = render(MarianiSilver { min_size: 4 }, view, color);
+        let differences = colors.iter().zip(&full).filter(|(a, b)| a != b).count();
+        assert!(differences < full.len() / 200, "{}", differences);
+        assert!(evaluations < full.len(), "{}", evaluations);
+    }
+
+    #[test]
+    fn evaluates_each_pixel_once() {
+        // Noise never has a uniform border, so every pixel ends up evaluated.
+        let color = |x: usize, y: usize| ((x * 7919 + y * 104729) % 13) as u32;
+        let view = View::new_centered_uniform(37, 23, 3.0);
+        let (colors, evaluations) = render(MarianiSilver { min_size: 3 }, view, color);
+        assert_eq!(evaluations, 37 * 23);
+        assert_eq!(colors[22 * 37 + 36], color(36, 22));
+    }
+
+    #[test]
+    fn solid_view_evaluates_border() {
+        let view = View::new_centered_uniform(40, 30, 3.0);
+        let mut subdivision = MarianiSilver { min_size: 4 }.subdivide(view);
+        let border = subdivision.positions().unwrap().len();
+        assert_eq!(border, 2 * 40 + 2 * 28);
+        subdivision.advance(&vec![7; border]);
+        assert!(subdivision.positions().is_none());
+        assert_eq!(subdivision.evaluations(), border);
+        assert!(subdivision.into_colors().iter().all(|&color| color == 7));
+    }
+}
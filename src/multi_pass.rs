@@ -0,0 +1,328 @@
+use crate::{
+    coloring::LoopVariable,
+    generator::{compute_group, write_start_point},
+    scene::Scene,
+    util::smallest_multiple_containing,
+};
+use std::{fmt::Write, time::Duration};
+
+/// Spreads escape-time iteration over several compute passes so no single
+/// dispatch runs long enough to trip driver watchdogs.
+///
+/// Each pass advances every unfinished pixel by at most `pass_iterations`,
+/// keeping `z`, `previous_z`, `n` and the colorings' loop variables per pixel
+/// between passes, and a final pass colors them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MultiPass {
+    pub pass_iterations: u32,
+    /// Stops iterating once this much time has passed, coloring pixels that
+    /// haven't finished as interior, as cancelling the render does.
+    pub time_limit: Option<Duration>,
+}
+
+/// Invocations along each side of the multi-pass compute stages' square
+/// workgroups.
+pub const WORKGROUP_SIDE: u32 = 8;
+
+/// The WGSL types of the fields every pixel state starts with: `z`,
+/// `previous_z`, `n` and `status`.
+const ORBIT_FIELD_TYPES: [&str; 4] = ["vec2<f32>", "vec2<f32>", "i32", "u32"];
+
+/// The status of a pixel no pass has reached yet. Buffers start zeroed, so
+/// this has to be 0.
+const STATUS_UNSTARTED: u32 = 0;
+/// The status of a pixel that needs more passes.
+const STATUS_ITERATING: u32 = 1;
+/// The status of a pixel that escaped or reached the iteration limit.
+const STATUS_FINISHED: u32 = 2;
+
+impl MultiPass {
+    /// The most passes needed to reach an iteration limit.
+    pub fn pass_count(&self, iterations: u32) -> u32 {
+        let pass_iterations = self.pass_iterations.max(1);
+        (iterations / pass_iterations + (iterations % pass_iterations).min(1)).max(1)
+    }
+
+    /// Writes the pixel state buffer, the final colors and the count of
+    /// finished pixels, bound at [`compute_group`], along with the
+    /// `iterate_main` compute stage that runs one pass and the `finish_main`
+    /// stage that colors every pixel from its state.
+    pub fn write_stages(&self, out: &mut String, scene: &Scene) {
+        out.push_str(
+            r#"
+struct PixelState {
+    z: vec2<f32>;
+    previous_z: vec2<f32>;
+    n: i32;
+    status: u32;
+"#,
+        );
+        for variable in loop_state(scene) {
+            writeln!(out, "    {}: {};", variable.name, variable.ty).unwrap();
+        }
+        write!(
+            out,
+            r#"}};
+
+[[block]]
+struct PixelStates {{
+    states: array<PixelState>;
+}};
+
+[[block]]
+struct FinalColors {{
+    colors: array<u32>;
+}};
+
+[[block]]
+struct Progress {{
+    finished: atomic<u32>;
+}};
+
+[[group({group}), binding(0)]]
+var<storage, read_write> pixel_states: PixelStates;
+[[group({group}), binding(1)]]
+var<storage, read_write> final_colors: FinalColors;
+[[group({group}), binding(2)]]
+var<storage, read_write> progress: Progress;
+
+let pass_iterations: i32 = {pass_iterations};
+
+fn pixel_index(id: vec3<u32>) -> i32 {{
+    if (f32(id.x) >= uniforms.view.image_size.x || f32(id.y) >= uniforms.view.image_size.y) {{
+        return -1;
+    }}
+    return i32(id.y * u32(uniforms.view.image_size.x) + id.x);
+}}
+
+fn final_color(state: PixelState, c: vec2<f32>, n: i32) -> vec4<f32> {{
+    let z = state.z;
+    let previous_z = state.previous_z;
+"#,
+            group = compute_group(scene),
+            pass_iterations = self.pass_iterations.max(1),
+        )
+        .unwrap();
+        for variable in loop_state(scene) {
+            writeln!(out, "    let {0} = state.{0};", variable.name).unwrap();
+        }
+        out.push_str("    if (n >= iterations) {\n");
+        scene.interior.write_color(out);
+        out.push_str("    } else {\n");
+        scene.exterior.write_color(out);
+        write!(
+            out,
+            r#"    }}
+}}
+
+[[stage(compute), workgroup_size({side}, {side})]]
+fn iterate_main([[builtin(global_invocation_id)]] id: vec3<u32>) {{
+    let index = pixel_index(id);
+    if (index < 0) {{
+        return;
+    }}
+    var state = pixel_states.states[index];
+    if (state.status == {finished}u) {{
+        return;
+    }}
+
+    let pixel = uniforms.view.plane_start + vec2<f32>(f32(id.x), f32(id.y)) * uniforms.view.image_scale;
+"#,
+            side = WORKGROUP_SIDE,
+            finished = STATUS_FINISHED,
+        )
+        .unwrap();
+        write_start_point(out, scene);
+        out.push_str("    var previous_z = vec2<f32>(0.0, 0.0);\n");
+        scene.exterior.write_loop_state(out);
+        scene.interior.write_loop_state(out);
+        write!(
+            out,
+            r#"    var n: i32 = 0;
+    if (state.status == {unstarted}u) {{
+"#,
+            unstarted = STATUS_UNSTARTED,
+        )
+        .unwrap();
+        if scene.applicable_interior_checks().cardioid {
+            out.push_str(
+                "        if (in_cardioid_or_bulb(c)) {\n            n = iterations;\n        }\n",
+            );
+        }
+        out.push_str(
+            r#"    } else {
+        z = state.z;
+        previous_z = state.previous_z;
+        n = state.n;
+"#,
+        );
+        for variable in loop_state(scene) {
+            writeln!(out, "        {0} = state.{0};", variable.name).unwrap();
+        }
+        out.push_str(
+            r#"    }
+
+    let end = min(n + pass_iterations, iterations);
+    for (; n < end; n = n + 1) {
+        if (length_sqr(z) > bailout * bailout) {
+            break;
+        }
+
+"#,
+        );
+        scene.exterior.write_iteration(out);
+        scene.interior.write_iteration(out);
+        out.push_str(
+            r#"        let z_next = f(z, previous_z, c, n);
+        previous_z = z;
+        z = z_next;
+"#,
+        );
+        scene.exterior.write_after_step(out);
+        out.push_str(
+            r#"    }
+
+    state.z = z;
+    state.previous_z = previous_z;
+    state.n = n;
+"#,
+        );
+        for variable in loop_state(scene) {
+            writeln!(out, "    state.{0} = {0};", variable.name).unwrap();
+        }
+        write!(
+            out,
+            r#"    state.status = {iterating}u;
+    if (n < end || n >= iterations) {{
+        state.status = {finished}u;
+        // Atomics are only parsed as expressions, so the old count is kept.
+        let previous = atomicAdd(&progress.finished, 1u);
+    }}
+    pixel_states.states[index] = state;
+}}
+
+[[stage(compute), workgroup_size({side}, {side})]]
+fn finish_main([[builtin(global_invocation_id)]] id: vec3<u32>) {{
+    let index = pixel_index(id);
+    if (index < 0) {{
+        return;
+    }}
+    let state = pixel_states.states[index];
+
+    let pixel = uniforms.view.plane_start + vec2<f32>(f32(id.x), f32(id.y)) * uniforms.view.image_scale;
+"#,
+            iterating = STATUS_ITERATING,
+            finished = STATUS_FINISHED,
+            side = WORKGROUP_SIDE,
+        )
+        .unwrap();
+        write_start_point(out, scene);
+        write!(
+            out,
+            r#"
+    // Pixels left unfinished by a cancelled render count as interior.
+    var n = state.n;
+    if (state.status != {finished}u) {{
+        n = iterations;
+    }}
+    var color = final_color(state, c, n);
+"#,
+            finished = STATUS_FINISHED,
+        )
+        .unwrap();
+        if let Some(condition) = scene.projection.miss_condition() {
+            writeln!(
+                out,
+                "    if ({}) {{\n        color = vec4<f32>(0.0, 0.0, 0.0, 1.0);\n    }}",
+                condition
+            )
+            .unwrap();
+        }
+        out.push_str(
+            r#"    let channels = clamp(color, vec4<f32>(0.0, 0.0, 0.0, 0.0), vec4<f32>(1.0, 1.0, 1.0, 1.0)) * 255.0 + vec4<f32>(0.5, 0.5, 0.5, 0.5);
+    final_colors.colors[index] = u32(channels.r) | (u32(channels.g) << 8u) | (u32(channels.b) << 16u) | (u32(channels.a) << 24u);
+}
+"#,
+        );
+    }
+}
+
+/// The loop variables of the scene's colorings, which pixel states keep
+/// after the orbit.
+fn loop_state(scene: &Scene) -> impl Iterator<Item = &'static LoopVariable> {
+    scene
+        .exterior
+        .loop_state()
+        .iter()
+        .chain(scene.interior.loop_state())
+}
+
+/// The size in bytes of the shader's `PixelState` for a scene, laid out by
+/// WGSL's alignment rules.
+pub fn pixel_state_size(scene: &Scene) -> u64 {
+    let types = ORBIT_FIELD_TYPES
+        .iter()
+        .copied()
+        .chain(loop_state(scene).map(|variable| variable.ty));
+
+    let mut size = 0;
+    let mut struct_align = 1;
+    for ty in types {
+        let (field_size, align) = match ty {
+            "f32" | "i32" | "u32" => (4, 4),
+            "vec2<f32>" => (8, 8),
+            "vec4<f32>" => (16, 16),
+            _ => panic!("Unsupported pixel state type: {}", ty),
+        };
+        size = smallest_multiple_containing(size, align) + field_size;
+        struct_align = struct_align.max(align);
+    }
+    smallest_multiple_containing(size, struct_align) as u64
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coloring::{AverageStatistic, ExteriorColoring, InteriorColoring, OrbitAverage},
+        multi_pass::{pixel_state_size, MultiPass},
+        scene::Scene,
+    };
+
+    #[test]
+    fn pass_count() {
+        let multi_pass = MultiPass {
+            pass_iterations: 64,
+            time_limit: None,
+        };
+        assert_eq!(multi_pass.pass_count(200), 4);
+        assert_eq!(multi_pass.pass_count(128), 2);
+        assert_eq!(multi_pass.pass_count(10), 1);
+    }
+
+    #[test]
+    fn pixel_state_layout() {
+        // The orbit alone has no trailing padding.
+        let scene = Scene::test_mandelbrot();
+        assert_eq!(pixel_state_size(&scene), 24);
+
+        // An atom domain's distance and iteration follow it directly.
+        let atom_domain = Scene {
+            interior: InteriorColoring::AtomDomain,
+            ..scene.clone()
+        };
+        assert_eq!(pixel_state_size(&atom_domain), 32);
+
+        // Three sums, then `z_older` aligned to 8 bytes.
+        let average = Scene {
+            exterior: ExteriorColoring::Average(OrbitAverage {
+                statistic: AverageStatistic::Stripe,
+                stripe_density: 5.0,
+                skip_iterations: 0,
+            }),
+            ..atom_domain
+        };
+        assert_eq!(pixel_state_size(&average), 56);
+    }
+}
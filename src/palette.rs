@@ -0,0 +1,139 @@
+use crate::{generator::float_literal, scene::UnknownOption};
+use std::{fmt::Write, str::FromStr};
+
+/// A gradient mapping values in [0, 1] to colors.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Palette {
+    /// Black through red and orange to white.
+    Fire,
+    /// Black through blue and cyan to white.
+    Ice,
+    /// Black to white.
+    Grayscale,
+}
+
+impl Palette {
+    /// The colors the gradient passes through, evenly spaced.
+    fn stops(self) -> &'static [[f32; 3]] {
+        match self {
+            Palette::Fire => &[
+                [0.0, 0.0, 0.0],
+                [0.5, 0.0, 0.0],
+                [1.0, 0.35, 0.0],
+                [1.0, 0.8, 0.2],
+                [1.0, 1.0, 1.0],
+            ],
+            Palette::Ice => &[
+                [0.0, 0.0, 0.0],
+                [0.0, 0.1, 0.4],
+                [0.0, 0.5, 0.9],
+                [0.5, 0.9, 1.0],
+                [1.0, 1.0, 1.0],
+            ],
+            Palette::Grayscale => &[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]],
+        }
+    }
+
+    /// Looks up the RGB color at `t`, which is clamped to [0, 1].
+    pub fn color(self, t: f32) -> [u8; 3] {
+        let stops = self.stops();
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let fraction = position - index as f32;
+
+        let mut color = [0u8; 3];
+        for (channel, value) in color.iter_mut().enumerate() {
+            let start = stops[index][channel];
+            let end = stops[index + 1][channel];
+            *value = ((start + (end - start) * fraction) * 255.0).round() as u8;
+        }
+        color
+    }
+
+    /// Writes a WGSL function `name(t: f32) -> vec4<f32>` looking up colors
+    /// the same way as [`Palette::color`].
+    pub fn write_wgsl(self, out: &mut String, name: &str) {
+        let stops = self.stops();
+        let segments = stops.len() - 1;
+        write!(
+            out,
+            "\nfn {}(t: f32) -> vec4<f32> {{\n    let position = clamp(t, 0.0, 1.0) * {};\n",
+            name,
+            float_literal(segments as f32)
+        )
+        .unwrap();
+        for segment in 0..segments {
+            let start = rgb_literal(stops[segment]);
+            let end = rgb_literal(stops[segment + 1]);
+            let fraction = format!("(position - {})", float_literal(segment as f32));
+            if segment + 1 < segments {
+                write!(
+                    out,
+                    "    if (position < {}) {{\n        return vec4<f32>({} + ({} - {}) * {}, 1.0);\n    }}\n",
+                    float_literal((segment + 1) as f32),
+                    start,
+                    end,
+                    start,
+                    fraction
+                )
+                .unwrap();
+            } else {
+                write!(
+                    out,
+                    "    return vec4<f32>({} + ({} - {}) * {}, 1.0);\n}}\n",
+                    start, end, start, fraction
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+fn rgb_literal(color: [f32; 3]) -> String {
+    format!(
+        "vec3<f32>({}, {}, {})",
+        float_literal(color[0]),
+        float_literal(color[1]),
+        float_literal(color[2])
+    )
+}
+
+impl FromStr for Palette {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fire" => Ok(Palette::Fire),
+            "ice" => Ok(Palette::Ice),
+            "grayscale" => Ok(Palette::Grayscale),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::palette::Palette;
+
+    #[test]
+    fn palette_endpoints() {
+        for &palette in &[Palette::Fire, Palette::Ice, Palette::Grayscale] {
+            assert_eq!(palette.color(0.0), [0, 0, 0]);
+            assert_eq!(palette.color(1.0), [255, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn palette_interpolates() {
+        assert_eq!(Palette::Grayscale.color(0.5), [128, 128, 128]);
+        assert_eq!(Palette::Fire.color(0.125), [64, 0, 0]);
+    }
+
+    #[test]
+    fn palette_clamps() {
+        assert_eq!(Palette::Ice.color(-1.0), [0, 0, 0]);
+        assert_eq!(Palette::Ice.color(2.0), [255, 255, 255]);
+    }
+}
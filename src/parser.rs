@@ -0,0 +1,298 @@
+use crate::{complex::Function, formula::Expr};
+use num_complex::Complex;
+use std::{iter::Peekable, str::Chars};
+
+/// Error potentially returned when parsing an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedCharacter(char),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnknownIdentifier(String),
+    InvalidNumber(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Identifier(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    OpenParen,
+    CloseParen,
+}
+
+/// Parses an expression such as `z^3 - 2z + 1` or `(z^2 + c) / (z - 1i)`.
+///
+/// Numbers followed directly by a variable or parenthesis are multiplied, and
+/// `i` is the imaginary unit.
+pub fn parse_expr(source: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens, index: 0 };
+    let expr = parser.expr()?;
+
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+
+    while let Some(&ch) = chars.peek() {
+        let token = match ch {
+            ' ' | '\t' | '\n' => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => {
+                tokens.push(Token::Number(number(&mut chars)?));
+                continue;
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut identifier = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_ascii_alphanumeric() || ch == '_' {
+                        identifier.push(ch);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Identifier(identifier));
+                continue;
+            }
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '^' => Token::Caret,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            other => return Err(ParseError::UnexpectedCharacter(other)),
+        };
+        chars.next();
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a number, leaving the iterator on the character after it.
+fn number(chars: &mut Peekable<Chars>) -> Result<f32, ParseError> {
+    let mut literal = String::new();
+    while let Some(&ch) = chars.peek() {
+        let exponent_sign = (ch == '-' || ch == '+') && literal.ends_with('e');
+        if ch.is_ascii_digit() || ch == '.' || exponent_sign || is_exponent(chars) {
+            literal.push(ch);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+
+    literal
+        .parse()
+        .map_err(|_| ParseError::InvalidNumber(literal.clone()))
+}
+
+/// Returns whether the iterator is on the `e` of an exponent, rather than
+/// the start of an identifier such as `exp`.
+fn is_exponent(chars: &Peekable<Chars>) -> bool {
+    let mut lookahead = chars.clone();
+    lookahead.next() == Some('e')
+        && matches!(lookahead.next(), Some(ch) if ch.is_ascii_digit() || ch == '-' || ch == '+')
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Plus) => {
+                    self.next();
+                    expr = expr + self.term()?;
+                }
+                Some(Token::Minus) => {
+                    self.next();
+                    expr = expr - self.term()?;
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// term := unary (('*' | '/')? unary)*
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Star) => {
+                    self.next();
+                    expr = expr * self.unary()?;
+                }
+                Some(Token::Slash) => {
+                    self.next();
+                    expr = expr / self.unary()?;
+                }
+                // Implicit multiplication, as in `2z` or `3(z + 1)`.
+                Some(Token::Number(_)) | Some(Token::Identifier(_)) | Some(Token::OpenParen) => {
+                    expr = expr * self.power()?;
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if let Some(Token::Minus) = self.peek() {
+            self.next();
+            Ok(-self.unary()?)
+        } else {
+            self.power()
+        }
+    }
+
+    /// power := primary ('^' unary)?
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.primary()?;
+        if let Some(Token::Caret) = self.peek() {
+            self.next();
+            Ok(base.pow(self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    /// primary := number | variable | function '(' expr ')' | '(' expr ')'
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::constant(value, 0.0)),
+            Some(Token::OpenParen) => {
+                let expr = self.expr()?;
+                self.expect(Token::CloseParen)?;
+                Ok(expr)
+            }
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "z" => Ok(Expr::Z),
+                "c" => Ok(Expr::C),
+                "i" => Ok(Expr::Constant(Complex::new(0.0, 1.0))),
+                _ => {
+                    self.expect(Token::OpenParen)?;
+                    let argument = self.expr()?;
+                    self.expect(Token::CloseParen)?;
+                    function(&name, argument)
+                }
+            },
+            Some(token) => Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+}
+
+/// Applies a named function to its argument.
+fn function(name: &str, argument: Expr) -> Result<Expr, ParseError> {
+    match name {
+        "conj" => Ok(argument.conj()),
+        "re" => Ok(argument.re()),
+        "im" => Ok(argument.im()),
+        "abs" => Ok(argument.abs()),
+        _ => match name.parse::<Function>() {
+            Ok(function) => Ok(argument.apply(function)),
+            Err(_) => Err(ParseError::UnknownIdentifier(name.to_string())),
+        },
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        complex::Function,
+        formula::{Expr, Variables},
+        parser::{parse_expr, ParseError},
+    };
+    use num_complex::Complex;
+
+    fn evaluate(source: &str, z: Complex<f32>) -> Complex<f32> {
+        parse_expr(source).unwrap().evaluate(&Variables {
+            z,
+            previous_z: Complex::new(0.0, 0.0),
+            c: Complex::new(0.5, 0.0),
+        })
+    }
+
+    #[test]
+    fn parse_precedence() {
+        assert_eq!(
+            parse_expr("z + 2 * z^2").unwrap(),
+            Expr::Z + Expr::constant(2.0, 0.0) * Expr::Z.pow(Expr::constant(2.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn parse_implicit_multiplication() {
+        let z = Complex::new(0.5, 0.25);
+        let expected: Complex<f32> = 3.0 * z * z - Complex::new(0.0, 2.0) * (z + 1.0);
+        assert!((evaluate("3z^2 - 2i(z + 1)", z) - expected).norm() < 1e-5);
+    }
+
+    #[test]
+    fn parse_negative_exponent() {
+        let z = Complex::new(2.0, 0.0);
+        assert!((evaluate("z^-1 + c", z) - Complex::new(1.0, 0.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn parse_scientific_notation() {
+        assert_eq!(parse_expr("1.5e-3").unwrap(), Expr::constant(1.5e-3, 0.0));
+        assert_eq!(
+            parse_expr("2exp(z)").unwrap(),
+            Expr::constant(2.0, 0.0) * Expr::Z.apply(Function::Exp)
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_expr("z +"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            parse_expr("foo(z)"),
+            Err(ParseError::UnknownIdentifier("foo".to_string()))
+        );
+        assert_eq!(
+            parse_expr("z $ 2"),
+            Err(ParseError::UnexpectedCharacter('$'))
+        );
+    }
+}
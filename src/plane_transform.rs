@@ -0,0 +1,285 @@
+use crate::{
+    complex::Function,
+    formula::{Expr, Variables},
+    scene::UnknownOption,
+};
+use num_complex::Complex;
+use std::{fmt::Write, str::FromStr};
+
+/// Remaps a pixel's position on the plane before iteration starts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PlaneTransform {
+    /// `1 / p`, which turns the plane inside out so the area around infinity
+    /// is in the middle.
+    Inversion,
+    /// Treats the pixel as `λ` in `λz(1 - z)`, using the `c` of the
+    /// conjugate quadratic `z^2 + c`, which is `λ/2 - λ^2/4`.
+    Lambda,
+    /// `(a p + b) / (c p + d)`.
+    Mobius {
+        a: Complex<f32>,
+        b: Complex<f32>,
+        c: Complex<f32>,
+        d: Complex<f32>,
+    },
+    /// Treats `x` as an angle and `y` as a depth, `radius * e^(-y)`, around a
+    /// center. Used by the exponential map.
+    LogPolar { center: Complex<f32>, radius: f32 },
+}
+
+impl PlaneTransform {
+    /// The transformed point as an expression of `z`, the point before the
+    /// transform.
+    pub fn expr(&self) -> Expr {
+        match *self {
+            PlaneTransform::Inversion => Expr::Z.apply(Function::Reciprocal),
+            PlaneTransform::Lambda => {
+                Expr::constant(0.5, 0.0) * Expr::Z - Expr::constant(0.25, 0.0) * Expr::Z.sqr()
+            }
+            PlaneTransform::Mobius { a, b, c, d } => {
+                (Expr::Constant(a) * Expr::Z + Expr::Constant(b))
+                    / (Expr::Constant(c) * Expr::Z + Expr::Constant(d))
+            }
+            PlaneTransform::LogPolar { center, radius } => {
+                Expr::Constant(center)
+                    + Expr::constant(radius, 0.0)
+                        * Expr::complex(-Expr::Z.im(), Expr::Z.re()).apply(Function::Exp)
+            }
+        }
+    }
+
+    /// Whether transforming the conjugate of a point gives the conjugate of
+    /// the transformed point, keeping the plane's mirror symmetry.
+    pub fn preserves_conjugation(&self) -> bool {
+        match *self {
+            PlaneTransform::Inversion | PlaneTransform::Lambda => true,
+            PlaneTransform::Mobius { a, b, c, d } => {
+                a.im == 0.0 && b.im == 0.0 && c.im == 0.0 && d.im == 0.0
+            }
+            PlaneTransform::LogPolar { .. } => false,
+        }
+    }
+
+    /// Whether transforming the negation of a point gives the negation of the
+    /// transformed point, keeping the plane's half turn symmetry about 0.
+    pub fn preserves_negation(&self) -> bool {
+        let zero = Complex::new(0.0, 0.0);
+        match *self {
+            PlaneTransform::Inversion => true,
+            PlaneTransform::Mobius { a, b, c, d } => {
+                (b == zero && c == zero) || (a == zero && d == zero)
+            }
+            PlaneTransform::Lambda | PlaneTransform::LogPolar { .. } => false,
+        }
+    }
+
+    /// Applies this transform on the CPU, using the same formula as the
+    /// shader.
+    pub fn apply(&self, point: Complex<f32>) -> Complex<f32> {
+        self.expr().evaluate(&Variables {
+            z: point,
+            previous_z: Complex::new(0.0, 0.0),
+            c: Complex::new(0.0, 0.0),
+        })
+    }
+}
+
+/// Applies a chain of transforms in order.
+pub fn apply_chain(transforms: &[PlaneTransform], point: Complex<f32>) -> Complex<f32> {
+    transforms
+        .iter()
+        .fold(point, |point, transform| transform.apply(point))
+}
+
+/// Writes the `plane_transform` function applying a chain of transforms in
+/// order.
+pub fn write_chain(out: &mut String, transforms: &[PlaneTransform]) {
+    out.push_str("\nfn plane_transform(pixel: vec2<f32>) -> vec2<f32> {\n    var z = pixel;\n");
+    for transform in transforms {
+        writeln!(out, "    z = {};", transform.expr().to_wgsl()).unwrap();
+    }
+    out.push_str("    return z;\n}\n");
+}
+
+/// Parses a comma separated chain of transforms like
+/// `inversion, mobius(1, 0.5i, 0, 1)`.
+pub fn parse_chain(s: &str) -> Result<Vec<PlaneTransform>, UnknownOption> {
+    if s.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Only split on commas outside of parentheses.
+    let mut transforms = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, character) in s.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                transforms.push(s[start..index].trim().parse()?);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    transforms.push(s[start..].trim().parse()?);
+    Ok(transforms)
+}
+
+impl FromStr for PlaneTransform {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inversion" => Ok(PlaneTransform::Inversion),
+            "lambda" => Ok(PlaneTransform::Lambda),
+            _ => {
+                let arguments = s
+                    .strip_prefix("mobius(")
+                    .and_then(|s| s.strip_suffix(')'))
+                    .ok_or(UnknownOption)?;
+                let coefficients = arguments
+                    .split(',')
+                    .map(|argument| argument.trim().parse().map_err(|_| UnknownOption))
+                    .collect::<Result<Vec<Complex<f32>>, _>>()?;
+                match coefficients[..] {
+                    [a, b, c, d] => Ok(PlaneTransform::Mobius { a, b, c, d }),
+                    _ => Err(UnknownOption),
+                }
+            }
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        plane_transform::{apply_chain, parse_chain, PlaneTransform},
+        scene::UnknownOption,
+    };
+    use num_complex::Complex;
+
+    fn assert_close(a: Complex<f32>, b: Complex<f32>) {
+        assert!((a - b).norm() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn apply_transforms() {
+        let p = Complex::new(0.5, -1.5);
+        assert_close(PlaneTransform::Inversion.apply(p), 1.0 / p);
+        assert_close(PlaneTransform::Lambda.apply(p), p / 2.0 - p * p / 4.0);
+
+        let (a, b, c, d) = (
+            Complex::new(1.0, 1.0),
+            Complex::new(0.0, 0.5),
+            Complex::new(0.25, 0.0),
+            Complex::new(2.0, -1.0),
+        );
+        assert_close(
+            PlaneTransform::Mobius { a, b, c, d }.apply(p),
+            (a * p + b) / (c * p + d),
+        );
+
+        let log_polar = PlaneTransform::LogPolar {
+            center: Complex::new(-0.75, 0.1),
+            radius: 2.0,
+        };
+        let angle = std::f32::consts::FRAC_PI_2;
+        assert_close(
+            log_polar.apply(Complex::new(angle, 2.0_f32.ln())),
+            Complex::new(-0.75, 1.1),
+        );
+    }
+
+    #[test]
+    fn preserves_conjugation() {
+        let p = Complex::new(0.5, -1.5);
+        let real_mobius = PlaneTransform::Mobius {
+            a: Complex::new(1.0, 0.0),
+            b: Complex::new(0.5, 0.0),
+            c: Complex::new(-2.0, 0.0),
+            d: Complex::new(1.0, 0.0),
+        };
+        for transform in &[
+            PlaneTransform::Inversion,
+            PlaneTransform::Lambda,
+            real_mobius,
+        ] {
+            assert!(transform.preserves_conjugation());
+            assert_close(transform.apply(p.conj()), transform.apply(p).conj());
+        }
+
+        let complex_mobius = PlaneTransform::Mobius {
+            a: Complex::new(1.0, 0.0),
+            b: Complex::new(0.0, 0.5),
+            c: Complex::new(0.0, 0.0),
+            d: Complex::new(1.0, 0.0),
+        };
+        assert!(!complex_mobius.preserves_conjugation());
+    }
+
+    #[test]
+    fn preserves_negation() {
+        let p = Complex::new(0.5, -1.5);
+        let scaling = PlaneTransform::Mobius {
+            a: Complex::new(2.0, 1.0),
+            b: Complex::new(0.0, 0.0),
+            c: Complex::new(0.0, 0.0),
+            d: Complex::new(1.0, 0.0),
+        };
+        let reciprocal = PlaneTransform::Mobius {
+            a: Complex::new(0.0, 0.0),
+            b: Complex::new(1.0, 0.0),
+            c: Complex::new(0.0, 1.0),
+            d: Complex::new(0.0, 0.0),
+        };
+        for transform in &[PlaneTransform::Inversion, scaling, reciprocal] {
+            assert!(transform.preserves_negation());
+            assert_close(transform.apply(-p), -transform.apply(p));
+        }
+
+        assert!(!PlaneTransform::Lambda.preserves_negation());
+        let translation = PlaneTransform::Mobius {
+            a: Complex::new(1.0, 0.0),
+            b: Complex::new(0.5, 0.0),
+            c: Complex::new(0.0, 0.0),
+            d: Complex::new(1.0, 0.0),
+        };
+        assert!(!translation.preserves_negation());
+    }
+
+    #[test]
+    fn apply_transform_chain() {
+        let p = Complex::new(0.5, -1.5);
+        let chain = [PlaneTransform::Inversion, PlaneTransform::Lambda];
+        let inverted = 1.0 / p;
+        assert_close(
+            apply_chain(&chain, p),
+            inverted / 2.0 - inverted * inverted / 4.0,
+        );
+        assert_close(apply_chain(&[], p), p);
+    }
+
+    #[test]
+    fn parse_transform_chain() {
+        assert_eq!(parse_chain(""), Ok(vec![]));
+        assert_eq!(
+            parse_chain("inversion, mobius(1, 0.5i, 0, 1+2i), lambda"),
+            Ok(vec![
+                PlaneTransform::Inversion,
+                PlaneTransform::Mobius {
+                    a: Complex::new(1.0, 0.0),
+                    b: Complex::new(0.0, 0.5),
+                    c: Complex::new(0.0, 0.0),
+                    d: Complex::new(1.0, 2.0),
+                },
+                PlaneTransform::Lambda,
+            ])
+        );
+        assert_eq!(parse_chain("inversion, spiral"), Err(UnknownOption));
+        assert_eq!(parse_chain("mobius(1, 2, 3)"), Err(UnknownOption));
+    }
+}
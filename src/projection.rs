@@ -0,0 +1,232 @@
+use crate::{generator::float_literal, view::View};
+use cgmath::{Deg, Matrix, Matrix3, Vector3};
+use num_complex::Complex;
+use std::{f32::consts::PI, fmt::Write};
+
+/// Maps pixels onto the complex plane, either directly or through the
+/// Riemann sphere so the whole plane including infinity can be seen at once.
+///
+/// The sphere is the unit sphere centered at 0, with 0 at its south pole,
+/// infinity at its north pole and its equator on the unit circle, and points
+/// on it are carried to the plane by stereographic projection. Yaw
+/// spins the sphere about the axis between 0 and infinity, and pitch tilts
+/// infinity towards the viewer, both in degrees.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    /// Pixels are points on the plane.
+    Flat,
+    /// A 360° panorama of the sphere, with longitude across the image and
+    /// latitude down it from infinity at the top to 0 at the bottom.
+    Equirectangular { yaw: f32, pitch: f32 },
+    /// The sphere seen from far away, facing 0 before rotating.
+    Orthographic { yaw: f32, pitch: f32 },
+}
+
+impl Projection {
+    /// The view of the projection's pixel coordinates.
+    ///
+    /// Equirectangular views cover every longitude and latitude, while
+    /// orthographic views frame the sphere's disc.
+    pub fn view(&self, image_width: usize, image_height: usize) -> View {
+        match self {
+            Projection::Flat => View::new_centered_uniform(image_width, image_height, 3.0),
+            Projection::Equirectangular { .. } => View {
+                image_width,
+                image_height,
+                image_x: 0,
+                image_y: 0,
+                image_scale_x: 2.0 * PI / image_width as f32,
+                image_scale_y: PI / image_height as f32,
+                plane_start_x: -PI,
+                plane_start_y: -PI / 2.0,
+            },
+            Projection::Orthographic { .. } => {
+                View::new_centered_uniform(image_width, image_height, 2.2)
+            }
+        }
+    }
+
+    /// Projects a pixel's coordinates onto the plane, returning `None` for
+    /// pixels that miss the sphere.
+    pub fn project(&self, pixel: Complex<f32>) -> Option<Complex<f32>> {
+        let point = match *self {
+            Projection::Flat => return Some(pixel),
+            Projection::Equirectangular { .. } => {
+                let (longitude, latitude) = (pixel.re, -pixel.im);
+                Vector3::new(
+                    latitude.cos() * longitude.cos(),
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                )
+            }
+            Projection::Orthographic { .. } => {
+                let distance_sqr = pixel.norm_sqr();
+                if distance_sqr > 1.0 {
+                    return None;
+                }
+                Vector3::new(pixel.re, pixel.im, -(1.0 - distance_sqr).sqrt())
+            }
+        };
+
+        let point = self.rotation() * point;
+        Some(Complex::new(point.x, point.y) / (1.0 - point.z))
+    }
+
+    /// Writes the `project` function mapping a pixel's coordinates onto the
+    /// plane, matching [`Projection::project`].
+    pub fn write_function(&self, out: &mut String) {
+        out.push_str("\nfn project(pixel: vec2<f32>) -> vec2<f32> {\n");
+        let point = match self {
+            Projection::Flat => {
+                out.push_str("    return pixel;\n}\n");
+                return;
+            }
+            Projection::Equirectangular { .. } => {
+                r#"    let latitude = -pixel.y;
+    let point = vec3<f32>(cos(latitude) * cos(pixel.x), cos(latitude) * sin(pixel.x), sin(latitude));
+"#
+            }
+            Projection::Orthographic { .. } => {
+                "    let point = vec3<f32>(pixel, -sqrt(max(1.0 - length_sqr(pixel), 0.0)));\n"
+            }
+        };
+        out.push_str(point);
+
+        let rotation = self.rotation();
+        let rows: Vec<String> = (0..3)
+            .map(|i| {
+                let row = rotation.row(i);
+                format!(
+                    "dot(vec3<f32>({}, {}, {}), point)",
+                    float_literal(row.x),
+                    float_literal(row.y),
+                    float_literal(row.z)
+                )
+            })
+            .collect();
+        write!(
+            out,
+            r#"    let rotated = vec3<f32>({}, {}, {});
+    return rotated.xy / (1.0 - rotated.z);
+}}
+"#,
+            rows[0], rows[1], rows[2]
+        )
+        .unwrap();
+    }
+
+    /// Writes an early return for pixels that miss the sphere, once `pixel`
+    /// has been computed.
+    pub fn write_miss_check(&self, out: &mut String) {
+        if let Some(condition) = self.miss_condition() {
+            write!(
+                out,
+                "    if ({}) {{\n        return vec4<f32>(0.0, 0.0, 0.0, 1.0);\n    }}\n",
+                condition
+            )
+            .unwrap();
+        }
+    }
+
+    /// The WGSL condition under which `pixel` misses the sphere, for
+    /// projections that can miss.
+    pub fn miss_condition(&self) -> Option<&'static str> {
+        match self {
+            Projection::Orthographic { .. } => Some("length_sqr(pixel) > 1.0"),
+            _ => None,
+        }
+    }
+
+    fn rotation(&self) -> Matrix3<f32> {
+        match *self {
+            Projection::Flat => Matrix3::from_scale(1.0),
+            Projection::Equirectangular { yaw, pitch }
+            | Projection::Orthographic { yaw, pitch } => {
+                Matrix3::from_angle_z(Deg(yaw)) * Matrix3::from_angle_x(Deg(pitch))
+            }
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::projection::Projection;
+    use num_complex::Complex;
+    use std::f32::consts::PI;
+
+    fn assert_close(a: Option<Complex<f32>>, b: Complex<f32>) {
+        let a = a.unwrap();
+        assert!((a - b).norm() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn project_equirectangular() {
+        let projection = Projection::Equirectangular {
+            yaw: 0.0,
+            pitch: 0.0,
+        };
+
+        // The equator is the unit circle, and the bottom is 0.
+        assert_close(
+            projection.project(Complex::new(0.0, 0.0)),
+            Complex::new(1.0, 0.0),
+        );
+        assert_close(
+            projection.project(Complex::new(PI / 2.0, 0.0)),
+            Complex::new(0.0, 1.0),
+        );
+        assert_close(
+            projection.project(Complex::new(0.0, PI / 2.0)),
+            Complex::new(0.0, 0.0),
+        );
+
+        // Halfway up from the equator, points are farther out.
+        let point = projection.project(Complex::new(0.0, -PI / 4.0)).unwrap();
+        assert!((point.norm() - 0.5_f32.sqrt() / (1.0 - 0.5_f32.sqrt())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn project_orthographic() {
+        let projection = Projection::Orthographic {
+            yaw: 0.0,
+            pitch: 0.0,
+        };
+        assert_close(
+            projection.project(Complex::new(0.0, 0.0)),
+            Complex::new(0.0, 0.0),
+        );
+        assert_close(
+            projection.project(Complex::new(1.0, 0.0)),
+            Complex::new(1.0, 0.0),
+        );
+        assert_eq!(projection.project(Complex::new(0.8, 0.8)), None);
+
+        // Tilting the sphere a quarter turn brings i to the middle.
+        let tilted = Projection::Orthographic {
+            yaw: 0.0,
+            pitch: 90.0,
+        };
+        assert_close(
+            tilted.project(Complex::new(0.0, 0.0)),
+            Complex::new(0.0, 1.0),
+        );
+
+        // Yaw spins the plane about 0.
+        let spun = Projection::Orthographic {
+            yaw: 90.0,
+            pitch: 0.0,
+        };
+        assert_close(
+            spun.project(Complex::new(0.5, 0.0)),
+            Complex::new(0.0, 0.5 / (1.0 + 0.75_f32.sqrt())),
+        );
+    }
+
+    #[test]
+    fn flat_projection() {
+        let pixel = Complex::new(-0.5, 2.0);
+        assert_eq!(Projection::Flat.project(pixel), Some(pixel));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    camera::Camera,
+    generator::{float_literal, write_frag_main_start},
+};
+use std::fmt::Write;
+
+/// A 3D fractal with a distance estimate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Fractal3D {
+    /// The Mandelbrot set extended to 3D through spherical coordinates.
+    Mandelbulb { power: f32 },
+    /// Repeatedly box folds, sphere folds, scales and translates by the
+    /// point.
+    Mandelbox {
+        scale: f32,
+        min_radius: f32,
+        fixed_radius: f32,
+    },
+    /// The Julia set of `q^2 + c` over quaternions, cut by the plane where
+    /// the last component of `q` is 0.
+    QuaternionJulia { c: [f32; 4] },
+}
+
+/// Renders a 3D fractal by marching a ray from the camera through each pixel
+/// until the fractal's distance estimate says it has hit the surface.
+///
+/// The camera's position and orientation are uniforms, and its field of view
+/// sets the view, so the camera can move between frames without generating
+/// a new shader.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayMarch {
+    pub fractal: Fractal3D,
+    pub camera: Camera,
+    /// The number of iterations of the fractal's formula in each distance
+    /// estimate.
+    pub iterations: u32,
+    /// The most steps a ray takes before it is considered to have missed.
+    pub max_steps: u32,
+    /// How sharp shadow edges are, with larger values being harder.
+    pub shadow_softness: f32,
+    /// How much creases are darkened by ambient occlusion, from 0 for none.
+    pub occlusion_strength: f32,
+    /// How quickly distant surfaces fade into the background.
+    pub fog_density: f32,
+}
+
+impl RayMarch {
+    /// Writes the distance estimate, lighting and the fragment stage.
+    pub fn write_shader(&self, out: &mut String) {
+        out.push_str(
+            r#"
+let max_distance: f32 = 20.0;
+let fog_color: vec3<f32> = vec3<f32>(0.55, 0.6, 0.7);
+let light_direction: vec3<f32> = vec3<f32>(0.5, 0.7, -0.5);
+"#,
+        );
+        self.write_distance_estimate(out);
+        write!(
+            out,
+            r#"
+fn surface_normal(p: vec3<f32>, epsilon: f32) -> vec3<f32> {{
+    let e = vec2<f32>(1.0, -1.0) * epsilon;
+    return normalize(
+        e.xyy * distance_estimate(p + e.xyy) +
+        e.yyx * distance_estimate(p + e.yyx) +
+        e.yxy * distance_estimate(p + e.yxy) +
+        e.xxx * distance_estimate(p + e.xxx)
+    );
+}}
+
+// Marches toward the light, darkening by how closely the ray passes the
+// surface on the way.
+fn soft_shadow(origin: vec3<f32>, direction: vec3<f32>) -> f32 {{
+    var shadow = 1.0;
+    var t = 0.01;
+    for (var i: i32 = 0; i < 64; i = i + 1) {{
+        let d = distance_estimate(origin + t * direction);
+        shadow = min(shadow, {shadow_softness} * d / t);
+        if (shadow < 0.001 || t > max_distance) {{
+            break;
+        }}
+        t = t + clamp(d, 0.005, 0.5);
+    }}
+    return clamp(shadow, 0.0, 1.0);
+}}
+
+// Samples the distance estimate along the normal, which is smaller than the
+// sample's distance from the surface in creases.
+fn ambient_occlusion(p: vec3<f32>, normal: vec3<f32>) -> f32 {{
+    var occlusion = 0.0;
+    var weight = 1.0;
+    for (var i: i32 = 1; i <= 5; i = i + 1) {{
+        let h = 0.01 + 0.03 * f32(i);
+        occlusion = occlusion + weight * (h - distance_estimate(p + h * normal));
+        weight = weight * 0.7;
+    }}
+    return clamp(1.0 - {occlusion_strength} * 3.0 * occlusion, 0.0, 1.0);
+}}
+"#,
+            shadow_softness = float_literal(self.shadow_softness),
+            occlusion_strength = float_literal(self.occlusion_strength),
+        )
+        .unwrap();
+
+        write_frag_main_start(out);
+        write!(
+            out,
+            r#"    let origin = uniforms.camera.position.xyz;
+    // Pixels are laid out top to bottom, so the image plane's y is flipped.
+    let direction = normalize(uniforms.camera.forward.xyz + pixel.x * uniforms.camera.right.xyz - pixel.y * uniforms.camera.up.xyz);
+
+    // Rays are considered to hit the surface when closer to it than about
+    // the width of their pixel.
+    let pixel_angle = uniforms.view.image_scale.x;
+
+    var t = 0.0;
+    var hit = false;
+    for (var step: i32 = 0; step < {max_steps}; step = step + 1) {{
+        let d = distance_estimate(origin + t * direction);
+        if (d < 0.5 * pixel_angle * t) {{
+            hit = true;
+            break;
+        }}
+        t = t + d;
+        if (t > max_distance) {{
+            break;
+        }}
+    }}
+    if (!hit) {{
+        return vec4<f32>(fog_color, 1.0);
+    }}
+
+    let p = origin + t * direction;
+    let epsilon = max(pixel_angle * t, 0.00001);
+    let normal = surface_normal(p, epsilon);
+    let light = normalize(light_direction);
+    let diffuse = max(dot(normal, light), 0.0) * soft_shadow(p + 2.0 * epsilon * normal, light);
+    let occlusion = ambient_occlusion(p, normal);
+    var color = vec3<f32>(0.9, 0.75, 0.55) * (0.2 * occlusion + 0.8 * diffuse);
+
+    let fog = 1.0 - exp(-{fog_density} * t);
+    color = color + (fog_color - color) * fog;
+    return vec4<f32>(color, 1.0);
+}}
+"#,
+            max_steps = self.max_steps,
+            fog_density = float_literal(self.fog_density),
+        )
+        .unwrap();
+    }
+
+    fn write_distance_estimate(&self, out: &mut String) {
+        match self.fractal {
+            Fractal3D::Mandelbulb { power } => write!(
+                out,
+                r#"
+fn distance_estimate(p: vec3<f32>) -> f32 {{
+    var z = p;
+    var dr = 1.0;
+    var r = 0.0;
+    for (var i: i32 = 0; i < {iterations}; i = i + 1) {{
+        r = length(z);
+        if (r > 2.0) {{
+            break;
+        }}
+        let theta = acos(clamp(z.z / r, -1.0, 1.0)) * {power};
+        let phi = atan2(z.y, z.x) * {power};
+        dr = pow(r, {power} - 1.0) * {power} * dr + 1.0;
+        z = pow(r, {power}) * vec3<f32>(sin(theta) * cos(phi), sin(phi) * sin(theta), cos(theta)) + p;
+    }}
+    return 0.5 * log(r) * r / dr;
+}}
+"#,
+                iterations = self.iterations,
+                power = float_literal(power),
+            ),
+            Fractal3D::Mandelbox {
+                scale,
+                min_radius,
+                fixed_radius,
+            } => write!(
+                out,
+                r#"
+fn distance_estimate(p: vec3<f32>) -> f32 {{
+    let min_radius_sqr = {min_radius_sqr};
+    let fixed_radius_sqr = {fixed_radius_sqr};
+    var z = p;
+    var dr = 1.0;
+    for (var i: i32 = 0; i < {iterations}; i = i + 1) {{
+        // Box fold.
+        z = clamp(z, vec3<f32>(-1.0, -1.0, -1.0), vec3<f32>(1.0, 1.0, 1.0)) * 2.0 - z;
+
+        // Sphere fold.
+        let r2 = dot(z, z);
+        if (r2 < min_radius_sqr) {{
+            z = z * (fixed_radius_sqr / min_radius_sqr);
+            dr = dr * (fixed_radius_sqr / min_radius_sqr);
+        }} else {{
+            if (r2 < fixed_radius_sqr) {{
+                z = z * (fixed_radius_sqr / r2);
+                dr = dr * (fixed_radius_sqr / r2);
+            }}
+        }}
+
+        z = {scale} * z + p;
+        dr = dr * {abs_scale} + 1.0;
+    }}
+    return length(z) / abs(dr);
+}}
+"#,
+                iterations = self.iterations,
+                min_radius_sqr = float_literal(min_radius * min_radius),
+                fixed_radius_sqr = float_literal(fixed_radius * fixed_radius),
+                scale = float_literal(scale),
+                abs_scale = float_literal(scale.abs()),
+            ),
+            Fractal3D::QuaternionJulia { c } => write!(
+                out,
+                r#"
+fn quaternion_multiply(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {{
+    return vec4<f32>(a.x * b.x - dot(a.yzw, b.yzw), a.x * b.yzw + b.x * a.yzw + cross(a.yzw, b.yzw));
+}}
+
+fn distance_estimate(p: vec3<f32>) -> f32 {{
+    var z = vec4<f32>(p, 0.0);
+    var dz = vec4<f32>(1.0, 0.0, 0.0, 0.0);
+    for (var i: i32 = 0; i < {iterations}; i = i + 1) {{
+        dz = 2.0 * quaternion_multiply(z, dz);
+        z = quaternion_multiply(z, z) + vec4<f32>({}, {}, {}, {});
+        if (dot(z, z) > 16.0) {{
+            break;
+        }}
+    }}
+    let r = length(z);
+    return 0.5 * r * log(r) / length(dz);
+}}
+"#,
+                float_literal(c[0]),
+                float_literal(c[1]),
+                float_literal(c[2]),
+                float_literal(c[3]),
+                iterations = self.iterations,
+            ),
+        }
+        .unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{auto_iterations::raise_limit, scene::Scene, view::View};
+
+/// Re-renders the tiles of an escape-time image where many pixels reached
+/// the iteration limit at higher limits, leaving the rest as they were.
+///
+/// The exterior colorings color a pixel by the iteration it escaped at
+/// rather than by how close that is to the limit, so a pixel that escapes
+/// under both limits gets the same color either way. Interior colorings
+/// taken from the cycle an orbit settles into don't change with the limit
+/// either, so refined tiles stitch into the image without seams. The rest
+/// would leave seams, so scenes can't use them with refinement; see
+/// [`InteriorColoring::depends_on_limit`](crate::coloring::InteriorColoring::depends_on_limit).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Refinement {
+    /// The largest width and height of the tiles the image is split into.
+    pub tile_size: usize,
+    /// Tiles where more than this fraction of sampled pixels reach the limit
+    /// are refined.
+    pub threshold: f32,
+}
+
+/// A tile to render again and the iteration limit to render it at.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RefinedTile {
+    pub tile: View,
+    pub iterations: u32,
+}
+
+/// Side length of the grid of pixels sampled from each tile.
+const SAMPLE_GRID: usize = 16;
+
+impl Refinement {
+    /// Finds the tiles of `view` that need a higher limit than the scene's,
+    /// doubling each one's limit until few enough sampled pixels reach it or
+    /// raising it stops making a difference, as it does for tiles inside the
+    /// set.
+    pub fn plan(&self, scene: &Scene, view: View) -> Vec<RefinedTile> {
+        view.subdivide_rectangles(self.tile_size, self.tile_size)
+            .filter_map(|tile| {
+                let starts = scene.orbit_starts(&tile, SAMPLE_GRID);
+                let iterations = raise_limit(scene, &starts, scene.iterations, |fraction| {
+                    fraction <= self.threshold
+                });
+                if iterations > scene.iterations {
+                    Some(RefinedTile { tile, iterations })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{refinement::Refinement, scene::Scene, view::View};
+
+    fn mandelbrot() -> Scene {
+        Scene {
+            iterations: 64,
+            ..Scene::test_mandelbrot()
+        }
+    }
+
+    #[test]
+    fn refines_boundary_tiles() {
+        let scene = mandelbrot();
+        let view = View::new_uniform(240, 160, 3.0, -0.5, 0.0);
+        let refinement = Refinement {
+            tile_size: 40,
+            threshold: 0.05,
+        };
+        let refined = refinement.plan(&scene, view);
+        assert!(!refined.is_empty());
+        assert!(refined.len() < 24, "{}", refined.len());
+
+        for refined in &refined {
+            assert!(refined.iterations > scene.iterations);
+            // Tiles far from the set never reach the limit.
+            let center = refined.tile.get_local_plane_coordinates((20, 20));
+            assert!((center + 0.5).norm() < 2.5, "{}", center);
+        }
+
+        // Tiles in the main cardioid reach every limit, so raising it
+        // doesn't change anything.
+        let cardioid = View::new_uniform(40, 40, 0.2, -0.1, 0.1);
+        assert!(refinement.plan(&scene, cardioid).is_empty());
+    }
+}
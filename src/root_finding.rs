@@ -0,0 +1,354 @@
+use crate::{
+    derivative::{NotHolomorphic, Variable},
+    formula::{Expr, Variables},
+    generator::{complex_literal, float_literal, write_frag_main_start},
+    scene::UnknownOption,
+};
+use num_complex::Complex;
+use std::{f32::consts::PI, fmt::Write, str::FromStr};
+
+/// The most roots given their own color.
+const MAX_ROOTS: usize = 32;
+
+/// Squared distance within which a converged `z` is attributed to a root.
+const ROOT_RADIUS_SQR: f32 = 1e-4;
+
+/// The iteration used to approach a root of the function.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RootMethod {
+    /// `z - g / g'`.
+    Newton,
+    /// `z - 2 g g' / (2 g'^2 - g g'')`, converging cubically.
+    Halley,
+    /// `z - g g' / (g'^2 - g g'')`, which keeps quadratic convergence at
+    /// repeated roots.
+    Schroder,
+}
+
+/// Renders the basins of attraction of a function's roots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootFinding {
+    /// The function whose roots are found, in terms of `z` and optionally `c`.
+    pub function: Expr,
+    pub derivative: Expr,
+    pub second_derivative: Expr,
+    pub method: RootMethod,
+    /// Multiplier applied to each step, 1 for the unmodified method.
+    pub relaxation: Complex<f32>,
+    /// When set, renders the Nova parameter plane: pixels are `c`, which is
+    /// added after every step, and iteration starts from this `z`.
+    pub nova_start: Option<Complex<f32>>,
+    /// Step size below which iteration counts as converged.
+    pub tolerance: f32,
+    /// The function's roots, found on the CPU, in palette order. Left empty
+    /// for the Nova parameter plane, whose orbits don't converge to them.
+    pub roots: Vec<Complex<f32>>,
+}
+
+impl RootFinding {
+    /// Differentiates the function and locates its roots.
+    pub fn new(
+        function: Expr,
+        method: RootMethod,
+        relaxation: Complex<f32>,
+        nova_start: Option<Complex<f32>>,
+        tolerance: f32,
+    ) -> Result<RootFinding, NotHolomorphic> {
+        let derivative = function.derivative(Variable::Z)?;
+        let second_derivative = derivative.derivative(Variable::Z)?;
+        let roots = match nova_start {
+            Some(_) => vec![],
+            None => find_roots(&function, &derivative, tolerance),
+        };
+
+        Ok(RootFinding {
+            function,
+            derivative,
+            second_derivative,
+            method,
+            relaxation,
+            nova_start,
+            tolerance,
+            roots,
+        })
+    }
+
+    /// Writes the function, its derivatives and the fragment stage.
+    pub fn write_shader(&self, out: &mut String) {
+        write!(
+            out,
+            r#"
+fn g(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {{
+    return {};
+}}
+
+fn g_dz(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {{
+    return {};
+}}
+
+fn g_dzdz(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {{
+    return {};
+}}
+
+fn root_step(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {{
+    let value = g(z, c);
+    let slope = g_dz(z, c);
+"#,
+            self.function.to_wgsl(),
+            self.derivative.to_wgsl(),
+            self.second_derivative.to_wgsl()
+        )
+        .unwrap();
+        out.push_str(match self.method {
+            RootMethod::Newton => "    return complex_divide(value, slope);\n}\n",
+            RootMethod::Halley => {
+                r#"    let curvature = g_dzdz(z, c);
+    let numerator = 2.0 * complex_multiply(value, slope);
+    let denominator = 2.0 * complex_sqr(slope) - complex_multiply(value, curvature);
+    return complex_divide(numerator, denominator);
+}
+"#
+            }
+            RootMethod::Schroder => {
+                r#"    let curvature = g_dzdz(z, c);
+    let numerator = complex_multiply(value, slope);
+    let denominator = complex_sqr(slope) - complex_multiply(value, curvature);
+    return complex_divide(numerator, denominator);
+}
+"#
+            }
+        });
+
+        if self.nova_start.is_none() {
+            self.write_nearest_root(out);
+        }
+
+        write_frag_main_start(out);
+        match self.nova_start {
+            Some(start) => write!(
+                out,
+                "    let c = pixel;\n    var z = {};\n",
+                complex_literal(start)
+            ),
+            None => write!(
+                out,
+                "    let c = vec2<f32>(0.0, 0.0);\n    var z = pixel;\n"
+            ),
+        }
+        .unwrap();
+        writeln!(
+            out,
+            "    let relaxation = {};",
+            complex_literal(self.relaxation)
+        )
+        .unwrap();
+        out.push_str(
+            r#"
+    var n: i32 = 0;
+    for (; n < iterations; n = n + 1) {
+"#,
+        );
+        out.push_str(if self.nova_start.is_some() {
+            "        let z_next = z - complex_multiply(relaxation, root_step(z, c)) + c;\n"
+        } else {
+            "        let z_next = z - complex_multiply(relaxation, root_step(z, c));\n"
+        });
+        write!(
+            out,
+            r#"        let change = length_sqr(z_next - z);
+        z = z_next;
+        if (change < {tolerance} * {tolerance}) {{
+            break;
+        }}
+    }}
+
+    if (n >= iterations) {{
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }}
+
+    // Points darken the longer they take to converge.
+    let brightness = 1.0 / (1.0 + f32(n) * 0.05);
+"#,
+            tolerance = float_literal(self.tolerance),
+        )
+        .unwrap();
+        match self.nova_start {
+            // The fixed points Nova orbits settle on move with `c`, so the
+            // hue follows where each orbit settled instead of a root of `g`.
+            Some(_) => out.push_str(
+                r#"    let hue = atan2(z.y, z.x) / 6.2831855 + 0.5;
+    return fromHSB(hue, 0.8, brightness, 1.0);
+}
+"#,
+            ),
+            None => write!(
+                out,
+                r#"    let root = nearest_root(z);
+    if (root < 0) {{
+        return fromHSB(0.0, 0.0, brightness, 1.0);
+    }}
+    return fromHSB(f32(root) / {root_count}, 0.8, brightness, 1.0);
+}}
+"#,
+                root_count = float_literal(self.roots.len().max(1) as f32)
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Writes `nearest_root`, which finds the index of the root a converged
+    /// `z` is within [`ROOT_RADIUS_SQR`] of, or -1.
+    fn write_nearest_root(&self, out: &mut String) {
+        out.push_str(
+            r#"
+fn nearest_root(z: vec2<f32>) -> i32 {
+    var root: i32 = -1;
+"#,
+        );
+        writeln!(
+            out,
+            "    var closest: f32 = {};",
+            float_literal(ROOT_RADIUS_SQR)
+        )
+        .unwrap();
+        for (index, &position) in self.roots.iter().enumerate() {
+            write!(
+                out,
+                r#"    let distance{index} = length_sqr(z - {position});
+    if (distance{index} < closest) {{
+        closest = distance{index};
+        root = {index};
+    }}
+"#,
+                index = index,
+                position = complex_literal(position)
+            )
+            .unwrap();
+        }
+        out.push_str("    return root;\n}\n");
+    }
+}
+
+/// Finds the roots of a function by running Newton's method from a grid of
+/// starting points.
+///
+/// Roots are ordered by angle around the origin so neighbouring roots get
+/// neighbouring hues.
+pub fn find_roots(function: &Expr, derivative: &Expr, tolerance: f32) -> Vec<Complex<f32>> {
+    const GRID_SIZE: usize = 24;
+    const GRID_EXTENT: f32 = 4.0;
+    const MAX_STEPS: usize = 200;
+
+    let mut roots: Vec<Complex<f32>> = vec![];
+    for i in 0..GRID_SIZE {
+        for j in 0..GRID_SIZE {
+            let re = -GRID_EXTENT + 2.0 * GRID_EXTENT * (i as f32 + 0.5) / GRID_SIZE as f32;
+            let im = -GRID_EXTENT + 2.0 * GRID_EXTENT * (j as f32 + 0.5) / GRID_SIZE as f32;
+            let mut vars = Variables {
+                z: Complex::new(re, im),
+                previous_z: Complex::new(0.0, 0.0),
+                c: Complex::new(0.0, 0.0),
+            };
+
+            for _ in 0..MAX_STEPS {
+                let step = function.evaluate(&vars) / derivative.evaluate(&vars);
+                if !step.is_finite() {
+                    break;
+                }
+                vars.z -= step;
+
+                if step.norm() < tolerance {
+                    let root = vars.z;
+                    let known = roots
+                        .iter()
+                        .any(|known| (known - root).norm_sqr() < ROOT_RADIUS_SQR);
+                    if !known && roots.len() < MAX_ROOTS {
+                        roots.push(root);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    let angle = |root: &Complex<f32>| (root.arg() + 2.0 * PI) % (2.0 * PI);
+    roots.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+    roots
+}
+
+impl FromStr for RootMethod {
+    type Err = UnknownOption;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newton" => Ok(RootMethod::Newton),
+            "halley" => Ok(RootMethod::Halley),
+            "schroder" => Ok(RootMethod::Schroder),
+            _ => Err(UnknownOption),
+        }
+    }
+}
+
+// Unit Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        derivative::Variable,
+        parser::parse_expr,
+        root_finding::{find_roots, RootFinding, RootMethod},
+    };
+    use num_complex::Complex;
+
+    #[test]
+    fn cube_roots_of_unity() {
+        let finding = RootFinding::new(
+            parse_expr("z^3 - 1").unwrap(),
+            RootMethod::Newton,
+            Complex::new(1.0, 0.0),
+            None,
+            1e-5,
+        )
+        .unwrap();
+
+        let expected = [
+            Complex::new(1.0, 0.0),
+            Complex::from_polar(1.0, 2.0 * std::f32::consts::PI / 3.0),
+            Complex::from_polar(1.0, 4.0 * std::f32::consts::PI / 3.0),
+        ];
+        assert_eq!(finding.roots.len(), 3);
+        for (root, expected) in finding.roots.iter().zip(expected.iter()) {
+            assert!((root - expected).norm() < 1e-3, "{} != {}", root, expected);
+        }
+    }
+
+    #[test]
+    fn nova_colors_by_attractor() {
+        let nova = RootFinding::new(
+            parse_expr("z^3 - 1").unwrap(),
+            RootMethod::Newton,
+            Complex::new(1.0, 0.0),
+            Some(Complex::new(1.0, 0.0)),
+            1e-5,
+        )
+        .unwrap();
+        assert!(nova.roots.is_empty());
+
+        let mut source = String::new();
+        nova.write_shader(&mut source);
+        assert!(!source.contains("nearest_root"));
+    }
+
+    #[test]
+    fn repeated_roots_are_merged() {
+        let function = parse_expr("(z - 1)^2 (z + 2)").unwrap();
+        let derivative = function.derivative(Variable::Z).unwrap();
+        assert_eq!(find_roots(&function, &derivative, 1e-4).len(), 2);
+    }
+
+    #[test]
+    fn parse_root_method() {
+        assert_eq!("schroder".parse(), Ok(RootMethod::Schroder));
+        assert!("secant".parse::<RootMethod>().is_err());
+    }
+}
@@ -0,0 +1,743 @@
+use crate::{
+    attractor::{Attractor, AttractorMap},
+    auto_iterations::AutoIterations,
+    buddhabrot::Buddhabrot,
+    camera::Camera,
+    coloring::{
+        AverageStatistic, CycleDetection, ExteriorColoring, ImageTrap, InteriorColoring,
+        OrbitAverage, OrbitTrap, TrapBlend, TrapColoring, TrapShape,
+    },
+    derivative::Derivatives,
+    domain_coloring::DomainColoring,
+    exponential_map::{ExponentialMap, ZoomFrames},
+    flame::{Flame, FractalFlame},
+    flame_file::{parse_flames, FlameFileError},
+    fold::{Fold, FoldFractal},
+    formula::{Family, Formula, Symmetry},
+    interior_check::InteriorChecks,
+    lyapunov::{parse_sequence, Lyapunov},
+    mariani_silver::MarianiSilver,
+    multi_pass::MultiPass,
+    palette::Palette,
+    parser::parse_expr,
+    plane_transform::{parse_chain, PlaneTransform},
+    projection::Projection,
+    ray_march::{Fractal3D, RayMarch},
+    refinement::Refinement,
+    root_finding::{RootFinding, RootMethod},
+    view::View,
+};
+use cgmath::Vector3;
+use num_complex::Complex;
+use std::{env, fs, path::PathBuf, str::FromStr, time::Duration};
+
+/// Describes what gets rendered.
+///
+/// Scenes are read from environment variables so they can be changed from a
+/// `.env` file without recompiling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub mode: RenderMode,
+    pub formula: Formula,
+    pub plane: Plane,
+    /// Transforms applied in order to each pixel's position before it is
+    /// iterated.
+    pub plane_transforms: Vec<PlaneTransform>,
+    /// How pixels are mapped onto the plane before the transforms.
+    pub projection: Projection,
+    pub exterior: ExteriorColoring,
+    pub interior: InteriorColoring,
+    /// The iteration limit of escape-time and root-finding shaders.
+    pub iterations: u32,
+    /// Replaces `iterations` with a limit chosen from the view when set.
+    pub auto_iterations: Option<AutoIterations>,
+    /// Shortcuts for interior points, used where
+    /// [`Scene::applicable_interior_checks`] allows.
+    pub interior_checks: InteriorChecks,
+    /// Renders escape-time fractals by subdividing rectangles when set.
+    pub mariani_silver: Option<MarianiSilver>,
+    /// Iterates escape-time fractals over several compute passes when set.
+    pub multi_pass: Option<MultiPass>,
+    /// Re-renders escape-time tiles that reach the iteration limit at higher
+    /// limits when set.
+    pub refinement: Option<Refinement>,
+}
+
+/// Describes which kind of fractal is rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderMode {
+    /// Iterates the scene's formula until it escapes, colored by the scene's
+    /// exterior and interior colorings.
+    EscapeTime,
+    /// Iterates a root-finding method, colored by the root each point
+    /// converges to.
+    RootFinding(RootFinding),
+    /// Plots the phase and modulus of a function.
+    DomainColoring(DomainColoring),
+    /// Accumulates the density of the formula's escaping orbits.
+    Buddhabrot(Buddhabrot),
+    /// Accumulates the density of a strange attractor.
+    Attractor(Attractor),
+    /// Plots the Lyapunov exponent of the logistic map.
+    Lyapunov(Lyapunov),
+    /// Ray marches a 3D fractal.
+    RayMarch(RayMarch),
+    /// Iterates geometric folds and inversions.
+    Fold(FoldFractal),
+    /// Accumulates the density of a fractal flame.
+    Flame(FractalFlame),
+    /// Renders the escape-time fractal as a log-polar strip for assembling
+    /// zoom videos.
+    ExponentialMap(ExponentialMap),
+}
+
+/// Describes how pixels are mapped onto the iteration.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Plane {
+    /// Pixels are `c` and iteration starts from the formula's critical point.
+    Parameter,
+    /// Pixels are the starting `z` and `c` is fixed.
+    Julia(Complex<f32>),
+}
+
+/// Error returned when parsing a setting that names an unknown option.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct UnknownOption;
+
+/// Error potentially returned when reading a scene from the environment.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SceneError {
+    InvalidValue {
+        name: &'static str,
+        value: String,
+    },
+    InvalidFlameFile(FlameFileError),
+    /// A coloring, by name, that can't be rendered with the option enabled by
+    /// `setting`.
+    UnsupportedColoring {
+        coloring: String,
+        setting: &'static str,
+    },
+}
+
+impl Scene {
+    /// Reads a scene from the environment, using defaults for any settings
+    /// that are missing.
+    pub fn from_env() -> Result<Scene, SceneError> {
+        let mode = match env_var("RENDER_MODE", String::from("escape-time"))?.as_str() {
+            "escape-time" => RenderMode::EscapeTime,
+            "root-finding" => RenderMode::RootFinding(root_finding()?),
+            "domain-coloring" => RenderMode::DomainColoring(domain_coloring()?),
+            "buddhabrot" => {
+                let limit = env_var("BUDDHABROT_ITERATIONS", 1000)?;
+                RenderMode::Buddhabrot(buddhabrot([limit; 3])?)
+            }
+            "nebulabrot" => RenderMode::Buddhabrot(buddhabrot([
+                env_var("NEBULABROT_RED_ITERATIONS", 5000)?,
+                env_var("NEBULABROT_GREEN_ITERATIONS", 500)?,
+                env_var("NEBULABROT_BLUE_ITERATIONS", 50)?,
+            ])?),
+            "attractor" => RenderMode::Attractor(attractor()?),
+            "lyapunov" => RenderMode::Lyapunov(lyapunov()?),
+            "ray-march" => RenderMode::RayMarch(ray_march()?),
+            "fold" => RenderMode::Fold(fold()?),
+            "flame" => RenderMode::Flame(flame()?),
+            "exponential-map" => RenderMode::ExponentialMap(exponential_map()?),
+            other => return Err(invalid_value("RENDER_MODE", other)),
+        };
+
+        let formula = match env_var("FORMULA", String::from("mandelbrot"))?.as_str() {
+            "hybrid" => hybrid()?,
+            name => family("FORMULA", name, None)?.formula(),
+        };
+
+        let plane = match env_var("PLANE", String::from("julia"))?.as_str() {
+            "parameter" => Plane::Parameter,
+            "julia" => Plane::Julia(env_var("JULIA_C", Complex::new(0.16611, 0.59419))?),
+            other => return Err(invalid_value("PLANE", other)),
+        };
+        let source = env_var("PLANE_TRANSFORMS", String::new())?;
+        let plane_transforms =
+            parse_chain(&source).map_err(|_| invalid_value("PLANE_TRANSFORMS", &source))?;
+
+        let projection = match env_var("PROJECTION", String::from("flat"))?.as_str() {
+            "flat" => Projection::Flat,
+            "equirectangular" => Projection::Equirectangular {
+                yaw: env_var("SPHERE_YAW", 0.0)?,
+                pitch: env_var("SPHERE_PITCH", 0.0)?,
+            },
+            "orthographic" => Projection::Orthographic {
+                yaw: env_var("SPHERE_YAW", 0.0)?,
+                pitch: env_var("SPHERE_PITCH", 0.0)?,
+            },
+            other => return Err(invalid_value("PROJECTION", other)),
+        };
+
+        let exterior = match env_var("EXTERIOR_COLORING", String::from("iteration"))?.as_str() {
+            "iteration" => ExteriorColoring::Iteration,
+            "orbit-trap" => ExteriorColoring::OrbitTrap(OrbitTrap {
+                shape: env_var("TRAP_SHAPE", TrapShape::Point)?,
+                coloring: env_var("TRAP_COLORING", TrapColoring::Distance)?,
+                position: env_var("TRAP_POSITION", Complex::new(0.0, 0.0))?,
+                angle: env_var("TRAP_ANGLE", 0.0)?,
+                radius: env_var("TRAP_RADIUS", 1.0)?,
+                scale: env_var("TRAP_SCALE", 1.0)?,
+            }),
+            "image-trap" => ExteriorColoring::ImageTrap(ImageTrap {
+                path: env_var("IMAGE_TRAP_PATH", PathBuf::from("trap.png"))?,
+                blend: env_var("IMAGE_TRAP_BLEND", TrapBlend::FirstHit)?,
+                position: env_var("IMAGE_TRAP_POSITION", Complex::new(-1.0, -1.0))?,
+                size: env_var("IMAGE_TRAP_SIZE", Complex::new(2.0, 2.0))?,
+                alpha_threshold: env_var("IMAGE_TRAP_ALPHA_THRESHOLD", 0.5)?,
+            }),
+            "stripe-average" => average(AverageStatistic::Stripe)?,
+            "triangle-inequality-average" => average(AverageStatistic::TriangleInequality)?,
+            "curvature-average" => average(AverageStatistic::Curvature)?,
+            other => return Err(invalid_value("EXTERIOR_COLORING", other)),
+        };
+
+        let interior_name = env_var("INTERIOR_COLORING", String::from("black"))?;
+        let interior = match interior_name.as_str() {
+            "black" => InteriorColoring::Black,
+            "magnitude" => InteriorColoring::Magnitude {
+                scale: env_var("INTERIOR_SCALE", 1.0)?,
+            },
+            "period" => InteriorColoring::Period(cycle_detection()?),
+            "distance-estimate" => InteriorColoring::DistanceEstimate {
+                cycle: cycle_detection()?,
+                scale: env_var("INTERIOR_SCALE", 1.0)?,
+            },
+            "atom-domain" => InteriorColoring::AtomDomain,
+            other => return Err(invalid_value("INTERIOR_COLORING", other)),
+        };
+
+        // Distance estimation needs the formula's derivatives.
+        if let InteriorColoring::DistanceEstimate { .. } = interior {
+            if formula
+                .steps
+                .iter()
+                .any(|step| Derivatives::of(step).is_err())
+            {
+                return Err(invalid_value("INTERIOR_COLORING", "distance-estimate"));
+            }
+        }
+
+        // Only escape-time views have pixels to choose a limit from.
+        let (iterations, auto_iterations) =
+            match env_var("ITERATIONS", String::from("200"))?.as_str() {
+                "auto" if mode == RenderMode::EscapeTime => (
+                    200,
+                    Some(env_var("AUTO_ITERATIONS", AutoIterations::Probe)?),
+                ),
+                value => (
+                    value
+                        .parse()
+                        .map_err(|_| invalid_value("ITERATIONS", value))?,
+                    None,
+                ),
+            };
+
+        let interior_checks = InteriorChecks {
+            cardioid: env_var("CARDIOID_CHECK", false)?,
+            periodicity: optional_env_var("PERIODICITY_EPSILON")?,
+        };
+
+        let mariani_silver = if env_var("MARIANI_SILVER", false)? {
+            Some(MarianiSilver {
+                min_size: env_var("MARIANI_SILVER_MIN_SIZE", 8)?,
+            })
+        } else {
+            None
+        };
+
+        let multi_pass = match optional_env_var("MULTI_PASS_ITERATIONS")? {
+            Some(pass_iterations) => Some(MultiPass {
+                pass_iterations,
+                time_limit: optional_env_var("MULTI_PASS_TIME_LIMIT")?.map(Duration::from_secs_f32),
+            }),
+            None => None,
+        };
+
+        let refinement = if env_var("ITERATION_REFINEMENT", false)? {
+            Some(Refinement {
+                tile_size: env_var("REFINEMENT_TILE_SIZE", 128)?,
+                threshold: env_var("REFINEMENT_THRESHOLD", 0.05)?,
+            })
+        } else {
+            None
+        };
+
+        // Refined tiles are stitched next to tiles rendered at lower limits.
+        if refinement.is_some() && interior.depends_on_limit() {
+            return Err(unsupported_coloring(&interior_name, "ITERATION_REFINEMENT"));
+        }
+
+        Ok(Scene {
+            mode,
+            formula,
+            plane,
+            plane_transforms,
+            projection,
+            exterior,
+            interior,
+            iterations,
+            auto_iterations,
+            interior_checks,
+            mariani_silver,
+            multi_pass,
+            refinement,
+        })
+    }
+
+    /// The interior checks that give the same image as iterating every point
+    /// to the limit.
+    ///
+    /// The cardioid check only holds for the Mandelbrot set's parameter
+    /// plane and skips the whole orbit, so it needs a black interior, while
+    /// periodicity checking stops on the cycle, which colorings that find the
+    /// cycle again don't mind. Its saved values aren't carried between
+    /// passes, so multi-pass rendering goes without it.
+    pub fn applicable_interior_checks(&self) -> InteriorChecks {
+        let mandelbrot = self.plane == Plane::Parameter
+            && self.formula == Family::Multibrot { power: 2.0 }.formula();
+        let on_cycle = match self.interior {
+            InteriorColoring::Black
+            | InteriorColoring::Period(_)
+            | InteriorColoring::DistanceEstimate { .. } => true,
+            InteriorColoring::Magnitude { .. } | InteriorColoring::AtomDomain => false,
+        };
+        InteriorChecks {
+            cardioid: self.interior_checks.cardioid
+                && mandelbrot
+                && self.interior == InteriorColoring::Black,
+            periodicity: self
+                .interior_checks
+                .periodicity
+                .filter(|_| on_cycle && self.multi_pass.is_none()),
+        }
+    }
+
+    /// The `z` and `c` that iteration starts from at the pixels of a `grid`
+    /// by `grid` sample of a view, leaving out pixels the projection misses.
+    pub fn orbit_starts(&self, view: &View, grid: usize) -> Vec<(Complex<f32>, Complex<f32>)> {
+        let mut starts = Vec::with_capacity(grid * grid);
+        for y in 0..grid {
+            for x in 0..grid {
+                let pixel = (x * view.image_width / grid, y * view.image_height / grid);
+                let point = match view.get_transformed_plane_coordinates(
+                    pixel,
+                    &self.projection,
+                    &self.plane_transforms,
+                ) {
+                    Some(point) => point,
+                    None => continue,
+                };
+                starts.push(match self.plane {
+                    Plane::Parameter => (self.formula.critical_point, point),
+                    Plane::Julia(c) => (point, c),
+                });
+            }
+        }
+        starts
+    }
+
+    /// The symmetries of the rendered image, which are the formula's when the
+    /// plane, projection, transforms and coloring all preserve them.
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        if self.mode != RenderMode::EscapeTime
+            || self.projection != Projection::Flat
+            || self.exterior != ExteriorColoring::Iteration
+        {
+            return vec![];
+        }
+
+        let critical_point = self.formula.critical_point;
+        let (starts_real, rotation) = match self.plane {
+            Plane::Parameter if critical_point == Complex::new(0.0, 0.0) => {
+                (true, self.formula.rotation())
+            }
+            Plane::Parameter => (critical_point.im == 0.0, 1),
+            Plane::Julia(c) => (c.im == 0.0, self.formula.julia_rotation),
+        };
+        let mut symmetries = vec![];
+        if starts_real
+            && self.formula.symmetries.contains(&Symmetry::RealAxis)
+            && self
+                .plane_transforms
+                .iter()
+                .all(PlaneTransform::preserves_conjugation)
+        {
+            symmetries.push(Symmetry::RealAxis);
+        }
+
+        // Transforms can only keep half turns.
+        let rotation = if self.plane_transforms.is_empty() {
+            rotation
+        } else if rotation % 2 == 0
+            && self
+                .plane_transforms
+                .iter()
+                .all(PlaneTransform::preserves_negation)
+        {
+            2
+        } else {
+            1
+        };
+        if rotation >= 2 {
+            symmetries.push(Symmetry::Rotational(rotation));
+        }
+        symmetries
+    }
+
+    /// The Mandelbrot set's parameter plane at 200 iterations, with a black
+    /// interior and none of the rendering options, for tests to adjust.
+    #[cfg(test)]
+    pub fn test_mandelbrot() -> Scene {
+        Scene {
+            mode: RenderMode::EscapeTime,
+            formula: Family::Multibrot { power: 2.0 }.formula(),
+            plane: Plane::Parameter,
+            plane_transforms: vec![],
+            projection: Projection::Flat,
+            exterior: ExteriorColoring::Iteration,
+            interior: InteriorColoring::Black,
+            iterations: 200,
+            auto_iterations: None,
+            interior_checks: InteriorChecks::NONE,
+            mariani_silver: None,
+            multi_pass: None,
+            refinement: None,
+        }
+    }
+}
+
+/// Reads a built-in formula by name from the `setting` variable. Its
+/// parameter, if it has one, is `parameter` when given and otherwise read
+/// from the environment.
+fn family(
+    setting: &'static str,
+    name: &str,
+    parameter: Option<&str>,
+) -> Result<Family, SceneError> {
+    fn parameter_or_env<T: FromStr>(
+        setting: &'static str,
+        parameter: Option<&str>,
+        env_name: &'static str,
+        default: T,
+    ) -> Result<T, SceneError> {
+        match parameter {
+            Some(value) => value.parse().map_err(|_| invalid_value(setting, value)),
+            None => env_var(env_name, default),
+        }
+    }
+
+    Ok(match name {
+        "mandelbrot" => Family::Multibrot { power: 2.0 },
+        "multibrot" => Family::Multibrot {
+            power: parameter_or_env(setting, parameter, "FORMULA_POWER", 3.0)?,
+        },
+        "complex-multibrot" => Family::ComplexMultibrot {
+            power: parameter_or_env(setting, parameter, "FORMULA_POWER", Complex::new(2.0, 0.5))?,
+        },
+        "burning-ship" => Family::BurningShip,
+        "tricorn" | "mandelbar" => Family::Tricorn,
+        "celtic" => Family::Celtic,
+        "buffalo" => Family::Buffalo,
+        "perpendicular-mandelbrot" => Family::PerpendicularMandelbrot,
+        "perpendicular-burning-ship" => Family::PerpendicularBurningShip,
+        "perpendicular-celtic" => Family::PerpendicularCeltic,
+        "perpendicular-buffalo" => Family::PerpendicularBuffalo,
+        "phoenix" => Family::Phoenix {
+            p: parameter_or_env(setting, parameter, "PHOENIX_P", Complex::new(-0.5, 0.0))?,
+        },
+        "magnet-1" => Family::MagnetI,
+        "magnet-2" => Family::MagnetII,
+        other => return Err(invalid_value(setting, other)),
+    })
+}
+
+/// Reads a hybrid formula from a list of built-in formulas applied in turn,
+/// such as `[mandelbrot, burning-ship, burning-ship]`. Parameters can be
+/// given in parentheses, as in `multibrot(3)` or `phoenix(-0.5+0.1i)`.
+fn hybrid() -> Result<Formula, SceneError> {
+    let source: String = env_var(
+        "HYBRID_FORMULAS",
+        String::from("[mandelbrot, burning-ship, burning-ship]"),
+    )?;
+    let list = source.trim().trim_start_matches('[').trim_end_matches(']');
+    if list.trim().is_empty() {
+        return Err(invalid_value("HYBRID_FORMULAS", &source));
+    }
+
+    let mut formulas = vec![];
+    for entry in list.split(',') {
+        let entry = entry.trim().replace('_', "-");
+        let (name, parameter) = match entry.find('(') {
+            Some(index) if entry.ends_with(')') => (
+                &entry[..index],
+                Some(entry[index + 1..entry.len() - 1].trim()),
+            ),
+            _ => (entry.as_str(), None),
+        };
+        formulas.push(family("HYBRID_FORMULAS", name.trim(), parameter)?.formula());
+    }
+    Ok(Formula::hybrid(&formulas))
+}
+
+/// Reads the function and method of a root-finding fractal.
+fn root_finding() -> Result<RootFinding, SceneError> {
+    let source = env_var("ROOT_FUNCTION", String::from("z^3 - 1"))?;
+    let function = parse_expr(&source).map_err(|_| invalid_value("ROOT_FUNCTION", &source))?;
+    RootFinding::new(
+        function,
+        env_var("ROOT_METHOD", RootMethod::Newton)?,
+        env_var("ROOT_RELAXATION", Complex::new(1.0, 0.0))?,
+        optional_env_var("NOVA_START")?,
+        env_var("ROOT_TOLERANCE", 1e-4)?,
+    )
+    .map_err(|_| invalid_value("ROOT_FUNCTION", &source))
+}
+
+/// Reads the function plotted by domain coloring.
+fn domain_coloring() -> Result<DomainColoring, SceneError> {
+    let source = env_var(
+        "DOMAIN_FUNCTION",
+        String::from("(z^2 - 1)(z - 2 - i)^2 / (z^2 + 2 + 2i)"),
+    )?;
+
+    Ok(DomainColoring {
+        function: parse_expr(&source).map_err(|_| invalid_value("DOMAIN_FUNCTION", &source))?,
+        contour_strength: env_var("DOMAIN_CONTOUR_STRENGTH", 0.3)?,
+        grid_spacing: optional_env_var("DOMAIN_GRID_SPACING")?,
+    })
+}
+
+/// Reads the sampling parameters of a Buddhabrot.
+fn buddhabrot(iteration_limits: [u32; 3]) -> Result<Buddhabrot, SceneError> {
+    Ok(Buddhabrot {
+        iteration_limits,
+        samples: env_var("BUDDHABROT_SAMPLES", 100_000_000)?,
+        sample_start: env_var("BUDDHABROT_SAMPLE_START", Complex::new(-2.0, -2.0))?,
+        sample_size: env_var("BUDDHABROT_SAMPLE_SIZE", Complex::new(4.0, 4.0))?,
+    })
+}
+
+/// Reads the map and parameters of a strange attractor.
+fn attractor() -> Result<Attractor, SceneError> {
+    let map = env_var("ATTRACTOR_MAP", AttractorMap::Clifford)?;
+    let [a, b, c, d] = map.default_parameters();
+
+    Ok(Attractor {
+        map,
+        parameters: [
+            env_var("ATTRACTOR_A", a)?,
+            env_var("ATTRACTOR_B", b)?,
+            env_var("ATTRACTOR_C", c)?,
+            env_var("ATTRACTOR_D", d)?,
+        ],
+        points: env_var("ATTRACTOR_POINTS", 200_000_000)?,
+        palette: env_var("ATTRACTOR_PALETTE", Palette::Fire)?,
+    })
+}
+
+/// Reads the rate sequence and coloring of a Lyapunov fractal.
+fn lyapunov() -> Result<Lyapunov, SceneError> {
+    let source = env_var("LYAPUNOV_SEQUENCE", String::from("AABAB"))?;
+
+    Ok(Lyapunov {
+        sequence: parse_sequence(&source)
+            .map_err(|_| invalid_value("LYAPUNOV_SEQUENCE", &source))?,
+        iterations: env_var("LYAPUNOV_ITERATIONS", 200)?,
+        settle_iterations: env_var("LYAPUNOV_SETTLE_ITERATIONS", 50)?,
+        stable_palette: env_var("LYAPUNOV_STABLE_PALETTE", Palette::Fire)?,
+        chaotic_palette: env_var("LYAPUNOV_CHAOTIC_PALETTE", Palette::Ice)?,
+    })
+}
+
+/// Reads the fractal, camera and lighting of a 3D render.
+fn ray_march() -> Result<RayMarch, SceneError> {
+    let fractal = match env_var("FRACTAL_3D", String::from("mandelbulb"))?.as_str() {
+        "mandelbulb" => Fractal3D::Mandelbulb {
+            power: env_var("MANDELBULB_POWER", 8.0)?,
+        },
+        "mandelbox" => {
+            let (scale, min_radius, fixed_radius) = mandelbox()?;
+            Fractal3D::Mandelbox {
+                scale,
+                min_radius,
+                fixed_radius,
+            }
+        }
+        "quaternion-julia" => Fractal3D::QuaternionJulia {
+            c: [
+                env_var("QUATERNION_JULIA_R", -0.125)?,
+                env_var("QUATERNION_JULIA_I", -0.256)?,
+                env_var("QUATERNION_JULIA_J", 0.847)?,
+                env_var("QUATERNION_JULIA_K", 0.0895)?,
+            ],
+        },
+        other => return Err(invalid_value("FRACTAL_3D", other)),
+    };
+
+    Ok(RayMarch {
+        fractal,
+        camera: Camera {
+            position: Vector3::new(
+                env_var("CAMERA_X", 0.0)?,
+                env_var("CAMERA_Y", 0.0)?,
+                env_var("CAMERA_Z", -2.5)?,
+            ),
+            yaw: env_var("CAMERA_YAW", 0.0)?,
+            pitch: env_var("CAMERA_PITCH", 0.0)?,
+            field_of_view: env_var("CAMERA_FIELD_OF_VIEW", 60.0)?,
+        },
+        iterations: env_var("RAY_MARCH_ITERATIONS", 12)?,
+        max_steps: env_var("RAY_MARCH_MAX_STEPS", 256)?,
+        shadow_softness: env_var("SHADOW_SOFTNESS", 16.0)?,
+        occlusion_strength: env_var("AMBIENT_OCCLUSION_STRENGTH", 1.0)?,
+        fog_density: env_var("FOG_DENSITY", 0.05)?,
+    })
+}
+
+/// Reads the transforms of a 2D fold fractal.
+fn fold() -> Result<FoldFractal, SceneError> {
+    let fold = match env_var("FOLD_FRACTAL", String::from("mandelbox"))?.as_str() {
+        "mandelbox" => {
+            let (scale, min_radius, fixed_radius) = mandelbox()?;
+            Fold::Mandelbox {
+                scale,
+                min_radius,
+                fixed_radius,
+            }
+        }
+        "kifs" => Fold::Kifs {
+            scale: env_var("KIFS_SCALE", 2.0)?,
+            angle: env_var("KIFS_ANGLE", 0.0)?,
+            offset: env_var("KIFS_OFFSET", Complex::new(1.0, 1.0))?,
+        },
+        "apollonian" => Fold::Apollonian {
+            scale: env_var("APOLLONIAN_SCALE", 1.0)?,
+        },
+        "kleinian" => Fold::Kleinian {
+            trace: env_var("KLEINIAN_TRACE", Complex::new(1.8462756, 0.09627581))?,
+        },
+        other => return Err(invalid_value("FOLD_FRACTAL", other)),
+    };
+
+    Ok(FoldFractal {
+        fold,
+        iterations: env_var("FOLD_ITERATIONS", 30)?,
+    })
+}
+
+/// Reads a flame from the `.flame` file at `FLAME_PATH`, or uses the
+/// Sierpinski triangle if there is none.
+fn flame() -> Result<FractalFlame, SceneError> {
+    let flame = match optional_env_var::<PathBuf>("FLAME_PATH")? {
+        Some(path) => {
+            let source = fs::read_to_string(&path)
+                .map_err(|_| invalid_value("FLAME_PATH", &path.to_string_lossy()))?;
+            let mut flames = parse_flames(&source).map_err(SceneError::InvalidFlameFile)?;
+            let index: usize = env_var("FLAME_INDEX", 0)?;
+            if index >= flames.len() {
+                return Err(invalid_value("FLAME_INDEX", &index.to_string()));
+            }
+            flames.swap_remove(index)
+        }
+        None => Flame::sierpinski(),
+    };
+
+    Ok(FractalFlame {
+        flame,
+        points: env_var("FLAME_POINTS", 200_000_000)?,
+    })
+}
+
+/// Reads the zoom rendered by an exponential map, and the frame rate of the
+/// video assembled from it if there should be one.
+fn exponential_map() -> Result<ExponentialMap, SceneError> {
+    let frames = match optional_env_var("EXPONENTIAL_MAP_FRAME_RATE")? {
+        Some(frame_rate) => Some(ZoomFrames {
+            frame_rate,
+            duration: env_var("EXPONENTIAL_MAP_DURATION", 10.0)?,
+            path: env_var("EXPONENTIAL_MAP_FRAMES_PATH", PathBuf::from("frames"))?,
+        }),
+        None => None,
+    };
+
+    let zoom: f64 = env_var("EXPONENTIAL_MAP_ZOOM", 100000.0)?;
+    if !zoom.is_finite() || zoom <= 1.0 {
+        return Err(invalid_value("EXPONENTIAL_MAP_ZOOM", &zoom.to_string()));
+    }
+
+    Ok(ExponentialMap {
+        center: env_var(
+            "EXPONENTIAL_MAP_CENTER",
+            Complex::new(-0.7435669, 0.1314023),
+        )?,
+        radius: env_var("EXPONENTIAL_MAP_RADIUS", 2.0)?,
+        zoom,
+        frames,
+    })
+}
+
+/// Reads the scale, minimum radius and fixed radius shared by 2D and 3D
+/// Mandelboxes.
+fn mandelbox() -> Result<(f32, f32, f32), SceneError> {
+    Ok((
+        env_var("MANDELBOX_SCALE", -1.5)?,
+        env_var("MANDELBOX_MIN_RADIUS", 0.5)?,
+        env_var("MANDELBOX_FIXED_RADIUS", 1.0)?,
+    ))
+}
+
+/// Reads the parameters of an averaging coloring.
+fn average(statistic: AverageStatistic) -> Result<ExteriorColoring, SceneError> {
+    Ok(ExteriorColoring::Average(OrbitAverage {
+        statistic,
+        stripe_density: env_var("STRIPE_DENSITY", 5.0)?,
+        skip_iterations: env_var("AVERAGE_SKIP_ITERATIONS", 1)?,
+    }))
+}
+
+/// Reads the parameters used to find interior points' attracting cycles.
+fn cycle_detection() -> Result<CycleDetection, SceneError> {
+    Ok(CycleDetection {
+        epsilon: env_var("PERIOD_EPSILON", 0.0001)?,
+        max_period: env_var("MAX_PERIOD", 64)?,
+    })
+}
+
+/// Reads and parses an environment variable, falling back to `default` if it
+/// is not set.
+fn env_var<T: FromStr>(name: &'static str, default: T) -> Result<T, SceneError> {
+    match env::var(name) {
+        Ok(value) => value.parse().map_err(|_| invalid_value(name, &value)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Reads and parses an environment variable that has no default.
+fn optional_env_var<T: FromStr>(name: &'static str) -> Result<Option<T>, SceneError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| invalid_value(name, &value)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn invalid_value(name: &'static str, value: &str) -> SceneError {
+    SceneError::InvalidValue {
+        name,
+        value: value.to_string(),
+    }
+}
+
+fn unsupported_coloring(coloring: &str, setting: &'static str) -> SceneError {
+    SceneError::UnsupportedColoring {
+        coloring: coloring.to_string(),
+        setting,
+    }
+}
@@ -0,0 +1,31 @@
+use std::{fmt::Display, time::Instant};
+
+/// Facts about a render, logged together once it has finished.
+pub struct RenderSummary {
+    start: Instant,
+    entries: Vec<(&'static str, String)>,
+}
+
+impl RenderSummary {
+    /// Starts timing a render.
+    pub fn start() -> RenderSummary {
+        RenderSummary {
+            start: Instant::now(),
+            entries: vec![],
+        }
+    }
+
+    /// Adds a named fact to the summary.
+    pub fn add(&mut self, name: &'static str, value: impl Display) {
+        self.entries.push((name, value.to_string()));
+    }
+
+    /// Logs the time since the render started followed by every fact.
+    pub fn log(&self) {
+        info!("Render summary:");
+        info!("    Render time: {:.2?}", self.start.elapsed());
+        for (name, value) in &self.entries {
+            info!("    {}: {}", name, value);
+        }
+    }
+}
@@ -6,14 +6,70 @@ struct View {
     image_size: vec2<f32>;
     image_scale: vec2<f32>;
     plane_start: vec2<f32>;
+    padding: vec2<f32>;
+};
+
+struct OrbitTrap {
+    position: vec2<f32>;
+    direction: vec2<f32>;
+    radius: f32;
+    scale: f32;
+    padding: vec2<f32>;
+};
+
+struct ImageTrap {
+    position: vec2<f32>;
+    size: vec2<f32>;
+    alpha_threshold: f32;
+    padding0: f32;
+    padding1: vec2<f32>;
+};
+
+struct Average {
+    stripe_density: f32;
+    skip_iterations: i32;
+    padding: vec2<f32>;
+};
+
+struct Interior {
+    epsilon: f32;
+    max_period: i32;
+    scale: f32;
+    padding: f32;
+};
+
+struct Sampling {
+    sample_start: vec2<f32>;
+    sample_size: vec2<f32>;
+    seed: u32;
+    padding0: u32;
+    padding1: vec2<u32>;
+};
+
+struct Attractor {
+    parameters: vec4<f32>;
+};
+
+struct Camera {
+    position: vec4<f32>;
+    right: vec4<f32>;
+    up: vec4<f32>;
+    forward: vec4<f32>;
 };
 
 [[block]]
 struct Uniforms {
     view: View;
+    trap: OrbitTrap;
+    image_trap: ImageTrap;
+    average: Average;
+    interior: Interior;
+    sampling: Sampling;
+    attractor: Attractor;
+    camera: Camera;
 };
 
-var indexable: array<vec2<f32>,6u> = array<vec2<f32>,6u>(
+var<private> indexable: array<vec2<f32>,6u> = array<vec2<f32>,6u>(
     vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0),
     vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0), vec2<f32>(-1.0, -1.0)
 );
@@ -22,8 +78,6 @@ var indexable: array<vec2<f32>,6u> = array<vec2<f32>,6u>(
 var<uniform> uniforms: Uniforms;
 
 let offset: vec2<f32> = vec2<f32>(-0.5, -0.5);
-let iterations: i32 = 200;
-let c: vec2<f32> = vec2<f32>(0.16611, 0.59419);
 
 [[stage(vertex)]]
 fn vert_main([[builtin(vertex_index)]] vert_index: u32) -> FragmentData {
@@ -33,18 +87,6 @@ fn vert_main([[builtin(vertex_index)]] vert_index: u32) -> FragmentData {
     return data;
 }
 
-fn complex_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
-    return a + b;
-}
-
-fn complex_multiply(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
-    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
-}
-
-fn complex_sqr(a: vec2<f32>) -> vec2<f32> {
-    return vec2<f32>(a.x * a.x - a.y * a.y, 2.0 * a.x * a.y);
-}
-
 fn length_sqr(a: vec2<f32>) -> f32 {
     return a.x * a.x + a.y * a.y;
 }
@@ -83,33 +125,3 @@ fn fromHSB(hue: f32, saturation: f32, brightness: f32, alpha: f32) -> vec4<f32>
         }
     }
 }
-
-fn f(z: vec2<f32>, c: vec2<f32>) -> vec2<f32> {
-    return complex_add(complex_sqr(z), c);
-}
-
-[[stage(fragment)]]
-fn frag_main(data: FragmentData) -> [[location(0)]] vec4<f32> {
-    // Only generate fractals for the requested area.
-    if (data.position.x >= uniforms.view.image_size.x || data.position.y >= uniforms.view.image_size.y) {
-        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
-    }
-
-    var z = uniforms.view.plane_start + (data.position.xy + offset) * uniforms.view.image_scale;
-
-    var n: i32 = 0;
-    for (; n < iterations; n = n + 1) {
-        if (length_sqr(z) > 16.0) {
-            break;
-        }
-
-        z = f(z, c);
-    }
-
-    if (n >= iterations) {
-        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
-    } else {
-        let v = f32(n);
-        return fromHSB((v * 3.3 / 256.0) % 1.0, 1.0, (v / 16.0) % 1.0, 1.0);
-    }
-}
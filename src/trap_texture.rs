@@ -0,0 +1,130 @@
+use crate::{coloring::ExteriorColoring, gpu::Gpu, scene::Scene};
+use core::num::NonZeroU32;
+use std::path::Path;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Device,
+    Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout, Origin3d, Queue, SamplerDescriptor,
+    ShaderStage, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsage, TextureViewDimension,
+};
+
+/// The image sampled by image orbit traps, bound next to the uniforms.
+pub struct TrapTexture {
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl TrapTexture {
+    /// Loads the texture of the scene's image trap, if it colors with one.
+    pub fn for_scene(gpu: &Gpu, scene: &Scene) -> Option<TrapTexture> {
+        match &scene.exterior {
+            ExteriorColoring::ImageTrap(trap) => {
+                info!("Loading trap texture...");
+                Some(TrapTexture::load(&gpu.device, &gpu.queue, &trap.path))
+            }
+            _ => None,
+        }
+    }
+
+    /// Loads an image from disk and uploads it as the trap texture.
+    pub fn load(device: &Device, queue: &Queue, path: &Path) -> TrapTexture {
+        let image = image::open(path).unwrap().to_rgba8();
+        let (width, height) = image.dimensions();
+
+        TrapTexture::from_rgba(device, queue, width, height, image.as_raw())
+    }
+
+    /// Uploads RGBA8 pixel data as the trap texture.
+    pub fn from_rgba(
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> TrapTexture {
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Trap Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
+        });
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            data,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * width),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            size,
+        );
+        let texture_view = texture.create_view(&Default::default());
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Trap Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Trap texture bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStage::FRAGMENT | ShaderStage::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStage::FRAGMENT | ShaderStage::COMPUTE,
+                    ty: BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Trap texture bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        TrapTexture {
+            bind_group_layout,
+            bind_group,
+        }
+    }
+}
@@ -1,9 +1,45 @@
-use crate::gpu_view::GPUView;
+use crate::{
+    gpu_attractor::GPUAttractor,
+    gpu_camera::GPUCamera,
+    gpu_coloring::{GPUAverage, GPUImageTrap, GPUInterior, GPUOrbitTrap},
+    gpu_sampling::GPUSampling,
+    gpu_view::GPUView,
+    scene::Scene,
+    view::View,
+};
 use bytemuck::{Pod, Zeroable};
 
+/// Everything the shaders read from the uniform buffer, in the order
+/// `template.wgsl` declares its `Uniforms` struct.
+///
+/// WGSL aligns structs in a uniform buffer to 16 bytes, so each member whose
+/// fields don't add up to a multiple of 16 bytes ends with `_padding`.
+#[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Uniforms {
     pub view: GPUView,
+    pub trap: GPUOrbitTrap,
+    pub image_trap: GPUImageTrap,
+    pub average: GPUAverage,
+    pub interior: GPUInterior,
+    pub sampling: GPUSampling,
+    pub attractor: GPUAttractor,
+    pub camera: GPUCamera,
+}
+
+impl Uniforms {
+    pub fn new(view: View, scene: &Scene) -> Uniforms {
+        Uniforms {
+            view: GPUView::from_view(view),
+            trap: GPUOrbitTrap::from_coloring(&scene.exterior),
+            image_trap: GPUImageTrap::from_coloring(&scene.exterior),
+            average: GPUAverage::from_coloring(&scene.exterior),
+            interior: GPUInterior::from_coloring(&scene.interior),
+            sampling: GPUSampling::from_scene(scene),
+            attractor: GPUAttractor::from_scene(scene),
+            camera: GPUCamera::from_scene(scene),
+        }
+    }
 }
 
 unsafe impl Zeroable for Uniforms {}
@@ -1,5 +1,10 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, mem::size_of};
 
+use crate::{
+    plane_transform::{apply_chain, PlaneTransform},
+    projection::Projection,
+    util::copy_region,
+};
 use num_complex::Complex;
 
 /// A view represents an image's width, height, and mapping onto the complex
@@ -26,6 +31,33 @@ pub enum ConstrainedValue<T> {
     GreaterThanConstraint,
 }
 
+/// The rows of a view that are mirror images of other rows about the real
+/// axis, so only the rest of the view needs rendering.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RealAxisMirror {
+    /// The contiguous part of the view that still has to be rendered.
+    pub rendered: View,
+    /// The sum of the indices of every pair of mirrored rows, twice the row
+    /// the axis lies on.
+    pub row_sum: usize,
+}
+
+/// The pixels of a view that are point reflections of other pixels through 0,
+/// so only the rest of the view needs rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointReflection {
+    /// The parts of the view that still have to be rendered: the rows on one
+    /// side of 0, and any pixels on the other side whose reflections fall
+    /// outside the view.
+    pub rendered: Vec<View>,
+    /// The part of the view that is filled in by reflection.
+    pub reflected: View,
+    /// The sum of the indices of every pair of reflected rows.
+    pub row_sum: usize,
+    /// The sum of the indices of every pair of reflected columns.
+    pub column_sum: usize,
+}
+
 impl View {
     /// Creates a view centered at (0 + 0i) on the complex plane with the same
     /// scaling for both x and y axis.
@@ -85,6 +117,83 @@ impl View {
         SubViewIter::new_rectangles(*self, max_width, max_height)
     }
 
+    /// Finds the rows of this view that mirror other rows about the real axis.
+    ///
+    /// Rows only mirror exactly when the axis lies on a pixel center or
+    /// between two rows, so other views return `None`, as do views the axis
+    /// doesn't cross.
+    pub fn real_axis_mirror(&self) -> Option<RealAxisMirror> {
+        if self.image_height == 0 {
+            return None;
+        }
+        let row_sum = reflection_sum(self.plane_start_y, self.image_scale_y)?;
+        let (start, end) = unreflected_rows(row_sum, self.image_height);
+        if end - start == self.image_height {
+            return None;
+        }
+
+        Some(RealAxisMirror {
+            rendered: self.region(0, start, self.image_width, end - start),
+            row_sum,
+        })
+    }
+
+    /// Finds the pixels of this view that are point reflections of other
+    /// pixels through 0, which a half turn about 0 swaps.
+    ///
+    /// As with [`View::real_axis_mirror`], 0 has to lie on a pixel center or
+    /// between two pixels along both axes, and views with no reflected
+    /// pixels return `None`.
+    pub fn point_reflection(&self) -> Option<PointReflection> {
+        if self.image_width == 0 || self.image_height == 0 {
+            return None;
+        }
+        let row_sum = reflection_sum(self.plane_start_y, self.image_scale_y)?;
+        let column_sum = reflection_sum(self.plane_start_x, self.image_scale_x)?;
+
+        // Skipped rows are reflected across the columns whose reflections are
+        // in the view, which reach at least one of its edges.
+        let (start, end) = unreflected_rows(row_sum, self.image_height);
+        let left = column_sum.saturating_sub(self.image_width - 1);
+        let right = (column_sum + 1).min(self.image_width);
+        if end - start == self.image_height || left >= right {
+            return None;
+        }
+        let (skipped_y, skipped_height) = if start > 0 {
+            (0, start)
+        } else {
+            (end, self.image_height - end)
+        };
+
+        let mut rendered = vec![self.region(0, start, self.image_width, end - start)];
+        if left > 0 {
+            rendered.push(self.region(0, skipped_y, left, skipped_height));
+        }
+        if right < self.image_width {
+            rendered.push(self.region(right, skipped_y, self.image_width - right, skipped_height));
+        }
+        Some(PointReflection {
+            rendered,
+            reflected: self.region(left, skipped_y, right - left, skipped_height),
+            row_sum,
+            column_sum,
+        })
+    }
+
+    /// The part of this view `width` by `height` pixels in size, starting `x`
+    /// and `y` pixels into it.
+    fn region(&self, x: usize, y: usize, width: usize, height: usize) -> View {
+        View {
+            image_width: width,
+            image_height: height,
+            image_x: self.image_x + x,
+            image_y: self.image_y + y,
+            plane_start_x: self.plane_start_x + x as f32 * self.image_scale_x,
+            plane_start_y: self.plane_start_y + y as f32 * self.image_scale_y,
+            ..*self
+        }
+    }
+
     /// Gets the coordinates on the complex plane for a given local pixel
     /// coordinate.
     pub fn get_local_plane_coordinates(&self, (x, y): (usize, usize)) -> Complex<f32> {
@@ -94,6 +203,20 @@ impl View {
         )
     }
 
+    /// Gets the point a given local pixel coordinate is iterated from once it
+    /// has been projected and a chain of plane transforms has been applied,
+    /// matching the shader. Returns `None` for pixels the projection misses.
+    pub fn get_transformed_plane_coordinates(
+        &self,
+        pixel: (usize, usize),
+        projection: &Projection,
+        transforms: &[PlaneTransform],
+    ) -> Option<Complex<f32>> {
+        projection
+            .project(self.get_local_plane_coordinates(pixel))
+            .map(|point| apply_chain(transforms, point))
+    }
+
     /// Gets the local pixel coordinates for a given coordinate on the complex
     /// plane.
     pub fn get_local_pixel_coordinates(
@@ -149,6 +272,123 @@ impl View {
     }
 }
 
+impl RealAxisMirror {
+    /// Fills in the rows that weren't rendered by copying their mirror images
+    /// from the rendered rows of an RGBA8 image of the whole view.
+    pub fn mirror_rows(&self, data: &mut [u8], view: &View) {
+        let start = self.rendered.image_y - view.image_y;
+        let end = start + self.rendered.image_height;
+        let row_size = view.image_width * size_of::<u32>();
+
+        // The skipped rows are all on one side of the rendered ones.
+        if start > 0 {
+            let (skipped, rendered) = data.split_at_mut(start * row_size);
+            for row in 0..start {
+                let source = self.row_sum - row - start;
+                copy_region(
+                    rendered,
+                    view.image_width,
+                    0,
+                    source,
+                    skipped,
+                    view.image_width,
+                    0,
+                    row,
+                    view.image_width,
+                    1,
+                );
+            }
+        } else {
+            let (rendered, skipped) = data.split_at_mut(end * row_size);
+            for row in end..view.image_height {
+                copy_region(
+                    rendered,
+                    view.image_width,
+                    0,
+                    self.row_sum - row,
+                    skipped,
+                    view.image_width,
+                    0,
+                    row - end,
+                    view.image_width,
+                    1,
+                );
+            }
+        }
+    }
+}
+
+impl PointReflection {
+    /// Fills in the pixels that weren't rendered by copying their reflections
+    /// from the rendered pixels of an RGBA8 image of the whole view.
+    pub fn reflect_pixels(&self, data: &mut [u8], view: &View) {
+        let x = self.reflected.image_x - view.image_x;
+        let y = self.reflected.image_y - view.image_y;
+        let width = self.reflected.image_width;
+        let height = self.reflected.image_height;
+        let row_size = view.image_width * size_of::<u32>();
+
+        // The reflected rows are all on one side of the rendered ones.
+        for row in y..y + height {
+            let source_row = self.row_sum - row;
+            let (source, source_y, dest, dest_y) = if y == 0 {
+                let (dest, source) = data.split_at_mut(height * row_size);
+                (source, source_row - height, dest, row)
+            } else {
+                let (source, dest) = data.split_at_mut(y * row_size);
+                (source, source_row, dest, row - y)
+            };
+            copy_region(
+                source,
+                view.image_width,
+                self.column_sum + 1 - x - width,
+                source_y,
+                dest,
+                view.image_width,
+                x,
+                dest_y,
+                width,
+                1,
+            );
+
+            // Reverse the copied pixels, keeping each pixel's channels in
+            // order.
+            let start = (dest_y * view.image_width + x) * size_of::<u32>();
+            let pixels = &mut dest[start..start + width * size_of::<u32>()];
+            pixels.reverse();
+            for pixel in pixels.chunks_exact_mut(size_of::<u32>()) {
+                pixel.reverse();
+            }
+        }
+    }
+}
+
+/// Twice the index of the pixel 0 lies on along one axis of a view, or `None`
+/// if 0 is off the pixel grid or before the first pixel.
+fn reflection_sum(plane_start: f32, image_scale: f32) -> Option<usize> {
+    if image_scale <= 0.0 {
+        return None;
+    }
+    let sum = -2.0 * plane_start / image_scale;
+    if sum < 0.0 || (sum - sum.round()).abs() > 1e-3 {
+        return None;
+    }
+    Some(sum.round() as usize)
+}
+
+/// The rows of a view `height` rows tall left to render when rows whose
+/// indices add up to `row_sum` are reflections of each other.
+///
+/// Whichever side of the reflection reaches the edge of the view is skipped,
+/// so the rendered rows stay contiguous.
+fn unreflected_rows(row_sum: usize, height: usize) -> (usize, usize) {
+    if row_sum >= height - 1 {
+        (0, (row_sum / 2 + 1).min(height))
+    } else {
+        (row_sum / 2 + row_sum % 2, height)
+    }
+}
+
 /// Special ordering for Views that ignores view size and only considers initial
 /// view position.
 impl PartialOrd for View {
@@ -407,7 +647,8 @@ impl ExactSizeIterator for SubViewIter {}
 
 #[cfg(test)]
 mod tests {
-    use crate::view::View;
+    use crate::{plane_transform::PlaneTransform, projection::Projection, view::View};
+    use num_complex::Complex;
 
     #[test]
     fn is_directly_after_divided_height() {
@@ -710,4 +951,164 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn transformed_plane_coordinates() {
+        let view = View::new_centered_uniform(10, 10, 4.0);
+
+        assert_eq!(
+            view.get_transformed_plane_coordinates((1, 9), &Projection::Flat, &[]),
+            Some(view.get_local_plane_coordinates((1, 9)))
+        );
+        assert_eq!(
+            view.get_transformed_plane_coordinates(
+                (0, 5),
+                &Projection::Flat,
+                &[PlaneTransform::Inversion]
+            ),
+            Some(Complex::new(-0.5, 0.0))
+        );
+
+        // Pixels outside the sphere's disc have no point.
+        let projection = Projection::Orthographic {
+            yaw: 0.0,
+            pitch: 0.0,
+        };
+        assert_eq!(
+            view.get_transformed_plane_coordinates((0, 0), &projection, &[]),
+            None
+        );
+        assert_eq!(
+            view.get_transformed_plane_coordinates((5, 5), &projection, &[]),
+            Some(Complex::new(0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn real_axis_mirror_below_axis() {
+        // Row 5 is on the axis, so rows 6 to 9 mirror rows 4 to 1.
+        let view = View::new_centered_uniform(3, 10, 3.0);
+        let mirror = view.real_axis_mirror().unwrap();
+        assert_eq!(mirror.row_sum, 10);
+        assert_eq!(mirror.rendered.image_y, 0);
+        assert_eq!(mirror.rendered.image_height, 6);
+        assert_eq!(mirror.rendered.plane_start_y, view.plane_start_y);
+
+        // Each row is filled with its own index.
+        let mut data = vec![0; 3 * 10 * 4];
+        for (index, pixel) in data.chunks_exact_mut(4).enumerate().take(3 * 6) {
+            pixel.copy_from_slice(&[(index / 3) as u8, 0, 0, 255]);
+        }
+        mirror.mirror_rows(&mut data, &view);
+        let rows: Vec<u8> = data.chunks_exact(3 * 4).map(|row| row[0]).collect();
+        assert_eq!(rows, vec![0, 1, 2, 3, 4, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn real_axis_mirror_above_axis() {
+        // The axis is between rows 1 and 2, so rows 0 and 1 mirror rows 3 and
+        // 2.
+        let view = View::new_uniform(2, 8, 2.0, 0.0, 2.5);
+        let mirror = view.real_axis_mirror().unwrap();
+        assert_eq!(mirror.row_sum, 3);
+        assert_eq!(mirror.rendered.image_y, 2);
+        assert_eq!(mirror.rendered.image_height, 6);
+        assert_eq!(
+            mirror.rendered.get_local_plane_coordinates((0, 0)),
+            view.get_local_plane_coordinates((0, 2))
+        );
+
+        let mut data = vec![0; 2 * 8 * 4];
+        for (index, pixel) in data.chunks_exact_mut(4).enumerate().skip(2 * 2) {
+            pixel.copy_from_slice(&[(index / 2) as u8, 0, 0, 255]);
+        }
+        mirror.mirror_rows(&mut data, &view);
+        let rows: Vec<u8> = data.chunks_exact(2 * 4).map(|row| row[0]).collect();
+        assert_eq!(rows, vec![3, 2, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn real_axis_mirror_misses() {
+        // The axis is off the pixel grid.
+        assert_eq!(
+            View::new_uniform(10, 10, 10.0, 0.0, 0.3).real_axis_mirror(),
+            None
+        );
+        // The axis is above or below the view.
+        assert_eq!(
+            View::new_uniform(10, 10, 10.0, 0.0, 6.0).real_axis_mirror(),
+            None
+        );
+        assert_eq!(
+            View::new_uniform(10, 10, 10.0, 0.0, -20.0).real_axis_mirror(),
+            None
+        );
+    }
+
+    #[test]
+    fn point_reflection() {
+        // 0 is at the corner of pixel (2, 3), so pixels whose coordinates
+        // add up to (4, 6) are reflections, leaving column 0 of rows 4 and 5
+        // with no reflection in the view.
+        let view = View::new_centered_uniform(4, 6, 4.0);
+        let reflection = view.point_reflection().unwrap();
+        assert_eq!(reflection.row_sum, 6);
+        assert_eq!(reflection.column_sum, 4);
+        let regions: Vec<_> = reflection
+            .rendered
+            .iter()
+            .chain(Some(&reflection.reflected))
+            .map(|region| {
+                (
+                    region.image_x,
+                    region.image_y,
+                    region.image_width,
+                    region.image_height,
+                )
+            })
+            .collect();
+        assert_eq!(regions, vec![(0, 0, 4, 4), (0, 4, 1, 2), (1, 4, 3, 2)]);
+        assert_eq!(
+            reflection.rendered[1].get_local_plane_coordinates((0, 0)),
+            view.get_local_plane_coordinates((0, 4))
+        );
+
+        // Each rendered pixel is filled with its own coordinates.
+        let mut data = vec![0; 4 * 6 * 4];
+        for region in &reflection.rendered {
+            for y in region.image_y..region.image_y + region.image_height {
+                for x in region.image_x..region.image_x + region.image_width {
+                    let index = (y * 4 + x) * 4;
+                    data[index..index + 4].copy_from_slice(&[x as u8, y as u8, 0, 255]);
+                }
+            }
+        }
+        reflection.reflect_pixels(&mut data, &view);
+        for y in 4..6 {
+            for x in 1..4 {
+                let index = (y * 4 + x) * 4;
+                assert_eq!(
+                    data[index..index + 4],
+                    [4 - x as u8, 6 - y as u8, 0, 255],
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn point_reflection_misses() {
+        // 0 is off the pixel grid horizontally.
+        assert_eq!(
+            View::new_uniform(10, 10, 10.0, 0.3, 0.0).point_reflection(),
+            None
+        );
+        // 0 is left of the view.
+        assert_eq!(
+            View::new_uniform(10, 10, 10.0, 20.0, 0.0).point_reflection(),
+            None
+        );
+    }
 }